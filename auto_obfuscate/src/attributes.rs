@@ -0,0 +1,188 @@
+use syn::{ Attribute, ImplItem, Item, LitBool, LitInt, TraitItem };
+use proc_macro2::{ Delimiter, Group, TokenStream, TokenTree };
+
+#[cfg(test)]
+mod attributes_tests;
+
+//a pass `#[obfuscate(...)]` can turn off for an item
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pass {
+    Strings,
+    Ints,
+    Flow,
+    Rename,
+}
+
+const ALL_PASSES: [Pass; 4] = [Pass::Strings, Pass::Ints, Pass::Flow, Pass::Rename];
+
+impl Pass {
+    //`no_strings` or `strings = false`
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "strings" => Some(Self::Strings),
+            "ints" => Some(Self::Ints),
+            "flow" => Some(Self::Flow),
+            "rename" => Some(Self::Rename),
+            _ => None,
+        }
+    }
+}
+
+//`#[obfuscate(...)]`, or `#[cryptify::obfuscate(...)]` written out
+fn is_obfuscate_attribute(attr: &Attribute) -> bool {
+    let path: Vec<String> = attr.path().segments.iter().map(|segment| segment.ident.to_string()).collect();
+    is_obfuscate_path(&path.join("::"))
+}
+
+fn is_obfuscate_path(path: &str) -> bool {
+    path == "obfuscate" || path == "cryptify::obfuscate"
+}
+
+//what the `#[obfuscate(...)]` attributes of an item, or of a `mod` declaration and the ones
+//enclosing it, set
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObfuscateOptions {
+    //`skip` turns off all passes, `no_strings`, `no_ints`, `no_flow`, `no_rename` or `rename = false` one
+    pub disabled: Vec<Pass>,
+    //`flow_density = 50`: percentage of blocks and statements the flow pass injects code into
+    pub flow_density: Option<u8>,
+}
+
+impl ObfuscateOptions {
+    //the options among `attrs`, unknown options are ignored
+    pub fn parse(attrs: &[Attribute]) -> Self {
+        let mut options = Self::default();
+        for attr in attrs.iter().filter(|attr| is_obfuscate_attribute(attr)) {
+            let parsed = attr.parse_nested_meta(|meta| {
+                let Some(name) = meta.path.get_ident().map(|ident| ident.to_string()) else {
+                    return Ok(());
+                };
+                if meta.input.peek(syn::Token![=]) && name == "flow_density" {
+                    let density: LitInt = meta.value()?.parse()?;
+                    match density.base10_parse::<u8>() {
+                        Ok(density) if density <= 100 => options.flow_density = Some(density),
+                        _ => return Err(meta.error("flow_density: expected integer between 0 and 100")),
+                    }
+                } else if meta.input.peek(syn::Token![=]) {
+                    let enabled: LitBool = meta.value()?.parse()?;
+                    if let Some(pass) = Pass::from_name(&name).filter(|_| !enabled.value) {
+                        options.disabled.push(pass);
+                    }
+                } else if name == "skip" {
+                    options.disabled.extend(ALL_PASSES);
+                } else if let Some(pass) = name.strip_prefix("no_").and_then(Pass::from_name) {
+                    options.disabled.push(pass);
+                }
+                Ok(())
+            });
+            if let Err(err) = parsed {
+                eprintln!("Ignoring malformed obfuscate attribute: {}", err);
+            }
+        }
+        options
+    }
+
+    //the options of a module declared with `attrs` inside one with these options
+    pub fn nested(&self, attrs: &[Attribute]) -> Self {
+        let mut options = Self::parse(attrs);
+        options.disabled.extend(&self.disabled);
+        options.flow_density = options.flow_density.or(self.flow_density);
+        options
+    }
+}
+
+//passes the `#[obfuscate(...)]` attributes among `attrs` turn off
+pub fn disabled_passes(attrs: &[Attribute]) -> Vec<Pass> {
+    ObfuscateOptions::parse(attrs).disabled
+}
+
+pub fn is_disabled(attrs: &[Attribute], pass: Pass) -> bool {
+    disabled_passes(attrs).contains(&pass)
+}
+
+pub fn item_attrs(item: &Item) -> &[Attribute] {
+    match item {
+        Item::Const(item) => &item.attrs,
+        Item::Enum(item) => &item.attrs,
+        Item::ExternCrate(item) => &item.attrs,
+        Item::Fn(item) => &item.attrs,
+        Item::ForeignMod(item) => &item.attrs,
+        Item::Impl(item) => &item.attrs,
+        Item::Macro(item) => &item.attrs,
+        Item::Mod(item) => &item.attrs,
+        Item::Static(item) => &item.attrs,
+        Item::Struct(item) => &item.attrs,
+        Item::Trait(item) => &item.attrs,
+        Item::TraitAlias(item) => &item.attrs,
+        Item::Type(item) => &item.attrs,
+        Item::Union(item) => &item.attrs,
+        Item::Use(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+pub fn impl_item_attrs(item: &ImplItem) -> &[Attribute] {
+    match item {
+        ImplItem::Const(item) => &item.attrs,
+        ImplItem::Fn(item) => &item.attrs,
+        ImplItem::Type(item) => &item.attrs,
+        ImplItem::Macro(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+pub fn trait_item_attrs(item: &TraitItem) -> &[Attribute] {
+    match item {
+        TraitItem::Const(item) => &item.attrs,
+        TraitItem::Fn(item) => &item.attrs,
+        TraitItem::Type(item) => &item.attrs,
+        TraitItem::Macro(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+//remove every `#[obfuscate(...)]` attribute from the code, once all passes have seen them
+pub fn strip_obfuscate_attributes(code: &str) -> String {
+    match code.parse::<TokenStream>() {
+        Ok(tokens) => strip_tokens(tokens).to_string(),
+        Err(_) => code.to_string(),
+    }
+}
+
+fn strip_tokens(tokens: TokenStream) -> TokenStream {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    let mut output = Vec::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        //`#[...]`, or `#![...]` for an inner attribute
+        let is_punct = |index: usize, ch: char| matches!(tokens.get(index), Some(TokenTree::Punct(punct)) if punct.as_char() == ch);
+        let body = if is_punct(index + 1, '!') { index + 2 } else { index + 1 };
+        let is_attribute = is_punct(index, '#') &&
+            matches!(tokens.get(body), Some(TokenTree::Group(group)) if is_obfuscate_body(group));
+        if is_attribute {
+            index = body + 1;
+            continue;
+        }
+        output.push(match &tokens[index] {
+            TokenTree::Group(group) => {
+                let mut new_group = Group::new(group.delimiter(), strip_tokens(group.stream()));
+                new_group.set_span(group.span());
+                TokenTree::Group(new_group)
+            }
+            token => token.clone(),
+        });
+        index += 1;
+    }
+    output.into_iter().collect()
+}
+
+//whether the `[...]` of an attribute starts with the path of ours
+fn is_obfuscate_body(group: &Group) -> bool {
+    let path: String = group
+        .stream()
+        .into_iter()
+        .take_while(|token| !matches!(token, TokenTree::Group(_)))
+        .map(|token| token.to_string())
+        .collect();
+    group.delimiter() == Delimiter::Bracket && is_obfuscate_path(&path)
+}
@@ -0,0 +1,165 @@
+#[cfg(test)]
+use super::*;
+use crate::obfuscate::{ Config, Obfuscator };
+
+fn disabled(attrs: &str) -> Vec<Pass> {
+    let item: syn::ItemFn = syn::parse_str(&format!("{} fn f() {{}}", attrs)).unwrap();
+    disabled_passes(&item.attrs)
+}
+
+#[test]
+fn test_disabled_passes() {
+    assert_eq!(disabled("#[obfuscate(skip)]"), ALL_PASSES);
+    assert_eq!(disabled("#[cryptify::obfuscate(no_flow, no_strings)]"), [Pass::Flow, Pass::Strings]);
+    assert_eq!(disabled("#[obfuscate(rename = false)] #[obfuscate(no_ints)]"), [Pass::Rename, Pass::Ints]);
+    assert_eq!(disabled("#[obfuscate(rename = true, flow = false)]"), [Pass::Flow]);
+    assert_eq!(disabled("#[obfuscate(unknown)] #[inline] #[other::obfuscate(skip)]"), []);
+}
+
+#[test]
+fn test_flow_density_option() {
+    let options = |attrs: &str| {
+        let item: syn::ItemFn = syn::parse_str(&format!("{} fn f() {{}}", attrs)).unwrap();
+        ObfuscateOptions::parse(&item.attrs)
+    };
+    let dense = options("#[obfuscate(flow_density = 80, no_strings)]");
+    assert_eq!(dense, ObfuscateOptions { disabled: vec![Pass::Strings], flow_density: Some(80) });
+    assert_eq!(options("#[obfuscate(flow_density = 101)]").flow_density, None);
+
+    //modules inherit the density of the enclosing one unless they set their own
+    let item: syn::ItemMod = syn::parse_str("#[obfuscate(no_rename)] mod inner {}").unwrap();
+    let inner = dense.nested(&item.attrs);
+    assert_eq!(inner.flow_density, Some(80));
+    assert_eq!(inner.disabled, [Pass::Rename, Pass::Strings]);
+    let item: syn::ItemMod = syn::parse_str("#[obfuscate(flow_density = 0)] mod inner {}").unwrap();
+    assert_eq!(dense.nested(&item.attrs).flow_density, Some(0));
+}
+
+#[test]
+fn test_strip_obfuscate_attributes() {
+    let code = r#"
+        #[obfuscate(skip)]
+        #[no_mangle]
+        pub extern "C" fn exported() {}
+        mod inner {
+            #[cryptify::obfuscate(rename = false)]
+            fn kept() {}
+        }
+        mod opted_out {
+            #![obfuscate(skip)]
+            #![allow(dead_code)]
+            fn untouched() {}
+        }
+    "#;
+    let expected: syn::File = syn::parse_str(
+        r#"
+        #[no_mangle]
+        pub extern "C" fn exported() {}
+        mod inner {
+            fn kept() {}
+        }
+        mod opted_out {
+            #![allow(dead_code)]
+            fn untouched() {}
+        }
+    "#
+    ).unwrap();
+    let stripped: syn::File = syn::parse_str(&strip_obfuscate_attributes(code)).unwrap();
+    assert_eq!(quote::quote!(#stripped).to_string(), quote::quote!(#expected).to_string());
+}
+
+#[test]
+fn test_opted_out_items_are_left_alone() {
+    let code = r#"
+        #[obfuscate(skip)]
+        #[no_mangle]
+        extern "C" fn ffi_entry(count: i32) -> i32 {
+            let label = "ffi";
+            helper(count) + 7
+        }
+        #[obfuscate(no_strings, no_flow)]
+        fn plain(value: i32) -> &'static str {
+            if value > 2 { "big" } else { "small" }
+        }
+        fn helper(input: i32) -> i32 {
+            let doubled = input * 2;
+            println!("{}", "helper");
+            doubled
+        }
+        fn main() {
+            println!("{} {}", ffi_entry(1), plain(3));
+        }
+    "#;
+    let mut config = Config::default();
+    config.rename_config.enable_rename_obfuscation = true;
+    config.int_config.enable_int_obfuscation = true;
+    config.flow_config.density = 100;
+    config.seed = Some(1);
+    let mut obfuscator = Obfuscator::from_config(config);
+    let obfuscated = obfuscator.obfuscate(code);
+    let file = syn::parse_file(&obfuscated).expect("obfuscated code is not valid Rust code");
+    let item_fn = |index: usize| match &file.items[index] {
+        Item::Fn(item_fn) => item_fn.clone(),
+        _ => panic!("expected a fn"),
+    };
+
+    //everything but the call of the renamed helper stays as it was
+    let ffi_entry = item_fn(0);
+    let renamed_helper = item_fn(2).sig.ident;
+    let expected: syn::ItemFn = syn::parse_quote! {
+        #[no_mangle]
+        extern "C" fn ffi_entry(count: i32) -> i32 {
+            let label = "ffi";
+            #renamed_helper(count) + 7
+        }
+    };
+    assert_eq!(quote::quote!(#ffi_entry).to_string(), quote::quote!(#expected).to_string());
+    assert_ne!(renamed_helper, "helper");
+
+    //only the strings and flow are left alone
+    let plain = item_fn(1);
+    let plain = quote::quote!(#plain).to_string();
+    assert!(plain.contains("\"big\"") && !plain.contains("flow_stmt"), "{}", plain);
+    assert!(plain.contains("encrypt_int") && !plain.contains("value"), "{}", plain);
+
+    let helper = item_fn(2);
+    let helper = quote::quote!(#helper).to_string();
+    assert!(helper.contains("flow_stmt") && helper.contains("encrypt_int"), "{}", helper);
+    assert!(helper.contains("encrypt_string ! (\"helper\")"), "{}", helper);
+    assert!(!obfuscated.contains("obfuscate ("), "{}", obfuscated);
+}
+
+#[test]
+fn test_file_attributes_apply_to_the_whole_file() {
+    let obfuscate = |code: &str| {
+        let mut config = Config::default();
+        config.rename_config.enable_rename_obfuscation = true;
+        config.int_config.enable_int_obfuscation = true;
+        config.flow_config.density = 100;
+        config.seed = Some(1);
+        Obfuscator::from_config(config).obfuscate(code)
+    };
+    let body = r#"
+        fn helper(input: i32) -> i32 {
+            let doubled = input * 2;
+            println!("{}", "helper");
+            doubled
+        }
+        fn main() {
+            println!("{}", helper(3));
+        }
+    "#;
+
+    //`skip` leaves the file as it was, without the attribute
+    let skipped = obfuscate(&format!("#![obfuscate(skip)]\n{}", body));
+    let skipped = syn::parse_file(&skipped).expect("obfuscated code is not valid Rust code");
+    let expected = syn::parse_file(body).unwrap();
+    assert_eq!(quote::quote!(#skipped).to_string(), quote::quote!(#expected).to_string());
+
+    //the other options hold for every item of the file
+    let obfuscated = obfuscate(&format!("#![cryptify::obfuscate(no_strings, flow_density = 0)]\n{}", body));
+    assert!(obfuscated.contains("\"helper\"") && !obfuscated.contains("encrypt_string"), "{}", obfuscated);
+    assert!(!obfuscated.contains("flow_stmt"), "{}", obfuscated);
+    assert!(obfuscated.contains("encrypt_int") && !obfuscated.contains("doubled"), "{}", obfuscated);
+    assert!(!obfuscated.contains("obfuscate ("), "{}", obfuscated);
+}
@@ -1,7 +1,34 @@
 use quote::quote;
-use rand::seq::SliceRandom;
-use rand::{thread_rng, Rng};
-use syn::{parse_file, parse_quote, visit_mut::VisitMut, Block, Expr, Pat, PatIdent, Stmt};
+use crate::obfuscate::seeded_rng;
+use rand::rngs::StdRng;
+use rand::Rng;
+use proc_macro2::{ TokenStream, TokenTree };
+use quote::ToTokens;
+use std::collections::HashSet;
+use syn::{
+    parse_file,
+    parse_quote,
+    visit_mut::VisitMut,
+    Block,
+    Expr,
+    ExprConst,
+    ImplItem,
+    ImplItemConst,
+    ImplItemFn,
+    Item,
+    ItemConst,
+    ItemFn,
+    ItemStatic,
+    Stmt,
+    TraitItem,
+    TraitItemConst,
+    TraitItemFn,
+};
+use labyrinth_core::junk::{ junk_block, junk_stmts };
+use crate::attributes::{impl_item_attrs, item_attrs, trait_item_attrs, ObfuscateOptions, Pass};
+use crate::string::format::is_format_macro;
+
+pub mod flatten;
 
 #[cfg(test)]
 mod flow_tests;
@@ -10,117 +37,302 @@ mod flow_tests;
 pub struct FlowConfig {
     pub enable_flow_obfuscation: bool,
     pub use_macro: bool,
+    //wrap statements in branches guarded by opaque predicates
+    pub opaque_predicates: bool,
+    //rewrite function bodies into `loop { match state { ... } }` dispatchers
+    pub flatten: bool,
+    //percentage of blocks that get a block of junk code, and of statements wrapped in an opaque branch
+    pub density: u8,
+    //at most this many junk blocks and opaque branches in one function
+    pub max_per_function: Option<usize>,
+    //injected code may grow a file by at most this percentage of its size, counted in tokens
+    pub size_budget: Option<usize>,
+    pub seed: Option<u64>,
 }
 impl FlowConfig {
     pub fn default() -> Self {
         Self {
             enable_flow_obfuscation: true,
             use_macro: true,
+            opaque_predicates: false,
+            flatten: false,
+            density: 33,
+            max_per_function: None,
+            size_budget: None,
+            seed: None,
         }
     }
 }
 
 pub struct FlowObfuscator {
-    loop_counter: u32,
     pub use_macro: bool,
     pub enabled: bool,
+    opaque_predicates: bool,
+    flatten: bool,
+    //density of the item being visited, `#[obfuscate(flow_density = ...)]` overrides the configured one
+    density: u8,
+    max_per_function: Option<usize>,
+    size_budget: Option<usize>,
+    //junk blocks and opaque branches injected into the function being visited
+    function_injections: usize,
+    //tokens injected into the file, and how many the size budget allows
+    injected_size: usize,
+    size_limit: Option<usize>,
+    //inside const fns and const initializers, where `black_box` can't be called
+    const_depth: usize,
+    //the `let x;` locals of the function being visited
+    deferred: HashSet<String>,
+    rng: StdRng,
 }
 
 impl FlowObfuscator {
     pub fn new(config: FlowConfig) -> Self {
         Self {
-            loop_counter: 0,
             use_macro: config.use_macro,
             enabled: config.enable_flow_obfuscation,
+            opaque_predicates: config.opaque_predicates,
+            flatten: config.flatten,
+            density: config.density.min(100),
+            max_per_function: config.max_per_function,
+            size_budget: config.size_budget,
+            function_injections: 0,
+            injected_size: 0,
+            size_limit: None,
+            const_depth: 0,
+            deferred: HashSet::new(),
+            rng: seeded_rng(config.seed),
         }
     }
     pub fn flow_obfuscate(&mut self, code: &str) -> String {
         let ast = parse_file(code).expect("Failed to parse code");
         let mut modified_ast = ast.clone();
-        self.visit_file_mut(&mut modified_ast);
-        let modified_code = quote!(#modified_ast).to_string();
-        modified_code
-    }
-    //check to see if statement in block is dummy loop
-    fn is_dummy_loop(stmt: &Stmt) -> bool {
-        if let Stmt::Expr(Expr::Block(expr_block), _) = stmt {
-            for stmt in &expr_block.block.stmts {
-                if let Stmt::Local(local) = stmt {
-                    if let Pat::Ident(PatIdent { ident, .. }) = &local.pat {
-                        if ident == "_is_dummy_145" {
-                            return true;
-                        }
-                    }
-                }
-            }
+        self.injected_size = 0;
+        self.size_limit = self.size_budget.map(|percent| token_count(ast.to_token_stream()) * percent / 100);
+        //inner `#![obfuscate(...)]` attributes of the file apply to all of it
+        let options = ObfuscateOptions::parse(&ast.attrs);
+        self.visit_with_options(options, |this| this.visit_file_mut(&mut modified_ast));
+        quote!(#modified_ast).to_string()
+    }
+    //whether to inject into the next block or statement, as often as the density says
+    fn roll(&mut self) -> bool {
+        self.rng.gen_ratio(self.density as u32, 100)
+    }
+    //whether `size` more tokens still fit the limit per function and the size budget, which
+    //they are counted against if they do
+    fn fits(&mut self, size: usize) -> bool {
+        let within_function = self.max_per_function.is_none_or(|max| self.function_injections < max);
+        let within_budget = self.size_limit.is_none_or(|limit| self.injected_size + size <= limit);
+        if within_function && within_budget {
+            self.function_injections += 1;
+            self.injected_size += size;
         }
-
-        false
-    }
-    //helper to generate random dummy loop
-    fn generate_dummy_loop() -> Stmt {
-        let mut rng = thread_rng();
-
-        let initial_value = rng.gen_range(1..=10);
-        let increment_value = rng.gen_range(1..=5);
-        let upper_bound = rng.gen_range(50..=100);
-        let add_extra_dummy_variable = rng.gen_bool(0.5);
-
-        let mut statements = vec![
-            quote! { let mut _dummy_counter = std::hint::black_box(#initial_value as i32); },
-            quote! { let _dummy_increment = std::hint::black_box(#increment_value as i32); },
-            quote! { let _dummy_upper_bound = std::hint::black_box(#upper_bound as i32); },
-        ];
-
-        //add extra dummy variable occasionally
-        if add_extra_dummy_variable {
-            let extra_dummy_value = rng.gen_range(1..=10);
-            statements.push(
-                quote! { let _extra_dummy_var = std::hint::black_box(#extra_dummy_value as i32); },
-            );
+        within_function && within_budget
+    }
+    //put a block of junk code at the start of a block. The templates use loops and calls const
+    //code can't make
+    fn insert_junk(&mut self, block: &mut Block) {
+        if self.const_depth > 0 || !self.roll() {
+            return;
         }
-
-        //randomize the order of variable assignments
-        statements.shuffle(&mut rng);
-
-        let loop_block = quote! {
-            loop {
-                if std::hint::black_box(_dummy_counter) > std::hint::black_box(_dummy_upper_bound) {
-                    break;
-                }
-                _dummy_counter = std::hint::black_box(std::hint::black_box(_dummy_counter) + std::hint::black_box(_dummy_increment));
+        //if use macro enabled, use macro to expand to junk code
+        let junk: Stmt = if self.use_macro {
+            syn::parse_quote! {
+                cryptify::flow_stmt!();
             }
+        } else {
+            let junk = junk_block(&mut self.rng);
+            syn::parse_quote! { #junk }
         };
-
-        parse_quote! {
-            {
-                let _is_dummy_145 = true;
-                #(#statements)*
-                #loop_block
+        if self.fits(token_count(junk.to_token_stream())) {
+            block.stmts.insert(0, junk);
+        }
+    }
+    //whether a statement can be moved into the arm of a branch without changing what it means.
+    //`let`s would go out of scope and items out of reach, and `x = ...` could be the deferred
+    //initialization of a `let x;`, at the top of the statement or nested in it. Other macros than
+    //the format ones (`println!`, `assert!`, ...) may expand to either, like `thread_local!` does.
+    //The last statement is the value of the block, or ends it by diverging like the `return`
+    //closing a `let ... else`, which the branch would hide
+    fn can_wrap(&self, stmt: &Stmt, is_last: bool) -> bool {
+        match stmt {
+            _ if is_last => false,
+            _ if flatten::assigns_deferred(stmt, &self.deferred) => false,
+            Stmt::Expr(Expr::Assign(assign), _) => !matches!(&*assign.left, Expr::Path(path) if path.path.get_ident().is_some()),
+            Stmt::Expr(..) => true,
+            Stmt::Macro(stmt_macro) => is_format_macro(&stmt_macro.mac),
+            Stmt::Local(_) | Stmt::Item(_) => false,
+        }
+    }
+    //wrap some of the statements of a block in `if` branches whose condition always has the same
+    //value, putting dead code in the arm that is never taken
+    fn insert_opaque_predicates(&mut self, block: &mut Block) {
+        let count = block.stmts.len();
+        let stmts = std::mem::take(&mut block.stmts);
+        for (index, stmt) in stmts.into_iter().enumerate() {
+            let is_last = index + 1 == count;
+            if !self.can_wrap(&stmt, is_last) || !self.roll() {
+                block.stmts.push(stmt);
+                continue;
+            }
+            let size = token_count(stmt.to_token_stream());
+            let branch = self.generate_opaque_branch(stmt.clone());
+            if self.fits(token_count(branch.to_token_stream()) - size) {
+                block.stmts.push(branch);
+            } else {
+                block.stmts.push(stmt);
+            }
+        }
+    }
+    fn generate_opaque_branch(&mut self, stmt: Stmt) -> Stmt {
+        let (always_true, always_false) = self.generate_opaque_predicate();
+        let dead_code = junk_stmts(&mut self.rng);
+        if self.rng.gen_bool(0.5) {
+            parse_quote! {
+                if #always_true { #stmt } else { #dead_code }
+            }
+        } else {
+            parse_quote! {
+                if #always_false { #dead_code } else { #stmt }
+            }
+        }
+    }
+    //helper to generate a predicate over `black_box`ed values from a number-theoretic identity,
+    //returned as a condition that always holds and one that never does. The values stay below
+    //2^16 so none of the arithmetic can overflow
+    fn generate_opaque_predicate(&mut self) -> (TokenStream, TokenStream) {
+        let rng = &mut self.rng;
+        let x = rng.gen_range(1u64..=0xffff);
+        let y = rng.gen_range(1u64..=0xffff);
+        let value = quote! { let v = std::hint::black_box(#x); };
+        match rng.gen_range(0..5) {
+            //the product of two consecutive integers is even
+            0 => (quote! { { #value (v * (v + 1)) % 2 == 0 } }, quote! { { #value (v * (v + 1)) % 2 == 1 } }),
+            //so is the product of three consecutive ones divisible by 3
+            1 => (quote! { { #value (v * v * v - v) % 3 == 0 } }, quote! { { #value (v * v * v - v) % 3 != 0 } }),
+            //a square is 0 or 1 mod 4
+            2 => (quote! { { #value (v * v) % 4 < 2 } }, quote! { { #value (v * v) % 4 == 2 } }),
+            //and 0 or 1 mod 3, so one more than a square never is a multiple of 3
+            3 => (quote! { { #value (v * v + 1) % 3 != 0 } }, quote! { { #value (v * v + 1) % 3 == 0 } }),
+            //a sum of two squares is never 3 mod 4
+            _ => {
+                let value = quote! { #value let w = std::hint::black_box(#y); };
+                (quote! { { #value (v * v + w * w) % 4 != 3 } }, quote! { { #value (v * v + w * w) % 4 == 3 } })
             }
         }
     }
 }
 
-impl VisitMut for FlowObfuscator {
-    fn visit_block_mut(&mut self, block: &mut Block) {
-        //check if the block already contains the dummy loop
-        if block.stmts.iter().any(|stmt| Self::is_dummy_loop(stmt)) || self.loop_counter % 3 != 0 {
-            self.loop_counter += 1;
+impl FlowObfuscator {
+    //visit an item unless `#[obfuscate(...)]` turned the pass off for it, with its density
+    fn visit_with_options(&mut self, options: ObfuscateOptions, visit: impl FnOnce(&mut Self)) {
+        if options.disabled.contains(&Pass::Flow) {
             return;
         }
-        //if use macro enabled, use macro to expand to dummy loop
-        if self.use_macro {
-            let macro_call = syn::parse_quote! {
-                cryptify::flow_stmt!();
-            };
-            block.stmts.insert(0, macro_call);
-        } else {
-            let dummy_loop = Self::generate_dummy_loop();
-            block.stmts.insert(0, dummy_loop);
+        let density = self.density;
+        self.density = options.flow_density.unwrap_or(density);
+        visit(self);
+        self.density = density;
+    }
+
+    //visit a function, which gets a limit per function of its own
+    fn visit_function(&mut self, is_const: bool, deferred: HashSet<String>, visit: impl FnOnce(&mut Self)) {
+        let injections = std::mem::take(&mut self.function_injections);
+        let outer_deferred = std::mem::replace(&mut self.deferred, deferred);
+        self.const_depth += is_const as usize;
+        visit(self);
+        self.const_depth -= is_const as usize;
+        self.deferred = outer_deferred;
+        self.function_injections = injections;
+    }
+
+    fn visit_const_context(&mut self, visit: impl FnOnce(&mut Self)) {
+        self.const_depth += 1;
+        visit(self);
+        self.const_depth -= 1;
+    }
+}
+
+fn token_count(tokens: TokenStream) -> usize {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Group(group) => 1 + token_count(group.stream()),
+            _ => 1,
+        })
+        .sum()
+}
+
+impl VisitMut for FlowObfuscator {
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        let options = ObfuscateOptions::parse(item_attrs(item));
+        self.visit_with_options(options, |this| syn::visit_mut::visit_item_mut(this, item));
+    }
+
+    fn visit_impl_item_mut(&mut self, item: &mut ImplItem) {
+        let options = ObfuscateOptions::parse(impl_item_attrs(item));
+        self.visit_with_options(options, |this| syn::visit_mut::visit_impl_item_mut(this, item));
+    }
+
+    fn visit_trait_item_mut(&mut self, item: &mut TraitItem) {
+        let options = ObfuscateOptions::parse(trait_item_attrs(item));
+        self.visit_with_options(options, |this| syn::visit_mut::visit_trait_item_mut(this, item));
+    }
+
+    fn visit_item_fn_mut(&mut self, item: &mut ItemFn) {
+        if self.flatten {
+            flatten::flatten_body(&mut item.block, &item.sig, &mut self.rng);
+        }
+        let is_const = item.sig.constness.is_some();
+        let deferred = flatten::deferred_locals(&item.block);
+        self.visit_function(is_const, deferred, |this| syn::visit_mut::visit_item_fn_mut(this, item));
+    }
+
+    fn visit_impl_item_fn_mut(&mut self, item: &mut ImplItemFn) {
+        if self.flatten {
+            flatten::flatten_body(&mut item.block, &item.sig, &mut self.rng);
         }
+        let is_const = item.sig.constness.is_some();
+        let deferred = flatten::deferred_locals(&item.block);
+        self.visit_function(is_const, deferred, |this| syn::visit_mut::visit_impl_item_fn_mut(this, item));
+    }
+
+    fn visit_trait_item_fn_mut(&mut self, item: &mut TraitItemFn) {
+        if let Some(block) = item.default.as_mut().filter(|_| self.flatten) {
+            flatten::flatten_body(block, &item.sig, &mut self.rng);
+        }
+        let is_const = item.sig.constness.is_some();
+        let deferred = item.default.as_ref().map(flatten::deferred_locals).unwrap_or_default();
+        self.visit_function(is_const, deferred, |this| syn::visit_mut::visit_trait_item_fn_mut(this, item));
+    }
+
+    fn visit_item_const_mut(&mut self, item: &mut ItemConst) {
+        self.visit_const_context(|this| syn::visit_mut::visit_item_const_mut(this, item));
+    }
+
+    fn visit_item_static_mut(&mut self, item: &mut ItemStatic) {
+        self.visit_const_context(|this| syn::visit_mut::visit_item_static_mut(this, item));
+    }
 
-        self.loop_counter += 1;
+    fn visit_impl_item_const_mut(&mut self, item: &mut ImplItemConst) {
+        self.visit_const_context(|this| syn::visit_mut::visit_impl_item_const_mut(this, item));
+    }
+
+    fn visit_trait_item_const_mut(&mut self, item: &mut TraitItemConst) {
+        self.visit_const_context(|this| syn::visit_mut::visit_trait_item_const_mut(this, item));
+    }
+
+    fn visit_expr_const_mut(&mut self, expr: &mut ExprConst) {
+        self.visit_const_context(|this| syn::visit_mut::visit_expr_const_mut(this, expr));
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        //every nested block gets its own chance, whatever happens to this one
         syn::visit_mut::visit_block_mut(self, block);
+        //inject once the blocks nested in this one were visited, so the generated code isn't
+        //visited again
+        if self.opaque_predicates && self.const_depth == 0 {
+            self.insert_opaque_predicates(block);
+        }
+        self.insert_junk(block);
     }
 }
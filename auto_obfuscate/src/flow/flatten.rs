@@ -0,0 +1,830 @@
+use crate::string::format::is_format_macro;
+use proc_macro2::{ Span, TokenStream, TokenTree };
+use quote::{ format_ident, quote };
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::collections::HashSet;
+use syn::{
+    parse_quote,
+    visit::{ self, Visit },
+    visit_mut::{ self as visit_mut, VisitMut },
+    BinOp,
+    Block,
+    Expr,
+    FnArg,
+    Ident,
+    Item,
+    Lifetime,
+    Local,
+    Macro,
+    Pat,
+    PatIdent,
+    ReturnType,
+    Signature,
+    Stmt,
+    Type,
+};
+
+//methods taking `self` by value, so calling them on a local moves it
+const CONSUMING_METHODS: &[&str] = &[
+    "expect",
+    "ok",
+    "err",
+    "and_then",
+    "or_else",
+    "zip",
+    "chain",
+    "enumerate",
+    "rev",
+    "sum",
+    "product",
+    "fold",
+    "collect",
+    "count",
+    "last",
+    "join",
+    "min",
+    "max",
+];
+const CONSUMING_PREFIXES: &[&str] = &["into", "unwrap", "map", "ok_or", "filter", "skip", "take", "flat"];
+
+//methods whose result owns its data, whatever the receiver borrows
+const OWNED_METHODS: &[&str] = &[
+    "len",
+    "is_empty",
+    "clone",
+    "cloned",
+    "copied",
+    "to_string",
+    "to_owned",
+    "to_vec",
+    "count",
+];
+
+//macros that never return
+const DIVERGING_MACROS: &[&str] = &["panic", "unreachable", "todo", "unimplemented"];
+
+//rewrite the statements of a function body into `loop { match state { ... } }` dispatchers.
+//Statements can't be moved away from the `let`s whose bindings they use, so every run of
+//statements between two `let`s (or items) gets its own dispatcher. The compiler sees every arm
+//of a dispatcher as reachable from every other, so runs that move a local, or use one holding a
+//borrow, are left as they are
+pub fn flatten_body(block: &mut Block, sig: &Signature, rng: &mut StdRng) {
+    let mut used = HashSet::new();
+    collect_idents(quote!(#sig #block), &mut used);
+    let state = format_ident!("{}", fresh_name("state", &used));
+    let label = Lifetime::new(&format!("'{}", fresh_name("dispatch", &used)), Span::call_site());
+
+    let mut locals = Locals::from_signature(sig);
+    let mut stmts = std::mem::take(&mut block.stmts);
+    let tail = match stmts.last() {
+        Some(Stmt::Expr(_, None)) => stmts.pop(),
+        _ => None,
+    };
+    //without a tail, a function returning a value has to end by diverging
+    let needs_divergence = tail.is_none() && !returns_unit(&sig.output);
+
+    let mut segment = Vec::new();
+    for stmt in stmts {
+        match stmt {
+            Stmt::Local(_) | Stmt::Item(_) => {
+                let dispatcher = Dispatcher::new(rng, &state, &label);
+                block.stmts.extend(dispatcher.flatten(std::mem::take(&mut segment), &locals, false));
+                if let Stmt::Local(local) = &stmt {
+                    locals.bind(local);
+                }
+                block.stmts.push(stmt);
+            }
+            stmt => segment.push(stmt),
+        }
+    }
+    let dispatcher = Dispatcher::new(rng, &state, &label);
+    block.stmts.extend(dispatcher.flatten(segment, &locals, needs_divergence));
+    block.stmts.extend(tail);
+}
+
+fn returns_unit(output: &ReturnType) -> bool {
+    match output {
+        ReturnType::Default => true,
+        ReturnType::Type(_, ty) => matches!(&**ty, Type::Tuple(tuple) if tuple.elems.is_empty()),
+    }
+}
+
+fn collect_idents(tokens: TokenStream, idents: &mut HashSet<String>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => {
+                idents.insert(ident.to_string());
+            }
+            TokenTree::Group(group) => collect_idents(group.stream(), idents),
+            _ => {}
+        }
+    }
+}
+
+fn mentions(tokens: TokenStream, names: &HashSet<String>) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => names.contains(&ident.to_string()),
+        TokenTree::Group(group) => mentions(group.stream(), names),
+        _ => false,
+    })
+}
+
+fn fresh_name(base: &str, used: &HashSet<String>) -> String {
+    (0..)
+        .map(|n| if n == 0 { base.to_string() } else { format!("{}{}", base, n) })
+        .find(|name| !used.contains(name))
+        .unwrap()
+}
+
+fn macro_name(mac: &Macro) -> String {
+    mac.path.segments.last().map(|segment| segment.ident.to_string()).unwrap_or_default()
+}
+
+fn is_consuming(method: &Ident) -> bool {
+    let method = method.to_string();
+    CONSUMING_METHODS.contains(&method.as_str()) ||
+        CONSUMING_PREFIXES.iter().any(|prefix| method.starts_with(prefix))
+}
+
+//whether a pattern binds a value instead of a reference to it, unit variants like `None` aside
+fn binds_by_value(pat: &Pat) -> bool {
+    struct Bindings(bool);
+    impl<'ast> Visit<'ast> for Bindings {
+        fn visit_pat_ident(&mut self, pat: &'ast PatIdent) {
+            let is_binding = pat.ident.to_string().starts_with(|c: char| c.is_lowercase() || c == '_');
+            self.0 |= is_binding && pat.by_ref.is_none();
+            visit::visit_pat_ident(self, pat);
+        }
+    }
+    let mut bindings = Bindings(false);
+    bindings.visit_pat(pat);
+    bindings.0
+}
+
+fn pat_idents(pat: &Pat) -> Vec<String> {
+    struct Idents(Vec<String>);
+    impl<'ast> Visit<'ast> for Idents {
+        fn visit_pat_ident(&mut self, pat: &'ast PatIdent) {
+            self.0.push(pat.ident.to_string());
+            visit::visit_pat_ident(self, pat);
+        }
+    }
+    let mut idents = Idents(Vec::new());
+    idents.visit_pat(pat);
+    idents.0
+}
+
+//the locals declared without a value anywhere in a block, like `let x;`
+pub fn deferred_locals(block: &Block) -> HashSet<String> {
+    struct Deferred(HashSet<String>);
+    impl<'ast> Visit<'ast> for Deferred {
+        fn visit_local(&mut self, local: &'ast Local) {
+            if local.init.is_none() {
+                self.0.extend(pat_idents(&local.pat));
+            }
+            visit::visit_local(self, local);
+        }
+
+        fn visit_item(&mut self, _item: &'ast Item) {}
+    }
+    let mut deferred = Deferred(HashSet::new());
+    deferred.visit_block(block);
+    deferred.0
+}
+
+//`x = ...` for one of the `deferred` locals
+fn is_deferred_assign(expr: &Expr, deferred: &HashSet<String>) -> bool {
+    matches!(expr, Expr::Assign(assign) if matches!(&*assign.left, Expr::Path(path) if
+        path.path.get_ident().is_some_and(|ident| deferred.contains(&ident.to_string()))))
+}
+
+//whether a statement assigns one of the `deferred` locals anywhere in it, which has to stay where
+//the compiler can tell it runs exactly once
+pub fn assigns_deferred(stmt: &Stmt, deferred: &HashSet<String>) -> bool {
+    struct Assigns<'a>(&'a HashSet<String>, bool);
+    impl<'ast> Visit<'ast> for Assigns<'_> {
+        fn visit_expr(&mut self, expr: &'ast Expr) {
+            self.1 |= is_deferred_assign(expr, self.0);
+            visit::visit_expr(self, expr);
+        }
+
+        fn visit_item(&mut self, _item: &'ast Item) {}
+    }
+    let mut assigns = Assigns(deferred, false);
+    assigns.visit_stmt(stmt);
+    assigns.1
+}
+
+//the locals declared ahead of a run of statements
+struct Locals {
+    //every binding, which a dispatcher must not move out of
+    bound: HashSet<String>,
+    //bindings that may hold a borrow, like `let first = &items[0];` or `let it = items.iter_mut();`
+    borrowing: HashSet<String>,
+    //reference parameters, a binding computed from them may borrow from what they point to
+    references: HashSet<String>,
+    //bindings declared without a value, like `let x;`, which must be assigned exactly once
+    deferred: HashSet<String>,
+}
+
+impl Locals {
+    fn from_signature(sig: &Signature) -> Self {
+        let mut locals = Self { bound: HashSet::new(), borrowing: HashSet::new(), references: HashSet::new(), deferred: HashSet::new() };
+        for input in &sig.inputs {
+            match input {
+                FnArg::Receiver(receiver) if receiver.reference.is_none() => {
+                    locals.bound.insert("self".to_string());
+                }
+                FnArg::Receiver(_) => {
+                    locals.references.insert("self".to_string());
+                }
+                FnArg::Typed(arg) => {
+                    let is_reference = matches!(&*arg.ty, Type::Reference(_));
+                    for ident in pat_idents(&arg.pat) {
+                        if is_reference {
+                            locals.references.insert(ident.clone());
+                        }
+                        locals.bound.insert(ident);
+                    }
+                }
+            }
+        }
+        locals
+    }
+
+    fn bind(&mut self, local: &Local) {
+        let (pat, ty) = match &local.pat {
+            Pat::Type(pat_type) => (&*pat_type.pat, Some(&*pat_type.ty)),
+            pat => (pat, None),
+        };
+        let mut check = BorrowCheck { locals: self, borrows: false };
+        if let Some(init) = &local.init {
+            check.visit_expr(&init.expr);
+        }
+        let borrows = check.borrows ||
+            ty.is_some_and(|ty| quote!(#ty).into_iter().any(|token| matches!(token, TokenTree::Punct(punct) if punct.as_char() == '&'))) ||
+            quote!(#pat).into_iter().any(|token| matches!(token, TokenTree::Ident(ident) if ident == "ref"));
+        for ident in pat_idents(pat) {
+            if borrows {
+                self.borrowing.insert(ident.clone());
+            }
+            if local.init.is_none() {
+                self.deferred.insert(ident.clone());
+            } else {
+                self.deferred.remove(&ident);
+            }
+            self.bound.insert(ident);
+        }
+    }
+}
+
+//whether the value of an initializer may hold on to a borrow
+struct BorrowCheck<'a> {
+    locals: &'a Locals,
+    borrows: bool,
+}
+
+impl<'ast> Visit<'ast> for BorrowCheck<'_> {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match expr {
+            Expr::Reference(_) | Expr::Closure(_) | Expr::Async(_) => {
+                self.borrows = true;
+            }
+            Expr::MethodCall(call) => {
+                self.borrows |= !OWNED_METHODS.contains(&call.method.to_string().as_str());
+            }
+            Expr::Path(path) => {
+                if let Some(ident) = path.path.get_ident().map(|ident| ident.to_string()) {
+                    self.borrows |= self.locals.borrowing.contains(&ident) || self.locals.references.contains(&ident);
+                }
+            }
+            Expr::Macro(expr_macro) if is_format_macro(&expr_macro.mac) => {}
+            Expr::Macro(expr_macro) => {
+                let tokens = expr_macro.mac.tokens.clone();
+                self.borrows |=
+                    tokens.clone().into_iter().any(|token| matches!(token, TokenTree::Punct(punct) if punct.as_char() == '&')) ||
+                    mentions(tokens.clone(), &self.locals.borrowing) ||
+                    mentions(tokens, &self.locals.references);
+            }
+            _ => visit::visit_expr(self, expr),
+        }
+    }
+}
+
+//whether statements may move out of one of the locals declared ahead of them. Locals that are
+//borrowed, compared, indexed or called methods on stay where they are, anything else is taken
+//for a move since the types aren't known here. Assigning a local declared without a value counts
+//too: every arm of the dispatcher may run again, so the compiler sees a second assignment to it
+//or a read of it before it's set
+struct MoveCheck<'a> {
+    bound: &'a HashSet<String>,
+    deferred: &'a HashSet<String>,
+    moves: bool,
+}
+
+impl MoveCheck<'_> {
+    //an expression only read or borrowed where it is, like `x`, `x.field` or `x[i]`
+    fn visit_place(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Path(_) => {}
+            Expr::Field(field) => self.visit_place(&field.base),
+            Expr::Paren(paren) => self.visit_place(&paren.expr),
+            //indices are integers or ranges of them
+            Expr::Index(index) => {
+                self.visit_place(&index.expr);
+                self.visit_place(&index.index);
+            }
+            expr => self.visit_expr(expr),
+        }
+    }
+
+    //the expression matched against `pat`, which moves it if it binds by value
+    fn visit_scrutinee<'p>(&mut self, expr: &Expr, mut pats: impl Iterator<Item = &'p Pat>) {
+        if pats.any(binds_by_value) {
+            self.visit_expr(expr);
+        } else {
+            self.visit_place(expr);
+        }
+    }
+
+    fn visit_condition(&mut self, cond: &Expr) {
+        match cond {
+            Expr::Let(expr_let) => self.visit_scrutinee(&expr_let.expr, std::iter::once(&*expr_let.pat)),
+            cond => self.visit_place(cond),
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for MoveCheck<'_> {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match expr {
+            Expr::Path(path) => {
+                self.moves |= path.path.get_ident().is_some_and(|ident| self.bound.contains(&ident.to_string()));
+            }
+            Expr::Reference(reference) => self.visit_place(&reference.expr),
+            Expr::MethodCall(call) => {
+                if is_consuming(&call.method) {
+                    self.visit_expr(&call.receiver);
+                } else {
+                    self.visit_place(&call.receiver);
+                }
+                call.args.iter().for_each(|arg| self.visit_expr(arg));
+            }
+            Expr::Index(_) => self.visit_place(expr),
+            Expr::Assign(assign) => {
+                self.moves |= is_deferred_assign(expr, self.deferred);
+                self.visit_place(&assign.left);
+                self.visit_expr(&assign.right);
+            }
+            //`String + &str` moves the string, other operators are used on copies or take references
+            Expr::Binary(binary) => {
+                if matches!(binary.op, BinOp::Add(_)) {
+                    self.visit_expr(&binary.left);
+                } else {
+                    self.visit_place(&binary.left);
+                }
+                self.visit_place(&binary.right);
+            }
+            Expr::Unary(unary) => self.visit_place(&unary.expr),
+            Expr::Cast(cast) => self.visit_place(&cast.expr),
+            Expr::Range(range) => {
+                range.start.iter().for_each(|start| self.visit_place(start));
+                range.end.iter().for_each(|end| self.visit_place(end));
+            }
+            //returning a local moves it, but the dispatcher is left with it
+            Expr::Return(expr_return) => expr_return.expr.iter().for_each(|expr| self.visit_place(expr)),
+            Expr::If(expr_if) => {
+                self.visit_condition(&expr_if.cond);
+                self.visit_block(&expr_if.then_branch);
+                expr_if.else_branch.iter().for_each(|(_, else_branch)| self.visit_expr(else_branch));
+            }
+            Expr::While(expr_while) => {
+                self.visit_condition(&expr_while.cond);
+                self.visit_block(&expr_while.body);
+            }
+            Expr::Match(expr_match) => {
+                self.visit_scrutinee(&expr_match.expr, expr_match.arms.iter().map(|arm| &arm.pat));
+                for arm in &expr_match.arms {
+                    arm.guard.iter().for_each(|(_, guard)| self.visit_expr(guard));
+                    self.visit_expr(&arm.body);
+                }
+            }
+            Expr::Closure(closure) if closure.capture.is_some() => {
+                let body = &closure.body;
+                self.moves |= mentions(quote!(#body), self.bound);
+            }
+            _ => visit::visit_expr(self, expr),
+        }
+    }
+
+    fn visit_macro(&mut self, mac: &'ast Macro) {
+        self.moves |= !is_format_macro(mac) && mentions(mac.tokens.clone(), self.bound);
+    }
+
+    fn visit_item(&mut self, _item: &'ast Item) {}
+}
+
+//a state of the dispatcher: the statements it runs, then where it goes next
+struct State {
+    key: u32,
+    stmts: Vec<Stmt>,
+    next: Next,
+    //position of the state in the source, arms are written in that order so type inference
+    //sees the statements in the order it did before
+    order: usize,
+}
+
+enum Next {
+    Goto(usize),
+    Branch(Box<Expr>, usize, usize),
+    Exit,
+    //the last statement never returns
+    Diverge,
+}
+
+//a flattened loop, what `break` and `continue` in it go to
+struct LoopTarget {
+    label: Option<String>,
+    head: usize,
+    exit: usize,
+}
+
+struct Dispatcher<'a> {
+    rng: &'a mut StdRng,
+    state: &'a Ident,
+    label: &'a Lifetime,
+    states: Vec<State>,
+    loops: Vec<LoopTarget>,
+    keys: HashSet<u32>,
+    order: usize,
+    //whether a `break` or `continue` was turned into a jump out of the middle of a state
+    jumps: bool,
+    //a `break` with a value, or one hidden in a macro, the dispatcher can't take over
+    failed: bool,
+}
+
+impl<'a> Dispatcher<'a> {
+    fn new(rng: &'a mut StdRng, state: &'a Ident, label: &'a Lifetime) -> Self {
+        Self {
+            rng,
+            state,
+            label,
+            states: Vec::new(),
+            loops: Vec::new(),
+            keys: HashSet::new(),
+            order: 0,
+            jumps: false,
+            failed: false,
+        }
+    }
+
+    //the dispatcher running `stmts`, or the statements as they were if it can't be built
+    fn flatten(mut self, stmts: Vec<Stmt>, locals: &Locals, needs_divergence: bool) -> Vec<Stmt> {
+        let is_worth_it = stmts.len() > 1 || stmts.iter().any(|stmt| Self::control_flow(stmt).is_some());
+        let mut move_check = MoveCheck { bound: &locals.bound, deferred: &locals.deferred, moves: false };
+        stmts.iter().for_each(|stmt| move_check.visit_stmt(stmt));
+        if !is_worth_it || move_check.moves || mentions(quote!(#(#stmts)*), &locals.borrowing) {
+            return stmts;
+        }
+
+        let entry = self.new_state();
+        let end = self.lower(stmts.clone(), entry);
+        if self.failed || (needs_divergence && end.is_some()) {
+            return stmts;
+        }
+        if let Some(end) = end {
+            self.set_next(end, Next::Exit);
+        }
+        vec![self.emit(entry)]
+    }
+
+    fn new_state(&mut self) -> usize {
+        let key = loop {
+            let key = self.rng.gen();
+            if self.keys.insert(key) {
+                break key;
+            }
+        };
+        self.states.push(State { key, stmts: Vec::new(), next: Next::Diverge, order: usize::MAX });
+        self.states.len() - 1
+    }
+
+    fn place(&mut self, state: usize) {
+        if self.states[state].order == usize::MAX {
+            self.states[state].order = self.order;
+            self.order += 1;
+        }
+    }
+
+    fn push(&mut self, state: usize, stmt: Stmt) {
+        self.place(state);
+        self.states[state].stmts.push(stmt);
+    }
+
+    fn set_next(&mut self, state: usize, next: Next) {
+        self.place(state);
+        self.states[state].next = next;
+    }
+
+    //the statements of a block, if they can become states of their own: a `let` or an item would
+    //go out of scope of the statements after it
+    fn flat_block(block: &Block) -> Option<Vec<Stmt>> {
+        if block.stmts.iter().any(|stmt| matches!(stmt, Stmt::Local(_) | Stmt::Item(_))) {
+            return None;
+        }
+        Some(block.stmts.clone())
+    }
+
+    //the `if`, `while` or `loop` a statement is, if the dispatcher can take over its control flow
+    fn control_flow(stmt: &Stmt) -> Option<&Expr> {
+        match stmt {
+            //an `if` without a semicolon is `()`, so are its blocks
+            Stmt::Expr(expr @ Expr::If(expr_if), None) if expr_if.attrs.is_empty() => {
+                let else_is_flat = match &expr_if.else_branch {
+                    None => true,
+                    Some((_, else_branch)) => match &**else_branch {
+                        Expr::Block(expr_block) => expr_block.attrs.is_empty() && expr_block.label.is_none() && Self::flat_block(&expr_block.block).is_some(),
+                        Expr::If(_) => true,
+                        _ => false,
+                    },
+                };
+                let is_flat = !matches!(&*expr_if.cond, Expr::Let(_)) && Self::flat_block(&expr_if.then_branch).is_some() && else_is_flat;
+                is_flat.then_some(expr)
+            }
+            Stmt::Expr(expr @ Expr::While(expr_while), _) if expr_while.attrs.is_empty() => {
+                let is_flat = !matches!(&*expr_while.cond, Expr::Let(_)) && Self::flat_block(&expr_while.body).is_some();
+                is_flat.then_some(expr)
+            }
+            Stmt::Expr(expr @ Expr::Loop(expr_loop), _) if expr_loop.attrs.is_empty() => Self::flat_block(&expr_loop.body).map(|_| expr),
+            _ => None,
+        }
+    }
+
+    //turn `stmts` into states starting at `current`, returning the state control ends up in,
+    //or `None` if they never finish
+    fn lower(&mut self, stmts: Vec<Stmt>, mut current: usize) -> Option<usize> {
+        let count = stmts.len();
+        for (index, stmt) in stmts.into_iter().enumerate() {
+            if let Some(expr) = Self::control_flow(&stmt).cloned() {
+                current = self.lower_control_flow(expr, current);
+                continue;
+            }
+            match &stmt {
+                Stmt::Expr(Expr::Break(expr_break), _) if expr_break.expr.is_none() => {
+                    let exit = self.target(expr_break.label.as_ref()).map(|target| target.exit);
+                    self.jump(current, exit);
+                    return None;
+                }
+                Stmt::Expr(Expr::Continue(expr_continue), _) => {
+                    let head = self.target(expr_continue.label.as_ref()).map(|target| target.head);
+                    self.jump(current, head);
+                    return None;
+                }
+                _ => {}
+            }
+            let mut stmt = stmt;
+            //the value of the block, `()` where a block is flattened, gets its semicolon
+            match &mut stmt {
+                Stmt::Expr(expr, semi @ None) if index + 1 == count && !is_block_like(expr) => {
+                    *semi = Some(Default::default());
+                }
+                Stmt::Macro(stmt_macro) if stmt_macro.semi_token.is_none() => {
+                    stmt_macro.semi_token = Some(Default::default());
+                }
+                _ => {}
+            }
+            let diverges = is_diverging(&stmt);
+            self.rewrite(&mut stmt, current);
+            self.push(current, stmt);
+            if diverges {
+                self.set_next(current, Next::Diverge);
+                return None;
+            }
+            let next = self.new_state();
+            self.set_next(current, Next::Goto(next));
+            current = next;
+        }
+        Some(current)
+    }
+
+    fn lower_control_flow(&mut self, expr: Expr, current: usize) -> usize {
+        match expr {
+            Expr::If(expr_if) => {
+                let mut cond = *expr_if.cond;
+                self.rewrite_expr(&mut cond, current);
+                let then_state = self.new_state();
+                let else_state = self.new_state();
+                self.set_next(current, Next::Branch(Box::new(cond), then_state, else_state));
+                let then_end = self.lower(Self::flat_block(&expr_if.then_branch).unwrap_or_default(), then_state);
+                let else_end = match expr_if.else_branch {
+                    Some((_, else_branch)) => {
+                        let stmts = match *else_branch {
+                            Expr::Block(expr_block) => Self::flat_block(&expr_block.block).unwrap_or_default(),
+                            else_if => vec![Stmt::Expr(else_if, None)],
+                        };
+                        self.lower(stmts, else_state)
+                    }
+                    None => Some(else_state),
+                };
+                let join = self.new_state();
+                for end in [then_end, else_end].into_iter().flatten() {
+                    self.set_next(end, Next::Goto(join));
+                }
+                join
+            }
+            Expr::While(expr_while) => {
+                let head = self.new_state();
+                self.set_next(current, Next::Goto(head));
+                let mut cond = *expr_while.cond;
+                self.rewrite_expr(&mut cond, head);
+                let body = self.new_state();
+                let exit = self.new_state();
+                self.set_next(head, Next::Branch(Box::new(cond), body, exit));
+                self.lower_loop_body(expr_while.label.map(|label| label.name.ident.to_string()), &expr_while.body, head, body, exit);
+                exit
+            }
+            Expr::Loop(expr_loop) => {
+                let head = self.new_state();
+                self.set_next(current, Next::Goto(head));
+                let exit = self.new_state();
+                self.lower_loop_body(expr_loop.label.map(|label| label.name.ident.to_string()), &expr_loop.body, head, head, exit);
+                exit
+            }
+            _ => unreachable!("not a flattened control flow expression"),
+        }
+    }
+
+    fn lower_loop_body(&mut self, label: Option<String>, body: &Block, head: usize, start: usize, exit: usize) {
+        self.loops.push(LoopTarget { label, head, exit });
+        if let Some(end) = self.lower(Self::flat_block(body).unwrap_or_default(), start) {
+            self.set_next(end, Next::Goto(head));
+        }
+        self.loops.pop();
+    }
+
+    //the flattened loop a `break` or `continue` with `label` goes to
+    fn target(&self, label: Option<&Lifetime>) -> Option<&LoopTarget> {
+        match label {
+            Some(label) => self.loops.iter().rev().find(|target| target.label.as_deref() == Some(&label.ident.to_string())),
+            None => self.loops.last(),
+        }
+    }
+
+    fn jump(&mut self, from: usize, to: Option<usize>) {
+        match to {
+            Some(to) => self.set_next(from, Next::Goto(to)),
+            None => self.failed = true,
+        }
+    }
+
+    fn rewrite(&mut self, stmt: &mut Stmt, current: usize) {
+        let mut rewriter = BreakRewriter { dispatcher: self, current, depth: 0 };
+        rewriter.visit_stmt_mut(stmt);
+    }
+
+    fn rewrite_expr(&mut self, expr: &mut Expr, current: usize) {
+        let mut rewriter = BreakRewriter { dispatcher: self, current, depth: 0 };
+        rewriter.visit_expr_mut(expr);
+    }
+
+    //the statement moving the dispatcher from one state to another, encoded as an operation
+    //on the current key instead of the key of the next state
+    fn transition(&mut self, from: usize, to: usize) -> TokenStream {
+        let state = self.state;
+        let (from, to) = (self.states[from].key, self.states[to].key);
+        match self.rng.gen_range(0..3) {
+            0 => {
+                let delta = from ^ to;
+                quote! { #state ^= #delta; }
+            }
+            1 => {
+                let delta = to.wrapping_sub(from);
+                quote! { #state = #state.wrapping_add(#delta); }
+            }
+            _ => {
+                let delta = from.wrapping_sub(to);
+                quote! { #state = #state.wrapping_sub(#delta); }
+            }
+        }
+    }
+
+    fn emit(mut self, entry: usize) -> Stmt {
+        let mut order: Vec<usize> = (0..self.states.len()).filter(|&state| self.states[state].order != usize::MAX).collect();
+        order.sort_by_key(|&state| self.states[state].order);
+
+        let (state, label) = (self.state, self.label);
+        //a dispatcher that only ends by returning leaves its label unused
+        let exits = self.states.iter().any(|state| matches!(state.next, Next::Exit));
+        let label = (self.jumps || exits).then(|| quote! { #label: });
+        let mut arms = Vec::new();
+        for index in order {
+            let next = match std::mem::replace(&mut self.states[index].next, Next::Diverge) {
+                Next::Goto(to) => self.transition(index, to),
+                Next::Branch(cond, then_state, else_state) => {
+                    let then_transition = self.transition(index, then_state);
+                    let else_transition = self.transition(index, else_state);
+                    quote! { if #cond { #then_transition } else { #else_transition } }
+                }
+                Next::Exit => {
+                    let label = self.label;
+                    quote! { break #label; }
+                }
+                Next::Diverge => quote! {},
+            };
+            let key = self.states[index].key;
+            let stmts = &self.states[index].stmts;
+            arms.push(quote! { #key => { #(#stmts)* #next } });
+        }
+        let entry = self.states[entry].key;
+        parse_quote! {
+            {
+                let mut #state = #entry;
+                #label loop {
+                    match #state {
+                        #(#arms)*
+                        _ => unreachable!(),
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn is_block_like(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::If(_) | Expr::Match(_) | Expr::While(_) | Expr::Loop(_) | Expr::ForLoop(_) | Expr::Block(_) | Expr::Unsafe(_) | Expr::TryBlock(_) | Expr::Const(_)
+    )
+}
+
+fn is_diverging(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Expr(Expr::Return(_) | Expr::Break(_) | Expr::Continue(_), _) => true,
+        Stmt::Expr(Expr::Macro(expr_macro), _) => DIVERGING_MACROS.contains(&macro_name(&expr_macro.mac).as_str()),
+        Stmt::Macro(stmt_macro) => DIVERGING_MACROS.contains(&macro_name(&stmt_macro.mac).as_str()),
+        _ => false,
+    }
+}
+
+//replaces a `break` or `continue` going to a flattened loop by a jump to its state
+struct BreakRewriter<'d, 'a> {
+    dispatcher: &'d mut Dispatcher<'a>,
+    current: usize,
+    //loops left in place around the expression being visited
+    depth: usize,
+}
+
+impl BreakRewriter<'_, '_> {
+    fn target(&self, label: Option<&Lifetime>) -> Option<&LoopTarget> {
+        match label {
+            Some(_) => self.dispatcher.target(label),
+            None if self.depth == 0 => self.dispatcher.target(None),
+            None => None,
+        }
+    }
+
+    fn jump(&mut self, to: usize) -> Expr {
+        let transition = self.dispatcher.transition(self.current, to);
+        let label = self.dispatcher.label;
+        self.dispatcher.jumps = true;
+        parse_quote! { { #transition continue #label; } }
+    }
+}
+
+impl VisitMut for BreakRewriter<'_, '_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Break(expr_break) => {
+                match self.target(expr_break.label.as_ref()).map(|target| target.exit) {
+                    Some(_) if expr_break.expr.is_some() => self.dispatcher.failed = true,
+                    Some(exit) => *expr = self.jump(exit),
+                    None => visit_mut::visit_expr_mut(self, expr),
+                }
+            }
+            Expr::Continue(expr_continue) => {
+                if let Some(head) = self.target(expr_continue.label.as_ref()).map(|target| target.head) {
+                    *expr = self.jump(head);
+                }
+            }
+            Expr::While(_) | Expr::Loop(_) | Expr::ForLoop(_) => {
+                self.depth += 1;
+                visit_mut::visit_expr_mut(self, expr);
+                self.depth -= 1;
+            }
+            //a `break` can't leave a closure or an async block
+            Expr::Closure(_) | Expr::Async(_) => {}
+            _ => visit_mut::visit_expr_mut(self, expr),
+        }
+    }
+
+    fn visit_macro_mut(&mut self, mac: &mut Macro) {
+        let mut idents = HashSet::new();
+        collect_idents(mac.tokens.clone(), &mut idents);
+        if idents.contains("break") || idents.contains("continue") {
+            self.dispatcher.failed = true;
+        }
+    }
+
+    fn visit_item_mut(&mut self, _item: &mut Item) {}
+}
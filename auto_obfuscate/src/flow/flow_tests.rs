@@ -16,16 +16,20 @@ fn test_loop_insertion() {
     let flow_config = FlowConfig {
         enable_flow_obfuscation: true,
         use_macro: false,
+        density: 100,
+        ..FlowConfig::default()
     };
     let mut obfuscator = FlowObfuscator::new(flow_config);
     let modified_code = obfuscator.flow_obfuscate(code);
 
     assert_ne!(modified_code, code);
-    //check if loop is inserted at start of block
-    assert!(modified_code.contains("_is_dummy_145"), "Dummy loop not found in modified code");
     //valid rust code
     let parse_result = syn::parse_file(&modified_code);
     assert!(parse_result.is_ok(), "Modified code is not valid Rust code");
+    //check if junk code is inserted at start of block
+    let Item::Fn(main) = &parse_result.unwrap().items[0] else { panic!("main missing") };
+    assert_eq!(main.block.stmts.len(), 6);
+    assert!(matches!(&main.block.stmts[0], Stmt::Expr(Expr::Block(_), None)), "Junk code not found in {}", modified_code);
 }
 
 #[test]
@@ -35,6 +39,7 @@ fn test_loop_skip() {
         fn calculate_sum(num1: i32, num2: i32) -> i32 {
             num1 + num2
         }
+        #[obfuscate(flow_density = 0)]
         fn ok(){
             
         }
@@ -46,20 +51,396 @@ fn test_loop_skip() {
                 println!("The sum is: {}", sum);
             }
         "#;
+    //every block gets junk code, except the ones of the function turning it off
     let flow_config = FlowConfig {
         enable_flow_obfuscation: true,
         use_macro: false,
+        density: 100,
+        ..FlowConfig::default()
     };
     let mut obfuscator = FlowObfuscator::new(flow_config);
     let modified_code = obfuscator.flow_obfuscate(code);
 
     assert_ne!(modified_code, code);
-    //check if loop is inserted at start of block
-    assert!(modified_code.contains("_is_dummy_145"), "Dummy loop not found in modified code");
+    let parse_result = syn::parse_file(&modified_code);
+    assert!(parse_result.is_ok(), "Modified code is not valid Rust code");
+
+    //check if junk code is inserted at start of block
+    let starts_with_junk: Vec<bool> = parse_result
+        .unwrap()
+        .items
+        .iter()
+        .map(|item| match item {
+            Item::Fn(item_fn) => matches!(item_fn.block.stmts.first(), Some(Stmt::Expr(Expr::Block(_), None))),
+            _ => false,
+        })
+        .collect();
+    assert_eq!(starts_with_junk, [true, false, true], "exactly two blocks of junk code not found in {}", modified_code);
+}
+
+#[test]
+fn test_max_flow_per_function() {
+    let code =
+        r#"
+        #[obfuscate(flow_density = 0)]
+        fn ok(){
+            let skipped = { 1 };
+        }
+            fn main() {
+                let nested = { 1 };
+                let other = { 2 };
+            }
+        "#;
+    let flow_config = FlowConfig {
+        enable_flow_obfuscation: true,
+        use_macro: true,
+        density: 100,
+        max_per_function: Some(1),
+        ..FlowConfig::default()
+    };
+    let mut obfuscator = FlowObfuscator::new(flow_config);
+    let modified_code = obfuscator.flow_obfuscate(code);
 
     let parse_result = syn::parse_file(&modified_code);
     assert!(parse_result.is_ok(), "Modified code is not valid Rust code");
+    //`main` has three blocks but only gets one call, `ok` none at all
+    let num_junk = modified_code.matches("flow_stmt").count();
+    assert!(num_junk == 1, "exactly one flow_stmt! call not found in {}", modified_code);
+}
 
-    let num_loops = modified_code.matches("_is_dummy_145").count();
-    assert!(num_loops == 2, "exactly two dummy loops not found in modified code");
+#[test]
+fn test_flow_density() {
+    let code =
+        r#"
+            fn main() {
+                let total = { let a = 1; a + 1 };
+                if total > 1 {
+                    for i in 0..total {
+                        println!("{}", i);
+                    }
+                }
+            }
+        "#;
+    let flow_config = FlowConfig {
+        enable_flow_obfuscation: true,
+        use_macro: true,
+        seed: Some(3),
+        ..FlowConfig::default()
+    };
+    let num_junk = |config: FlowConfig| FlowObfuscator::new(config).flow_obfuscate(code).matches("flow_stmt").count();
+
+    //every block gets junk code, including the ones nested in blocks that got some
+    assert_eq!(num_junk(FlowConfig { density: 100, ..flow_config.clone() }), 4);
+    assert_eq!(num_junk(FlowConfig { density: 0, ..flow_config.clone() }), 0);
+
+    //the cap holds for each function
+    assert_eq!(num_junk(FlowConfig { density: 100, max_per_function: Some(2), ..flow_config.clone() }), 2);
+    let two_fns = format!("{}\nfn other() {{ let b = {{ 2 }}; }}", code);
+    let modified_code = FlowObfuscator::new(FlowConfig { density: 100, max_per_function: Some(1), ..flow_config.clone() })
+        .flow_obfuscate(&two_fns);
+    assert_eq!(modified_code.matches("flow_stmt").count(), 2);
+
+    //`cryptify :: flow_stmt ! () ;` takes 7 tokens of the budget, the file about 40, so 40% fit one or two
+    assert_eq!(num_junk(FlowConfig { density: 100, size_budget: Some(5), ..flow_config.clone() }), 0);
+    let budgeted = num_junk(FlowConfig { density: 100, size_budget: Some(40), ..flow_config.clone() });
+    assert!(budgeted > 0 && budgeted < 4, "{} flow_stmt! calls within the budget", budgeted);
+}
+
+#[test]
+fn test_seeded_output_is_reproducible() {
+    use crate::obfuscate::{ Config, Obfuscator };
+
+    let code =
+        r#"
+            fn helper() {
+                let a = "a";
+            }
+            fn main() {
+                let b = "b";
+                helper();
+            }
+        "#;
+    let mut config = Config::default();
+    config.flow_config.use_macro = false;
+    config.rename_config.enable_rename_obfuscation = true;
+    config.string_config.percentage = 50;
+    config.string_config.strategy = crate::string::SelectionStrategy::Random;
+    config.seed = Some(42);
+
+    let obfuscate = |file: &str| Obfuscator::from_config(config.for_file(file)).obfuscate(code);
+    assert_eq!(obfuscate("src/main.rs"), obfuscate("src/main.rs"));
+    //every file derives its own choices
+    assert_ne!(obfuscate("src/main.rs"), obfuscate("src/lib.rs"));
+}
+
+#[test]
+fn test_opaque_predicates() {
+    let code =
+        r#"
+            const fn square(x: u32) -> u32 {
+                let y = x;
+                y * y
+            }
+            fn main() {
+                let mut total = 0;
+                let later;
+                later = 5;
+                total += later;
+                println!("{}", total);
+                for i in 0..3 {
+                    total += i;
+                    println!("{}", i);
+                }
+                total
+            }
+        "#;
+    let flow_config = FlowConfig {
+        enable_flow_obfuscation: true,
+        use_macro: true,
+        opaque_predicates: true,
+        density: 100,
+        seed: Some(7),
+        ..FlowConfig::default()
+    };
+    let mut obfuscator = FlowObfuscator::new(flow_config.clone());
+    let modified_code = obfuscator.flow_obfuscate(code);
+    let ast = syn::parse_file(&modified_code).expect("Modified code is not valid Rust code");
+
+    //wrapped statements are guarded by black_box values
+    assert!(modified_code.contains("if { let v = std :: hint :: black_box"), "No opaque predicate in {}", modified_code);
+
+    //neither `black_box` nor the loops of junk code can be used in a const fn
+    let Item::Fn(square) = &ast.items[0] else { panic!("const fn missing") };
+    let square = &square.block;
+    let square = quote!(#square).to_string();
+    assert!(!square.contains("black_box") && !square.contains("flow_stmt"), "{}", square);
+
+    //the `let`s, the deferred initialization and the tail stay where they were
+    let Item::Fn(main) = &ast.items[1] else { panic!("main missing") };
+    let stmts: Vec<String> = main.block.stmts
+        .iter()
+        .map(|stmt| quote!(#stmt).to_string())
+        .collect();
+    for stmt in ["let mut total = 0 ;", "let later ;", "later = 5 ;"] {
+        assert!(stmts.iter().any(|kept| kept == stmt), "`{}` was moved in {}", stmt, modified_code);
+    }
+    assert!(stmts.iter().any(|stmt| stmt.starts_with("if {")), "Nothing wrapped in {}", modified_code);
+    assert_eq!(stmts.last().unwrap(), "total");
+
+    //the `return` still ends the `else` of the `let ... else`, whichever statements get wrapped
+    let code = r#"
+        fn main() {
+            let Some(step) = Some(2) else {
+                println!("no step");
+                println!("giving up");
+                return;
+            };
+            println!("{}", step);
+        }
+    "#;
+    for seed in 0..16 {
+        let mut obfuscator = FlowObfuscator::new(FlowConfig { seed: Some(seed), ..flow_config.clone() });
+        let ast = syn::parse_file(&obfuscator.flow_obfuscate(code)).unwrap();
+        let Item::Fn(main) = &ast.items[0] else { panic!("main missing") };
+        let else_block = main.block.stmts
+            .iter()
+            .find_map(|stmt| match stmt {
+                Stmt::Local(syn::Local { init: Some(syn::LocalInit { diverge: Some((_, diverge)), .. }), .. }) => Some(diverge),
+                _ => None,
+            })
+            .expect("let ... else missing");
+        let Expr::Block(else_block) = &**else_block else { panic!("else is a block") };
+        let last = else_block.block.stmts.last().unwrap();
+        assert_eq!(quote!(#last).to_string(), "return ;");
+    }
+}
+
+#[test]
+fn test_opaque_predicates_keep_declaring_macros() {
+    let code =
+        r#"
+            macro_rules! bind {
+                ($name:ident) => { let $name = 1; };
+            }
+            fn main() {
+                thread_local!(static HITS: std::cell::Cell<u32> = std::cell::Cell::new(0));
+                bind!(one);
+                println!("{}", one);
+                HITS.with(|hits| hits.set(one));
+                println!("done");
+            }
+        "#;
+    let mut wrapped_println = false;
+    for seed in 0..16 {
+        let flow_config = FlowConfig {
+            enable_flow_obfuscation: true,
+            use_macro: true,
+            opaque_predicates: true,
+            density: 100,
+            seed: Some(seed),
+            ..FlowConfig::default()
+        };
+        let mut obfuscator = FlowObfuscator::new(flow_config);
+        let modified_code = obfuscator.flow_obfuscate(code);
+        let ast = syn::parse_file(&modified_code).expect("Modified code is not valid Rust code");
+        let Item::Fn(main) = &ast.items[1] else { panic!("main missing") };
+        let stmts: Vec<String> = main.block.stmts
+            .iter()
+            .map(|stmt| quote!(#stmt).to_string())
+            .collect();
+        //what `thread_local!` and `bind!` declare must stay in scope of the statements after them
+        for stmt in ["thread_local ! (static HITS", "bind ! (one) ;"] {
+            assert!(stmts.iter().any(|kept| kept.starts_with(stmt)), "`{}` was moved in {}", stmt, modified_code);
+        }
+        wrapped_println |= !stmts.iter().any(|stmt| stmt.starts_with("println ! (\"{}\" , one)"));
+    }
+    assert!(wrapped_println, "format macros should still be wrapped");
+}
+
+fn flatten_fn(code: &str) -> String {
+    let mut item: syn::ItemFn = syn::parse_str(code).unwrap();
+    flatten::flatten_body(&mut item.block, &item.sig, &mut seeded_rng(Some(1)));
+    quote!(#item).to_string()
+}
+
+#[test]
+fn test_flatten() {
+    let flattened = flatten_fn(
+        r#"
+            fn find(items: &[i32], target: i32) -> Option<usize> {
+                let mut index = 0;
+                'search: loop {
+                    if index >= items.len() {
+                        return None;
+                    }
+                    while items[index] < 0 {
+                        index += 1;
+                        continue 'search;
+                    }
+                    if items[index] == target {
+                        break;
+                    }
+                    index += 1;
+                }
+                Some(index)
+            }
+        "#
+    );
+    let item: syn::ItemFn = syn::parse_str(&flattened).expect("Flattened code is not valid Rust code");
+
+    //the `let` and the tail stay around the dispatcher
+    let stmts = &item.block.stmts;
+    assert_eq!(stmts.len(), 3, "{}", flattened);
+    assert!(matches!(&stmts[0], Stmt::Local(_)));
+    let tail = &stmts[2];
+    assert_eq!(quote!(#tail).to_string(), "Some (index)");
+
+    //the loops are states of the dispatcher now
+    assert!(flattened.contains("'dispatch : loop { match state {"), "{}", flattened);
+    assert!(!flattened.contains("'search"), "{}", flattened);
+    assert!(!flattened.contains("while"), "{}", flattened);
+    assert!(flattened.contains("return None ;"), "{}", flattened);
+    assert!(flattened.contains("break 'dispatch ;"), "{}", flattened);
+}
+
+#[test]
+fn test_flatten_fallback() {
+    let unchanged = [
+        //`name` would be moved in every pass of the dispatcher
+        r#"
+            fn add(names: &mut Vec<String>, name: String) {
+                println!("adding {}", name);
+                names.push(name);
+            }
+        "#,
+        //`first` keeps `values` borrowed across states
+        r#"
+            fn bump() -> i32 {
+                let mut values = vec![1, 2, 3];
+                let first = &mut values[0];
+                *first += 10;
+                values.push(4);
+                values.iter().sum()
+            }
+        "#,
+        //the function has to end by diverging, which `exit` isn't known to do
+        r#"
+            fn fail(code: i32) -> u8 {
+                println!("failing");
+                std::process::exit(code);
+            }
+        "#,
+        //`break` with a value
+        r#"
+            fn first_even(values: &[u32]) {
+                let mut index = 0;
+                loop {
+                    if values[index] % 2 == 0 {
+                        break values[index];
+                    }
+                    index += 1;
+                };
+            }
+        "#,
+        //`x` is assigned once on every path, in the dispatcher it would look like it's assigned
+        //in every pass and read before it's set
+        r#"
+            fn deferred(c: bool) -> i32 {
+                let x;
+                if c {
+                    x = 1;
+                } else {
+                    x = 2;
+                }
+                println!("{}", x);
+                x
+            }
+        "#,
+    ];
+    for code in unchanged {
+        let item: syn::ItemFn = syn::parse_str(code).unwrap();
+        assert_eq!(flatten_fn(code), quote!(#item).to_string());
+    }
+}
+
+#[test]
+fn test_opaque_predicates_keep_deferred_assignments() {
+    let code =
+        r#"
+            fn main() {
+                let c = std::env::args().count() > 1;
+                let x;
+                if c { x = 1; } else { x = 2; }
+                let y;
+                match c { true => y = 10, false => y = 20 }
+                let z = x + y;
+                println!("{}", z);
+                println!("done");
+            }
+        "#;
+    let mut wrapped_let = false;
+    for seed in 0..16 {
+        let flow_config = FlowConfig {
+            enable_flow_obfuscation: true,
+            use_macro: true,
+            opaque_predicates: true,
+            density: 100,
+            seed: Some(seed),
+            ..FlowConfig::default()
+        };
+        let mut obfuscator = FlowObfuscator::new(flow_config);
+        let modified_code = obfuscator.flow_obfuscate(code);
+        let ast = syn::parse_file(&modified_code).expect("Modified code is not valid Rust code");
+        let Item::Fn(main) = &ast.items[0] else { panic!("main missing") };
+        let stmts: Vec<String> = main.block.stmts
+            .iter()
+            .map(|stmt| quote!(#stmt).to_string())
+            .collect();
+        //the branches initializing `x` and `y` must run where the compiler sees them run once
+        for stmt in ["if c {", "match c {"] {
+            assert!(stmts.iter().any(|kept| kept.starts_with(stmt)), "`{}` was moved in {}", stmt, modified_code);
+        }
+        wrapped_let |= !stmts.iter().any(|stmt| stmt.starts_with("println ! (\"{}\" , z)"));
+    }
+    assert!(wrapped_let, "other statements should still be wrapped");
 }
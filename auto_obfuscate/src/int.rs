@@ -0,0 +1,186 @@
+use syn::{
+    visit_mut::VisitMut,
+    parse_file,
+    parse_quote,
+    Attribute,
+    Block,
+    Expr,
+    ExprConst,
+    ExprLit,
+    ExprRepeat,
+    ExprUnary,
+    GenericArgument,
+    ImplItem,
+    ImplItemConst,
+    ImplItemFn,
+    ItemConst,
+    ItemEnum,
+    ItemFn,
+    ItemStatic,
+    Item,
+    Lit,
+    Pat,
+    TraitItem,
+    TraitItemConst,
+    TraitItemFn,
+    Type,
+    UnOp,
+};
+use quote::quote;
+use rand::rngs::StdRng;
+use labyrinth_core::codegen::encrypt_int;
+use crate::attributes::{ impl_item_attrs, is_disabled, item_attrs, trait_item_attrs, Pass };
+use crate::obfuscate::seeded_rng;
+
+#[cfg(test)]
+mod int_tests;
+
+#[derive(Clone)]
+pub struct IntConfig {
+    pub enable_int_obfuscation: bool,
+    //emit `cryptify::encrypt_int!` or what it expands to
+    pub use_macro: bool,
+    pub seed: Option<u64>,
+}
+
+//default int obfuscation to false
+impl IntConfig {
+    pub fn default() -> Self {
+        Self {
+            enable_int_obfuscation: false,
+            use_macro: true,
+            seed: None,
+        }
+    }
+}
+
+pub struct IntObfuscator {
+    pub enabled: bool,
+    use_macro: bool,
+    rng: StdRng,
+    fn_depth: usize,
+}
+
+impl IntObfuscator {
+    pub fn new(config: IntConfig) -> Self {
+        Self {
+            enabled: config.enable_int_obfuscation,
+            use_macro: config.use_macro,
+            rng: seeded_rng(config.seed),
+            fn_depth: 0,
+        }
+    }
+
+    pub fn obfuscate_ints(&mut self, code: &str) -> String {
+        let ast = parse_file(code).expect("Failed to parse code");
+        if is_disabled(&ast.attrs, Pass::Ints) {
+            return code.to_string();
+        }
+        let mut modified_ast = ast.clone();
+        self.visit_file_mut(&mut modified_ast);
+        quote!(#modified_ast).to_string()
+    }
+
+    //visit a function body, unless the function is a const fn whose body is a const context
+    fn visit_fn_body(&mut self, is_const: bool, block: &mut Block) {
+        if is_const {
+            return;
+        }
+        self.fn_depth += 1;
+        self.visit_block_mut(block);
+        self.fn_depth -= 1;
+    }
+}
+
+impl VisitMut for IntObfuscator {
+    fn visit_item_mut(&mut self, i: &mut Item) {
+        if !is_disabled(item_attrs(i), Pass::Ints) {
+            syn::visit_mut::visit_item_mut(self, i);
+        }
+    }
+
+    fn visit_impl_item_mut(&mut self, i: &mut ImplItem) {
+        if !is_disabled(impl_item_attrs(i), Pass::Ints) {
+            syn::visit_mut::visit_impl_item_mut(self, i);
+        }
+    }
+
+    fn visit_trait_item_mut(&mut self, i: &mut TraitItem) {
+        if !is_disabled(trait_item_attrs(i), Pass::Ints) {
+            syn::visit_mut::visit_trait_item_mut(self, i);
+        }
+    }
+
+    fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
+        self.visit_fn_body(i.sig.constness.is_some(), &mut i.block);
+    }
+
+    fn visit_impl_item_fn_mut(&mut self, i: &mut ImplItemFn) {
+        self.visit_fn_body(i.sig.constness.is_some(), &mut i.block);
+    }
+
+    fn visit_trait_item_fn_mut(&mut self, i: &mut TraitItemFn) {
+        if let Some(block) = &mut i.default {
+            self.visit_fn_body(i.sig.constness.is_some(), block);
+        }
+    }
+
+    //replace integer literals in function bodies with call to obfuscation macro
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        //`-128` only fits an i8 as a negated literal, so leave possible signed minimums alone
+        if let Expr::Unary(ExprUnary { op: UnOp::Neg(_), expr: operand, .. }) = expr {
+            if let Expr::Lit(ExprLit { lit: Lit::Int(lit_int), .. }) = &**operand {
+                if lit_int.base10_parse::<u128>().is_ok_and(|value| value >= 128 && value.is_power_of_two()) {
+                    return;
+                }
+            }
+        }
+        if let Expr::Lit(ExprLit { lit: Lit::Int(lit_int), attrs }) = expr {
+            if self.fn_depth > 0 && attrs.is_empty() {
+                if self.use_macro {
+                    *expr = parse_quote!(cryptify::encrypt_int!(#lit_int));
+                } else if let Ok(value) = lit_int.base10_parse::<u128>() {
+                    //literals the macro would reject stay as they are
+                    if let Some(encrypted) = encrypt_int(value, lit_int.suffix(), &mut self.rng) {
+                        *expr = parse_quote!(#encrypted);
+                    }
+                }
+            }
+            return;
+        }
+        syn::visit_mut::visit_expr_mut(self, expr);
+    }
+
+    //array repeat lengths are const contexts
+    fn visit_expr_repeat_mut(&mut self, i: &mut ExprRepeat) {
+        self.visit_expr_mut(&mut i.expr);
+    }
+
+    fn visit_expr_const_mut(&mut self, _i: &mut ExprConst) {}
+
+    //const generic arguments such as `foo::<3>()`
+    fn visit_generic_argument_mut(&mut self, i: &mut GenericArgument) {
+        if !matches!(i, GenericArgument::Const(_)) {
+            syn::visit_mut::visit_generic_argument_mut(self, i);
+        }
+    }
+
+    //types only hold const expressions like array lengths
+    fn visit_type_mut(&mut self, _i: &mut Type) {}
+
+    //literal and range patterns must stay literals
+    fn visit_pat_mut(&mut self, _i: &mut Pat) {}
+
+    fn visit_attribute_mut(&mut self, _i: &mut Attribute) {}
+
+    fn visit_item_const_mut(&mut self, _i: &mut ItemConst) {}
+
+    fn visit_item_static_mut(&mut self, _i: &mut ItemStatic) {}
+
+    fn visit_impl_item_const_mut(&mut self, _i: &mut ImplItemConst) {}
+
+    fn visit_trait_item_const_mut(&mut self, _i: &mut TraitItemConst) {}
+
+    //enum discriminants are const contexts
+    fn visit_item_enum_mut(&mut self, _i: &mut ItemEnum) {}
+}
@@ -0,0 +1,101 @@
+#[cfg(test)]
+use super::*;
+
+#[test]
+fn test_int_replacement_in_fn_body() {
+    let code =
+        r#"
+        fn main() {
+            let license = 0xC0FFEE;
+            let id = 42u16;
+            let min: i8 = -128;
+            let negative = -5;
+            if license == 12648430 {
+                println!("{}", id + 1);
+            }
+        }
+    "#;
+    let int_config = IntConfig { enable_int_obfuscation: true, ..IntConfig::default() };
+    let mut int_obfuscator = IntObfuscator::new(int_config);
+    let obfuscated_code = int_obfuscator.obfuscate_ints(code);
+
+    assert!(obfuscated_code.contains("cryptify :: encrypt_int ! (0xC0FFEE)"));
+    assert!(obfuscated_code.contains("cryptify :: encrypt_int ! (42u16)"));
+    assert!(obfuscated_code.contains("cryptify :: encrypt_int ! (12648430)"));
+    assert!(obfuscated_code.contains("- cryptify :: encrypt_int ! (5)"));
+    assert!(obfuscated_code.contains("min : i8 = - 128"));
+    //macro arguments are left alone
+    assert!(obfuscated_code.contains("id + 1"));
+
+    let parse_result = syn::parse_file(&obfuscated_code);
+    assert!(parse_result.is_ok(), "Modified code is not valid Rust code");
+}
+
+#[test]
+fn test_int_const_contexts_skipped() {
+    let code =
+        r#"
+        const LIMIT: usize = 10;
+        enum Kind { A = 1, B = 2 }
+        struct Wrapper { data: [u8; 4] }
+
+        impl Wrapper {
+            const SIZE: usize = 4;
+            const fn size() -> usize { 4 }
+        }
+
+        fn main() {
+            let buffer = [0u8; 16];
+            let typed: [u8; 8] = [7; 8];
+            let n = generic::<3>();
+            match buffer.len() {
+                0 => {}
+                1..=5 => {}
+                _ => {}
+            }
+        }
+    "#;
+    let int_config = IntConfig { enable_int_obfuscation: true, ..IntConfig::default() };
+    let mut int_obfuscator = IntObfuscator::new(int_config);
+    let obfuscated_code = int_obfuscator.obfuscate_ints(code);
+
+    assert!(obfuscated_code.contains("const LIMIT : usize = 10"));
+    assert!(obfuscated_code.contains("A = 1"));
+    assert!(obfuscated_code.contains("[u8 ; 4]"));
+    assert!(obfuscated_code.contains("const SIZE : usize = 4"));
+    assert!(obfuscated_code.contains("const fn size () -> usize { 4 }"));
+    assert!(obfuscated_code.contains("[cryptify :: encrypt_int ! (0u8) ; 16]"));
+    assert!(obfuscated_code.contains("[u8 ; 8] = [cryptify :: encrypt_int ! (7) ; 8]"));
+    assert!(obfuscated_code.contains("generic :: < 3 > ()"));
+    assert!(obfuscated_code.contains("0 => { }"));
+    assert!(obfuscated_code.contains("1 ..= 5 => { }"));
+
+    let parse_result = syn::parse_file(&obfuscated_code);
+    assert!(parse_result.is_ok(), "Modified code is not valid Rust code");
+}
+
+#[test]
+fn test_int_disable_macro() {
+    let code =
+        r#"
+        fn main() {
+            let id = 42u16;
+            let big = 1e3;
+        }
+    "#;
+    let int_config = IntConfig { enable_int_obfuscation: true, use_macro: false, seed: Some(7) };
+    let mut int_obfuscator = IntObfuscator::new(int_config);
+    let obfuscated_code = int_obfuscator.obfuscate_ints(code);
+
+    assert!(!obfuscated_code.contains("encrypt_int"));
+    assert!(!obfuscated_code.contains("42u16"));
+    assert_eq!(obfuscated_code.matches("std :: hint :: black_box").count(), 3);
+    assert_eq!(obfuscated_code.matches("u16").count(), 3);
+    assert!(obfuscated_code.contains("1e3"));
+    //seeded runs are reproducible
+    let mut int_obfuscator = IntObfuscator::new(IntConfig { enable_int_obfuscation: true, use_macro: false, seed: Some(7) });
+    assert_eq!(obfuscated_code, int_obfuscator.obfuscate_ints(code));
+
+    let parse_result = syn::parse_file(&obfuscated_code);
+    assert!(parse_result.is_ok(), "Modified code is not valid Rust code");
+}
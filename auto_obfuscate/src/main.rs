@@ -1,11 +1,23 @@
 mod rename;
+mod names;
 mod flow;
 mod string;
 mod obfuscate;
+mod int;
+mod attributes;
+mod mapping;
 use crate::obfuscate::{ Obfuscator, Config };
+use crate::rename::{ VariableRenamer, ModuleSource, Rename };
+use crate::mapping::RenameMap;
+use crate::attributes::{ strip_obfuscate_attributes, ObfuscateOptions };
+use crate::names::NameStyle;
+use crate::string::SelectionStrategy;
+use labyrinth_core::seed::parse_seed;
 use clap::{ App, Arg };
-use std::path::Path;
+use syn::{ Expr, ExprLit, Item, Lit, Meta };
+use std::path::{ Path, PathBuf };
 use std::fs;
+use std::io::{ self, Read };
 
 fn main() {
     //default config
@@ -14,6 +26,7 @@ fn main() {
         .version("1.0")
         .author("Pranav Dronavalli")
         .about("Obfuscates Rust source code")
+        .subcommand_negates_reqs(true)
         .arg(
             Arg::with_name("path")
                 .help("Path to the Rust file or directory")
@@ -25,16 +38,102 @@ fn main() {
         .arg(
             Arg::with_name("disable_macro")
                 .long("disable_macro")
-                .help("disable macro and modify source directly for flow obfuscation")
+                .help("write out what the cryptify macros expand to instead of calling them")
+        )
+        .arg(
+            Arg::with_name("flatten")
+                .long("flatten")
+                .help("rewrite function bodies into a dispatcher loop over encoded states")
+        )
+        .arg(
+            Arg::with_name("opaque_predicates")
+                .long("opaque_predicates")
+                .help("wrap statements in branches guarded by opaque predicates, with dead code in the arm never taken")
+        )
+        .arg(
+            Arg::with_name("flow_density")
+                .long("flow_density")
+                .help("percentage of blocks and statements flow obfuscation injects code into, 33 by default")
+                .value_name("PERCENTAGE")
+        )
+        .arg(
+            Arg::with_name("max_flow_per_fn")
+                .long("max_flow_per_fn")
+                .help("inject at most this many blocks of junk code and opaque branches into one function")
+                .value_name("N")
+        )
+        .arg(
+            Arg::with_name("flow_budget")
+                .long("flow_budget")
+                .help("let flow obfuscation grow a file by at most this percentage of its size")
+                .value_name("PERCENTAGE")
         )
         .arg(Arg::with_name("var").long("var").help("Enable variable renaming"))
+        .arg(
+            Arg::with_name("rename_types")
+                .long("rename_types")
+                .help("also rename private structs, enums, variants, traits, fields and methods, implies --var")
+        )
+        .arg(
+            Arg::with_name("name_style")
+                .long("name_style")
+                .help("what new names look like: random, confusable, homoglyph or short, homoglyph names need #![allow(mixed_script_confusables)] in the crate root")
+                .value_name("STYLE")
+        )
+        .arg(Arg::with_name("int").long("int").help("Enable integer literal obfuscation"))
         .arg(Arg::with_name("p")
                  .short('p')
                  .long("percent_strings_to_encrypt")
                  .help("set upper bound for string literal encryption")
                  .value_name("PERCENTAGE"))
+        .arg(
+            Arg::with_name("strategy")
+                .long("strategy")
+                .help("how -p picks string literals: random, first or priority")
+                .value_name("STRATEGY")
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .help("seed that makes every random choice reproducible, an integer or any string")
+                .value_name("SEED")
+        )
+        .arg(
+            Arg::with_name("crate")
+                .long("crate")
+                .help("obfuscate the whole crate rooted at the path, renaming consistently across its files")
+        )
+        .arg(
+            Arg::with_name("string_report")
+                .long("string_report")
+                .help("print which string literals were encrypted")
+        )
+        .arg(
+            Arg::with_name("mapping")
+                .long("mapping")
+                .help("write every rename with its file and line to a JSON file")
+                .value_name("FILE")
+        )
+        .arg(
+            Arg::with_name("apply_mapping")
+                .long("apply_mapping")
+                .alias("apply-mapping")
+                .help("give the symbols of a JSON file written by --mapping their names from it again")
+                .value_name("FILE")
+        )
+        .subcommand(
+            App::new("deobfuscate")
+                .about("Restores the original names in obfuscated code or any other text, like a backtrace")
+                .arg(Arg::with_name("map").help("JSON file written by --mapping").required(true).index(1))
+                .arg(Arg::with_name("file").help("file to deobfuscate, stdin if not given").index(2))
+        )
         .get_matches();
 
+    if let Some(matches) = matches.subcommand_matches("deobfuscate") {
+        deobfuscate(matches.value_of("map").unwrap(), matches.value_of("file"));
+        return;
+    }
+
     let path = matches.value_of("path").unwrap();
 
     //disable string obfuscation if the flag is set
@@ -50,11 +149,69 @@ fn main() {
     //disable use of proc macro if the flag is set
     if matches.is_present("disable_macro") {
         config.flow_config.use_macro = false;
+        config.string_config.use_macro = false;
+        config.int_config.use_macro = false;
+    }
+    //enable control flow flattening if the flag is set
+    if matches.is_present("flatten") {
+        config.flow_config.flatten = true;
+    }
+    //enable opaque predicates if the flag is set
+    if matches.is_present("opaque_predicates") {
+        config.flow_config.opaque_predicates = true;
+    }
+    //set how often flow obfuscation injects code
+    if let Some(density) = matches.value_of("flow_density") {
+        config.flow_config.density = match density.parse() {
+            Ok(n) if n <= 100 => n,
+            _ => {
+                eprintln!("--flow_density: expected integer between 0 and 100, got: `{}`", density);
+                eprintln!("defaulting to 33%");
+                33
+            }
+        };
+    }
+    //cap the code flow obfuscation injects into one function
+    if let Some(max) = matches.value_of("max_flow_per_fn") {
+        match max.parse() {
+            Ok(n) => config.flow_config.max_per_function = Some(n),
+            Err(_) => {
+                eprintln!("--max_flow_per_fn: expected integer, got: `{}`", max);
+                eprintln!("defaulting to no limit");
+            }
+        }
+    }
+    //cap the code flow obfuscation injects into one file
+    if let Some(budget) = matches.value_of("flow_budget") {
+        match budget.parse() {
+            Ok(n) => config.flow_config.size_budget = Some(n),
+            Err(_) => {
+                eprintln!("--flow_budget: expected integer, got: `{}`", budget);
+                eprintln!("defaulting to no limit");
+            }
+        }
     }
     //enable variable renaming if the flag is set
     if matches.is_present("var") {
         config.rename_config.enable_rename_obfuscation = true;
     }
+    //enable renaming of private types and their members if the flag is set
+    if matches.is_present("rename_types") {
+        config.rename_config.enable_rename_obfuscation = true;
+        config.rename_config.rename_types = true;
+    }
+    //set what the names given by renaming look like
+    if let Some(style) = matches.value_of("name_style") {
+        config.rename_config.name_style = NameStyle::from_name(style).unwrap_or_else(|| {
+            eprintln!("--name_style: expected random, confusable, homoglyph or short, got: `{}`", style);
+            eprintln!("defaulting to random");
+            NameStyle::Random
+        });
+    }
+    //enable integer literal obfuscation if the flag is set
+    if matches.is_present("int") {
+        config.int_config.enable_int_obfuscation = true;
+    }
     //set upper bound for string literal encryption
     if let Some(percentage) = matches.value_of("p") {
         config.string_config.percentage = match percentage.parse() {
@@ -66,37 +223,257 @@ fn main() {
             }
         };
     }
+    //set how the string literals to encrypt are picked
+    if let Some(strategy) = matches.value_of("strategy") {
+        config.string_config.strategy = SelectionStrategy::from_name(strategy).unwrap_or_else(|| {
+            eprintln!("--strategy: expected random, first or priority, got: `{}`", strategy);
+            eprintln!("defaulting to first");
+            SelectionStrategy::FirstN
+        });
+    }
+    //make the output reproducible if a seed is set
+    if let Some(seed) = matches.value_of("seed") {
+        config.seed = Some(parse_seed(seed));
+    }
+    //report the encrypted string literals of every file if the flag is set
+    if matches.is_present("string_report") {
+        config.string_config.report = true;
+    }
+    //keep the names of an earlier run for the symbols that are still there
+    if let Some(mapping) = matches.value_of("apply_mapping") {
+        match RenameMap::load(Path::new(mapping)) {
+            Ok(map) => config.rename_config.previous = map.symbols,
+            Err(err) => {
+                eprintln!("--apply_mapping: failed to read mapping file {}: {}", mapping, err);
+                eprintln!("defaulting to new names for every symbol");
+            }
+        }
+    }
 
-    process_path(&path, &config);
+    let mut renames = Vec::new();
+    if matches.is_present("crate") {
+        process_crate(path, &config, &mut renames);
+    } else {
+        process_path(path, &config, &mut renames);
+    }
+
+    //write the renames of all processed files if a mapping file is set
+    if let Some(mapping) = matches.value_of("mapping") {
+        let map = RenameMap { symbols: renames };
+        if let Err(err) = map.save(Path::new(mapping)) {
+            eprintln!("Failed to write mapping file {}: {}", mapping, err);
+        }
+    }
 }
 
-fn process_path(path_str: &str, config: &Config) {
+//print `file`, or stdin, with the names renamed according to `map` restored
+fn deobfuscate(map: &str, file: Option<&str>) {
+    let map = match RenameMap::load(Path::new(map)) {
+        Ok(map) => map,
+        Err(err) => {
+            eprintln!("Failed to read mapping file {}: {}", map, err);
+            return;
+        }
+    };
+    let text = match file {
+        Some(file) => fs::read_to_string(file),
+        None => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text).map(|_| text)
+        }
+    };
+    match text {
+        Ok(text) => print!("{}", map.deobfuscate(&text)),
+        Err(err) => eprintln!("Failed to read {}: {}", file.unwrap_or("stdin"), err),
+    }
+}
+
+fn process_path(path_str: &str, config: &Config, renames: &mut Vec<Rename>) {
     let path = Path::new(path_str);
     if path.is_dir() {
-        process_directory(path, config);
+        process_directory(path, config, renames);
     } else if path.is_file() {
-        process_file(path, config);
+        process_file(path, config, renames);
     } else {
         eprintln!("Invalid path: {}", path_str);
     }
 }
 //process all files in directory
-fn process_directory(dir_path: &Path, config: &Config) {
+fn process_directory(dir_path: &Path, config: &Config, renames: &mut Vec<Rename>) {
     for entry in fs::read_dir(dir_path).expect("Failed to read directory") {
         let entry = entry.expect("Failed to read entry");
         let path = entry.path();
         if path.is_file() {
-            process_file(&path, config);
+            process_file(&path, config, renames);
         }
     }
 }
+//every crate root of a crate directory, `src/lib.rs` and `src/main.rs` or `lib.rs` and `main.rs`
+fn crate_roots(path: &Path) -> Vec<PathBuf> {
+    if path.is_file() {
+        return vec![path.to_path_buf()];
+    }
+    let in_dir = |dir: &Path| -> Vec<PathBuf> {
+        ["lib.rs", "main.rs"]
+            .iter()
+            .map(|root| dir.join(root))
+            .filter(|root| root.is_file())
+            .collect()
+    };
+    let roots = in_dir(&path.join("src"));
+    if roots.is_empty() { in_dir(path) } else { roots }
+}
+
+//obfuscate every file of the crates at `path`, renamed items keep one name across all files of a crate
+fn process_crate(path_str: &str, config: &Config, renames: &mut Vec<Rename>) {
+    let roots = crate_roots(Path::new(path_str));
+    if roots.is_empty() {
+        eprintln!("No crate root (lib.rs or main.rs) found at: {}", path_str);
+    }
+    for root in roots {
+        let root_dir = root.parent().unwrap_or(Path::new("")).to_path_buf();
+        let mut modules = Vec::new();
+        collect_crate_files(root.clone(), Vec::new(), true, ObfuscateOptions::default(), &mut modules);
+
+        //every pass except renaming works file by file
+        let mut sources = Vec::new();
+        for module in &modules {
+            let mut file_config = config.for_file(&module.path.to_string_lossy()).without_passes(&module.options.disabled);
+            if let Some(density) = module.options.flow_density {
+                file_config.flow_config.density = density;
+            }
+            let mut obfuscator = Obfuscator::from_config(file_config.clone());
+            sources.push(ModuleSource {
+                module: module.module.clone(),
+                file: module.path.display().to_string(),
+                code: obfuscator.obfuscate_file(&module.code),
+                original: module.code.clone(),
+            });
+            if file_config.string_config.enable_string_obfuscation && config.string_config.report {
+                println!("{}: {}", module.path.display(), obfuscator.string_report());
+            }
+        }
+        let obfuscated = if config.rename_config.enable_rename_obfuscation {
+            let rename_config = config.for_file(&root.to_string_lossy()).rename_config;
+            let mut renamer = VariableRenamer::new(rename_config);
+            let obfuscated = renamer.rename_crate(&sources);
+            renames.append(&mut renamer.renames);
+            obfuscated
+        } else {
+            sources.into_iter().map(|source| source.code).collect()
+        };
+
+        for (module, obfuscated_code) in modules.iter().zip(obfuscated) {
+            let file_path = &module.path;
+            let obfuscated_code = strip_obfuscate_attributes(&obfuscated_code);
+            if syn::parse_file(&obfuscated_code).is_err() {
+                eprintln!("Obfuscated code is not valid Rust code: {}", file_path.display());
+                continue;
+            }
+            let relative_path = file_path.strip_prefix(&root_dir).unwrap_or(file_path);
+            write_obfuscated_code(relative_path, &obfuscated_code);
+        }
+    }
+}
+
+//a file of the crate being obfuscated
+struct CrateFile {
+    module: Vec<String>,
+    path: PathBuf,
+    code: String,
+    //what `#[obfuscate(...)]` on its `mod` declaration or an enclosing one sets
+    options: ObfuscateOptions,
+}
+
+//read the file of the module `module` and, following its `mod` declarations, those of its submodules.
+//`mod_rs` is set for crate roots, `mod.rs` files and files loaded through `#[path]`, whose submodules
+//live next to them rather than in a directory named after the module
+fn collect_crate_files(
+    file_path: PathBuf,
+    module: Vec<String>,
+    mod_rs: bool,
+    options: ObfuscateOptions,
+    modules: &mut Vec<CrateFile>
+) {
+    let Ok(code) = fs::read_to_string(&file_path) else {
+        eprintln!("Failed to read module file: {}", file_path.display());
+        return;
+    };
+    let Ok(file) = syn::parse_file(&code) else {
+        eprintln!("Failed to parse module file: {}", file_path.display());
+        return;
+    };
+    //inner `#![obfuscate(...)]` attributes of the file work like the attribute on its `mod` declaration
+    let options = options.nested(&file.attrs);
+    let file_dir = file_path.parent().unwrap_or(Path::new("")).to_path_buf();
+    let child_dir = match file_path.file_stem() {
+        Some(stem) if !mod_rs => file_dir.join(stem),
+        _ => file_dir.clone(),
+    };
+    modules.push(CrateFile { module: module.clone(), path: file_path, code, options: options.clone() });
+    collect_module_files(&file.items, &file_dir, &child_dir, &module, false, &options, modules);
+}
+
+fn collect_module_files(
+    items: &[Item],
+    file_dir: &Path,
+    child_dir: &Path,
+    module: &[String],
+    inline: bool,
+    options: &ObfuscateOptions,
+    modules: &mut Vec<CrateFile>
+) {
+    for item in items {
+        let Item::Mod(item_mod) = item else {
+            continue;
+        };
+        let name = item_mod.ident.to_string();
+        let mut child = module.to_vec();
+        child.push(name.clone());
+        let child_options = options.nested(&item_mod.attrs);
+        match &item_mod.content {
+            Some((_, items)) => {
+                collect_module_files(items, file_dir, &child_dir.join(&name), &child, true, &child_options, modules);
+            }
+            None => {
+                //`#[path]` is relative to the file's directory, or inside an inline module to the
+                //directory that module's files would be in
+                let path_attr = item_mod.attrs.iter().find_map(|attr| match &attr.meta {
+                    Meta::NameValue(meta) if meta.path.is_ident("path") => match &meta.value {
+                        Expr::Lit(ExprLit { lit: Lit::Str(path), .. }) => Some(path.value()),
+                        _ => None,
+                    },
+                    _ => None,
+                });
+                let (file_path, mod_rs) = match path_attr {
+                    Some(path) if inline => (child_dir.join(path), true),
+                    Some(path) => (file_dir.join(path), true),
+                    None => {
+                        let file_path = child_dir.join(format!("{}.rs", name));
+                        if file_path.is_file() {
+                            (file_path, false)
+                        } else {
+                            (child_dir.join(&name).join("mod.rs"), true)
+                        }
+                    }
+                };
+                collect_crate_files(file_path, child, mod_rs, child_options, modules);
+            }
+        }
+    }
+}
+
 //read code from file
-fn process_file(file_path: &Path, config: &Config) {
+fn process_file(file_path: &Path, config: &Config, renames: &mut Vec<Rename>) {
     if file_path.extension().unwrap_or_default() == "rs" {
         let code = fs::read_to_string(file_path).expect("Failed to read file");
 
-        let mut obfuscator = Obfuscator::from_config(config.clone());
+        let mut obfuscator = Obfuscator::from_config(config.for_file(&file_path.to_string_lossy()));
         let obfuscated_code = obfuscator.obfuscate(&code);
+        if config.string_config.enable_string_obfuscation && config.string_config.report {
+            println!("{}: {}", file_path.display(), obfuscator.string_report());
+        }
+        renames.extend_from_slice(obfuscator.renames());
 
         //check if obfuscated code is valid Rust code
         let parse_result = syn::parse_file(&obfuscated_code);
@@ -104,15 +481,16 @@ fn process_file(file_path: &Path, config: &Config) {
             eprintln!("Obfuscated code is not valid Rust code");
             return;
         }
-        write_obfuscated_code(file_path, &obfuscated_code);
+        write_obfuscated_code(Path::new(file_path.file_name().unwrap()), &obfuscated_code);
     }
 }
-//write file to obfuscated_code directory
-fn write_obfuscated_code(original_path: &Path, obfuscated_code: &str) {
-    let obfuscated_dir = Path::new("obfuscated_code");
-    fs::create_dir_all(&obfuscated_dir).expect("Failed to create directory");
+//write file to obfuscated_code directory, under its path relative to the directory or crate root
+fn write_obfuscated_code(relative_path: &Path, obfuscated_code: &str) {
+    let obfuscated_path = Path::new("obfuscated_code").join(relative_path);
+    if let Some(obfuscated_dir) = obfuscated_path.parent() {
+        fs::create_dir_all(obfuscated_dir).expect("Failed to create directory");
+    }
 
-    let obfuscated_path = obfuscated_dir.join(original_path.file_name().unwrap());
     println!("Writing to {:?}", obfuscated_path);
     fs::write(obfuscated_path, obfuscated_code).expect("Failed to write obfuscated code");
 }
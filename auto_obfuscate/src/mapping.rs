@@ -0,0 +1,53 @@
+use crate::rename::Rename;
+use serde::{ Deserialize, Serialize };
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[cfg(test)]
+mod mapping_tests;
+
+//every rename of a run, written by `--mapping` and read back by `deobfuscate`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenameMap {
+    pub symbols: Vec<Rename>,
+}
+
+impl RenameMap {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(io::Error::from)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::from)?;
+        fs::write(path, json)
+    }
+
+    //replace every renamed identifier in `text`, like obfuscated code or a backtrace, by its
+    //original name. Only whole identifiers are replaced, and if files renamed separately handed
+    //out the same name, the first rename of the map wins
+    pub fn deobfuscate(&self, text: &str) -> String {
+        let mut originals: HashMap<&str, &str> = HashMap::new();
+        for rename in &self.symbols {
+            originals.entry(rename.renamed.as_str()).or_insert(rename.original.as_str());
+        }
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find(is_ident_char) {
+            result.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = rest.find(|c: char| !is_ident_char(c)).unwrap_or(rest.len());
+            let ident = &rest[..end];
+            result.push_str(originals.get(ident).copied().unwrap_or(ident));
+            rest = &rest[end..];
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
@@ -0,0 +1,69 @@
+#[cfg(test)]
+use super::*;
+use crate::rename::{ RenameConfig, SymbolKind, VariableRenamer };
+
+fn rename_map(code: &str) -> (String, RenameMap) {
+    let rename_config = RenameConfig {
+        enable_rename_obfuscation: true,
+        rename_types: true,
+        seed: Some(3),
+        file: "src/main.rs".to_string(),
+        ..RenameConfig::default()
+    };
+    let mut renamer = VariableRenamer::new(rename_config);
+    let renamed = renamer.rename(code);
+    (renamed, RenameMap { symbols: renamer.renames })
+}
+
+#[test]
+fn test_deobfuscate_restores_renamed_code() {
+    let code = r#"
+        struct Counter { hits: u32 }
+        impl Counter {
+            fn bump(&mut self, by: u32) { self.hits += by; }
+        }
+        fn main() {
+            let mut counter = Counter { hits: 0 };
+            counter.bump(2);
+            println!("{}", counter.hits);
+        }
+    "#;
+    let (renamed, map) = rename_map(code);
+    let file = syn::parse_file(code).unwrap();
+    assert_ne!(renamed, quote::quote!(#file).to_string());
+    assert_eq!(map.deobfuscate(&renamed), quote::quote!(#file).to_string());
+}
+
+#[test]
+fn test_deobfuscate_replaces_whole_identifiers_only() {
+    let rename = |original: &str, renamed: &str| Rename {
+        original: original.to_string(),
+        renamed: renamed.to_string(),
+        kind: SymbolKind::Local,
+        symbol: format!("main::{}", original),
+        file: "main.rs".to_string(),
+        line: 1,
+    };
+    let map = RenameMap { symbols: vec![rename("count", "ab"), rename("total", "ab_c")] };
+    let text = "thread 'main' panicked at ab_c: ab=1, abc=2, xab, ab.ab_c()";
+    assert_eq!(map.deobfuscate(text), "thread 'main' panicked at total: count=1, abc=2, xab, count.total()");
+}
+
+#[test]
+fn test_map_round_trip() {
+    let (_, map) = rename_map("fn helper(x: i32) -> i32 { x }\nfn main() { helper(1); }");
+    let path = std::env::temp_dir().join(format!("rename_map_{}.json", std::process::id()));
+    map.save(&path).unwrap();
+    let json = fs::read_to_string(&path).unwrap();
+    assert!(json.contains("\"kind\": \"fn\""), "{}", json);
+    let loaded = RenameMap::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.symbols.len(), map.symbols.len());
+    for (loaded, saved) in loaded.symbols.iter().zip(&map.symbols) {
+        assert_eq!(
+            (&loaded.original, &loaded.renamed, loaded.kind, &loaded.symbol, &loaded.file, loaded.line),
+            (&saved.original, &saved.renamed, saved.kind, &saved.symbol, &saved.file, saved.line)
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use rand::{ rngs::StdRng, Rng };
+use proc_macro2::{ TokenStream, TokenTree };
+use std::collections::HashSet;
+use crate::rename::SymbolKind;
+
+#[cfg(test)]
+mod names_tests;
+
+//what generated names look like
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NameStyle {
+    //lowercase letters with the odd underscore, like `qwz_nc`
+    Random,
+    //only `I`, `l` and `1`, like `Il1lI1l`, or `l` and `1` for names that must be snake_case
+    Confusable,
+    //latin letters mixed with their cyrillic lookalikes, like `сoрy`
+    Homoglyph,
+    //the shortest free names in order, `a`, `b`, ..., `aa`, `ab`
+    Short,
+}
+
+impl NameStyle {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "random" => Some(Self::Random),
+            "confusable" => Some(Self::Confusable),
+            "homoglyph" => Some(Self::Homoglyph),
+            "short" => Some(Self::Short),
+            _ => None,
+        }
+    }
+}
+
+//strict, reserved and weak keywords of every edition, none of them works as a name
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe",
+    "use", "where", "while", "abstract", "become", "box", "do", "final", "gen", "macro", "override",
+    "priv", "try", "typeof", "unsized", "virtual", "yield", "macro_rules", "raw", "safe", "union",
+];
+
+pub fn is_keyword(name: &str) -> bool {
+    KEYWORDS.contains(&name)
+}
+
+//latin letters and the cyrillic letters that look the same
+const HOMOGLYPHS: &[(char, char)] = &[
+    ('a', 'а'),
+    ('c', 'с'),
+    ('e', 'е'),
+    ('i', 'і'),
+    ('o', 'о'),
+    ('p', 'р'),
+    ('x', 'х'),
+    ('y', 'у'),
+];
+
+//hands out names that are never keywords, never an identifier the code already uses and never
+//handed out twice
+pub struct NameGenerator {
+    style: NameStyle,
+    rng: StdRng,
+    used: HashSet<String>,
+    next_short: usize,
+}
+
+impl NameGenerator {
+    pub fn new(style: NameStyle, rng: StdRng) -> Self {
+        Self {
+            style,
+            rng,
+            used: HashSet::new(),
+            next_short: 0,
+        }
+    }
+
+    //keep `name` from being generated, false if it was already taken
+    pub fn reserve(&mut self, name: &str) -> bool {
+        self.used.insert(name.to_string())
+    }
+
+    //reserve every identifier of `tokens`, including those inside macro calls
+    pub fn reserve_idents(&mut self, tokens: TokenStream) {
+        for token in tokens {
+            match token {
+                TokenTree::Ident(ident) => {
+                    self.reserve(&ident.to_string());
+                }
+                TokenTree::Group(group) => self.reserve_idents(group.stream()),
+                _ => {}
+            }
+        }
+    }
+
+    //a name for a symbol of `kind`, UpperCamelCase for types, variants and traits and snake_case
+    //for everything else, like the naming lints expect
+    pub fn next_name(&mut self, kind: SymbolKind) -> String {
+        let camel_case = matches!(kind, SymbolKind::Type | SymbolKind::Variant);
+        loop {
+            let name = match self.style {
+                NameStyle::Random => self.random_name(),
+                NameStyle::Confusable => self.confusable_name(camel_case),
+                NameStyle::Homoglyph => self.homoglyph_name(),
+                NameStyle::Short => self.short_name(),
+            };
+            let name = if camel_case { upper_camel_case(&name) } else { name };
+            //two names that only differ in lookalike letters trip `confusable_idents`, so the
+            //all-latin spelling of a homoglyph name is taken along with it
+            let latin = latin_lookalike(&name);
+            if !is_keyword(&name) && !self.used.contains(&latin) && self.used.insert(name.clone()) {
+                self.used.insert(latin);
+                return name;
+            }
+        }
+    }
+
+    fn random_name(&mut self) -> String {
+        let rng = &mut self.rng;
+        let name_length = rng.gen_range(3..=10);
+
+        let mut last_char_was_underscore = false;
+        let mut name = String::new();
+
+        while name.len() < name_length {
+            let next_char = if rng.gen_bool(0.8) { rng.gen_range(b'a'..=b'z') as char } else { '_' };
+
+            // Ensure not two underscores in a row
+            if !(last_char_was_underscore && next_char == '_') {
+                name.push(next_char);
+                last_char_was_underscore = next_char == '_';
+            }
+        }
+        // Ensure the name does not start or end with an underscore
+        if name.starts_with('_') {
+            name.remove(0);
+            name.insert(0, rng.gen_range(b'a'..=b'z') as char);
+        }
+        if name.ends_with('_') {
+            name.pop();
+            name.push(rng.gen_range(b'a'..=b'z') as char);
+        }
+
+        name
+    }
+
+    //names can't start with a digit, so the first letter is `I` for a camel case name and `l`
+    //otherwise, and snake_case leaves out the `I`
+    fn confusable_name(&mut self, camel_case: bool) -> String {
+        let name_length = self.rng.gen_range(8..=12);
+        let letters: &[char] = if camel_case { &['I', 'l', '1'] } else { &['l', '1'] };
+        let mut name = String::new();
+        name.push(if camel_case { 'I' } else { 'l' });
+        while name.len() < name_length {
+            name.push(letters[self.rng.gen_range(0..letters.len())]);
+        }
+        name
+    }
+
+    fn homoglyph_name(&mut self) -> String {
+        let name_length = self.rng.gen_range(4..=10);
+        (0..name_length)
+            .map(|_| {
+                let (latin, cyrillic) = HOMOGLYPHS[self.rng.gen_range(0..HOMOGLYPHS.len())];
+                if self.rng.gen_bool(0.5) { latin } else { cyrillic }
+            })
+            .collect()
+    }
+
+    //the `next_short`th name of `a`..`z`, `aa`..`zz`, `aaa`..
+    fn short_name(&mut self) -> String {
+        let mut index = self.next_short;
+        self.next_short += 1;
+        let mut name = Vec::new();
+        loop {
+            name.push(b'a' + (index % 26) as u8);
+            if index < 26 {
+                break;
+            }
+            index = index / 26 - 1;
+        }
+        name.reverse();
+        String::from_utf8(name).unwrap()
+    }
+}
+
+//`сoрy` as `copy`
+fn latin_lookalike(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            let lower = c.to_lowercase().next().unwrap_or(c);
+            match HOMOGLYPHS.iter().find(|&&(_, cyrillic)| cyrillic == lower) {
+                Some(&(latin, _)) if c.is_uppercase() => latin.to_ascii_uppercase(),
+                Some(&(latin, _)) => latin,
+                None => c,
+            }
+        })
+        .collect()
+}
+
+//`qwz_nc` as `QwzNc`
+fn upper_camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            chars.next().map(|first| first.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default()
+        })
+        .collect()
+}
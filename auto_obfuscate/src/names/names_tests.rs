@@ -0,0 +1,78 @@
+#[cfg(test)]
+use super::*;
+use crate::obfuscate::seeded_rng;
+
+#[test]
+fn test_names_are_unique_valid_identifiers() {
+    for style in [NameStyle::Random, NameStyle::Confusable, NameStyle::Homoglyph, NameStyle::Short] {
+        let mut names = NameGenerator::new(style, seeded_rng(Some(1)));
+        let mut seen = HashSet::new();
+        for _ in 0..1000 {
+            let name = names.next_name(SymbolKind::Local);
+            assert!(!is_keyword(&name), "{:?} generated the keyword `{}`", style, name);
+            assert!(syn::parse_str::<syn::Ident>(&name).is_ok(), "{:?} generated `{}`", style, name);
+            assert!(seen.insert(name.clone()), "{:?} generated `{}` twice", style, name);
+        }
+    }
+}
+
+#[test]
+fn test_styles() {
+    let mut names = NameGenerator::new(NameStyle::Confusable, seeded_rng(Some(1)));
+    let name = names.next_name(SymbolKind::Local);
+    assert!(name.chars().all(|c| matches!(c, 'I' | 'l' | '1')), "{}", name);
+
+    let mut names = NameGenerator::new(NameStyle::Homoglyph, seeded_rng(Some(1)));
+    let name = names.next_name(SymbolKind::Local);
+    assert!(name.chars().all(|c| HOMOGLYPHS.iter().any(|&(latin, cyrillic)| c == latin || c == cyrillic)), "{}", name);
+    //no two names look the same, nor like one the code already uses
+    names.reserve("copy");
+    let lookalikes: HashSet<String> = (0..1000).map(|_| latin_lookalike(&names.next_name(SymbolKind::Local))).collect();
+    assert_eq!(lookalikes.len(), 1000);
+    assert!(!lookalikes.contains("copy"));
+    assert_eq!(latin_lookalike("Сoрy"), "Copy");
+
+    let mut names = NameGenerator::new(NameStyle::Short, seeded_rng(Some(1)));
+    let generated: Vec<String> = (0..28).map(|_| names.next_name(SymbolKind::Local)).collect();
+    assert_eq!(&generated[..3], ["a", "b", "c"]);
+    assert_eq!(&generated[25..], ["z", "aa", "ab"]);
+}
+
+#[test]
+fn test_keywords_and_reserved_names_are_skipped() {
+    let mut names = NameGenerator::new(NameStyle::Short, seeded_rng(Some(1)));
+    names.reserve("a");
+    names.reserve_idents(quote::quote!(fn b() { println!("{}", c); }));
+    assert_eq!(names.next_name(SymbolKind::Local), "d");
+
+    //`as`, `do`, `fn`, `if` and `in` are keywords
+    let generated: Vec<String> = (0..26 * 27).map(|_| names.next_name(SymbolKind::Local)).collect();
+    for keyword in ["as", "do", "fn", "if", "in"] {
+        assert!(!generated.iter().any(|name| name == keyword), "generated `{}`", keyword);
+    }
+}
+
+#[test]
+fn test_type_names_are_camel_case() {
+    let mut names = NameGenerator::new(NameStyle::Random, seeded_rng(Some(1)));
+    for kind in [SymbolKind::Type, SymbolKind::Variant] {
+        for _ in 0..100 {
+            let name = names.next_name(kind);
+            assert!(name.starts_with(|c: char| c.is_ascii_uppercase()) && !name.contains('_'), "{:?} named `{}`", kind, name);
+        }
+    }
+    for kind in [SymbolKind::Fn, SymbolKind::Member, SymbolKind::Local] {
+        let name = names.next_name(kind);
+        assert!(!name.contains(|c: char| c.is_ascii_uppercase()), "{:?} named `{}`", kind, name);
+    }
+    assert_eq!(upper_camel_case("qwz_nc"), "QwzNc");
+
+    //confusable names keep to `l` and `1` where `I` would break snake_case
+    let mut names = NameGenerator::new(NameStyle::Confusable, seeded_rng(Some(1)));
+    for _ in 0..100 {
+        let name = names.next_name(SymbolKind::Local);
+        assert!(name.starts_with('l') && name.chars().all(|c| matches!(c, 'l' | '1')), "{}", name);
+        let name = names.next_name(SymbolKind::Type);
+        assert!(name.starts_with('I') && name.chars().all(|c| matches!(c, 'I' | 'l' | '1')), "{}", name);
+    }
+}
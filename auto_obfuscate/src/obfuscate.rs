@@ -1,12 +1,18 @@
 use crate::string::{ StringObfuscator, StringConfig };
-use crate::rename::{ VariableRenamer, RenameConfig };
+use crate::rename::{ VariableRenamer, RenameConfig, Rename };
 use crate::flow::{ FlowObfuscator, FlowConfig };
+use crate::int::{ IntObfuscator, IntConfig };
+use crate::attributes::{ strip_obfuscate_attributes, Pass };
+use labyrinth_core::seed::derive_seed;
+use rand::{ rngs::StdRng, SeedableRng };
 
 #[derive(Clone)]
 pub struct Config {
     pub rename_config: RenameConfig,
     pub flow_config: FlowConfig,
     pub string_config: StringConfig,
+    pub int_config: IntConfig,
+    pub seed: Option<u64>,
 }
 
 impl Config {
@@ -15,14 +21,53 @@ impl Config {
             rename_config: RenameConfig::default(),
             flow_config: FlowConfig::default(),
             string_config: StringConfig::default(),
+            int_config: IntConfig::default(),
+            seed: None,
         }
     }
+
+    //config for one file, every pass gets its own seed derived from the build-wide one and the file
+    pub fn for_file(&self, file: &str) -> Self {
+        let mut config = self.clone();
+        config.rename_config.file = file.to_string();
+        if let Some(seed) = self.seed {
+            let pass_seed = |pass: &str| Some(derive_seed(seed, format!("{}:{}", file, pass).as_bytes()));
+            config.rename_config.seed = pass_seed("rename");
+            config.flow_config.seed = pass_seed("flow");
+            config.string_config.seed = pass_seed("string");
+            config.int_config.seed = pass_seed("int");
+        }
+        config
+    }
+
+    //config for a file whose `mod` declaration turned `passes` off, renaming sees those attributes itself
+    pub fn without_passes(&self, passes: &[Pass]) -> Self {
+        let mut config = self.clone();
+        for pass in passes {
+            match pass {
+                Pass::Strings => config.string_config.enable_string_obfuscation = false,
+                Pass::Ints => config.int_config.enable_int_obfuscation = false,
+                Pass::Flow => config.flow_config.enable_flow_obfuscation = false,
+                Pass::Rename => {}
+            }
+        }
+        config
+    }
+}
+
+//reproducible when seeded, random otherwise
+pub fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
 }
 
 pub struct Obfuscator {
     rename_obfuscator: VariableRenamer,
     flow_obfuscator: FlowObfuscator,
     string_obfuscator: StringObfuscator,
+    int_obfuscator: IntObfuscator,
 }
 
 impl Obfuscator {
@@ -32,6 +77,7 @@ impl Obfuscator {
             rename_obfuscator: VariableRenamer::new(RenameConfig::default()),
             flow_obfuscator: FlowObfuscator::new(FlowConfig::default()),
             string_obfuscator: StringObfuscator::new(StringConfig::default()),
+            int_obfuscator: IntObfuscator::new(IntConfig::default()),
         }
     }
     pub fn from_config(config: Config) -> Self {
@@ -39,20 +85,40 @@ impl Obfuscator {
             rename_obfuscator: VariableRenamer::new(config.rename_config),
             flow_obfuscator: FlowObfuscator::new(config.flow_config),
             string_obfuscator: StringObfuscator::new(config.string_config),
+            int_obfuscator: IntObfuscator::new(config.int_config),
         }
     }
 
+    //which string literals the last file had encrypted
+    pub fn string_report(&self) -> String {
+        self.string_obfuscator.report()
+    }
+
+    //what renaming did to the last file
+    pub fn renames(&self) -> &[Rename] {
+        &self.rename_obfuscator.renames
+    }
+
     pub fn obfuscate(&mut self, code: &str) -> String {
+        let mut result = self.obfuscate_file(code);
+        if self.rename_obfuscator.enabled {
+            result = self.rename_obfuscator.rename_file(code, &result);
+        }
+        strip_obfuscate_attributes(&result)
+    }
+
+    //every pass except renaming, which in crate mode runs once all files went through this
+    pub fn obfuscate_file(&mut self, code: &str) -> String {
         let mut result = code.to_string();
         if self.string_obfuscator.enabled {
             result = self.string_obfuscator.obfuscate_strings(&result);
         }
+        if self.int_obfuscator.enabled {
+            result = self.int_obfuscator.obfuscate_ints(&result);
+        }
         if self.flow_obfuscator.enabled {
             result = self.flow_obfuscator.flow_obfuscate(&result);
         }
-        if self.rename_obfuscator.enabled {
-            result = self.rename_obfuscator.rename(&result);
-        }
         result
     }
 }
@@ -1,23 +1,67 @@
-use rand::{ Rng };
+use crate::obfuscate::seeded_rng;
+use crate::names::{ NameGenerator, NameStyle };
+use crate::attributes::{ impl_item_attrs, is_disabled, item_attrs, trait_item_attrs, Pass };
+use crate::string::format::{ join_format_string, parse_format_string, rename_placeholder, FormatMacro, FormatPiece };
 use syn::{
-    visit_mut::VisitMut,
+    visit_mut::{ self, VisitMut },
     parse_file,
+    Arm,
+    Attribute,
+    Block,
+    Expr,
+    ExprAssign,
+    ExprClosure,
+    ExprField,
+    ExprForLoop,
+    ExprIf,
+    ExprLet,
+    ExprLit,
+    ExprMethodCall,
+    ExprPath,
+    ExprWhile,
+    Field,
+    FieldValue,
+    File,
+    FnArg,
+    Generics,
     Ident,
+    ImplItem,
+    ImplItemFn,
+    Item,
+    ItemEnum,
     ItemFn,
+    ItemImpl,
+    ItemMod,
+    ItemStruct,
+    ItemTrait,
+    ItemUnion,
+    ItemUse,
+    Lit,
+    LitStr,
     Local,
-    Expr,
-    ExprPath,
     Macro,
-    Visibility,
+    Member,
+    Pat,
+    PatIdent,
+    Path,
+    PathSegment,
+    QSelf,
+    Signature,
+    Stmt,
+    TraitItem,
+    TraitItemFn,
+    Type,
+    TypePath,
     UseTree,
-    UsePath,
-    UseName,
-    UseRename,
-    ItemUse,
+    Visibility,
 };
 use quote::quote;
+use serde::{ Deserialize, Serialize };
 use std::collections::{ HashMap, HashSet };
-use proc_macro2::{ TokenStream, TokenTree, Group };
+use proc_macro2::{ TokenStream, TokenTree, Group, Spacing };
+
+mod members;
+use members::{ renamable_members, renamed_bounds };
 
 #[cfg(test)]
 mod rename_tests;
@@ -25,6 +69,14 @@ mod rename_tests;
 #[derive(Clone)]
 pub struct RenameConfig {
     pub enable_rename_obfuscation: bool,
+    //also rename private structs, enums, variants, traits, fields and methods
+    pub rename_types: bool,
+    pub name_style: NameStyle,
+    pub seed: Option<u64>,
+    //the file being renamed, as recorded in the rename map
+    pub file: String,
+    //renames of an earlier run whose names are given again to the symbols that are still there
+    pub previous: Vec<Rename>,
 }
 
 //default rename to false
@@ -32,216 +84,1303 @@ impl RenameConfig {
     pub fn default() -> Self {
         Self {
             enable_rename_obfuscation: false,
+            rename_types: false,
+            name_style: NameStyle::Random,
+            seed: None,
+            file: String::new(),
+            previous: Vec::new(),
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+    //a fn item
+    Fn,
+    //a struct, enum or trait
+    Type,
+    Variant,
+    //a field or method
+    Member,
+    //a variable, parameter or pattern binding
+    Local,
+}
+
+//a symbol that got a new name
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Rename {
+    pub original: String,
+    pub renamed: String,
+    pub kind: SymbolKind,
+    //what the symbol is, stable as long as the code around it doesn't change: the item path like
+    //`util::helper` or `Shape::Circle`, for locals the path of their fn like `main::x` with `#1`,
+    //`#2`, ... for further bindings of that name, and just the name for fields and methods
+    pub symbol: String,
+    pub file: String,
+    //line of the definition or binding, 0 if it isn't in the original code
+    pub line: usize,
+}
+
+//a file of the crate being renamed
+pub struct ModuleSource {
+    //its module path, empty for the crate root
+    pub module: Vec<String>,
+    pub file: String,
+    pub code: String,
+    //the code before the other passes reformatted it, where the lines of the rename map come from
+    pub original: String,
+}
+
+//what a name in scope refers to, `name` is the original spelling if the binding isn't renamed
+#[derive(Clone, Debug)]
+struct Binding {
+    name: String,
+    item: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ScopeKind {
+    //a file or inline `mod`, nothing outside of it is visible by its bare name
+    Module,
+    //a fn body, outer locals are not visible from it but outer items are
+    Fn,
+    //a block, closure, loop, `if let` or match arm
+    Block,
+}
+
+struct Scope {
+    kind: ScopeKind,
+    bindings: HashMap<String, Binding>,
+}
+
+//a renamed item of a module
+#[derive(Clone)]
+struct ModuleItem {
+    name: String,
+    //the module it is visible in, along with all modules below that one
+    visible_in: Vec<String>,
+}
+
+//what `Self` or a path prefix like `Shape::` refers to when it is followed by an associated item
+#[derive(Clone)]
+enum Owner {
+    //a renamed struct, enum or trait, by its new name
+    Type(String),
+    //the implementor inside a renamed trait or an impl of one, only its methods are known
+    Implementor,
+}
+
 pub struct VariableRenamer {
-    renamed_vars: HashMap<String, String>,
-    imported_functions: HashSet<String>,
+    pub renames: Vec<Rename>,
     pub enabled: bool,
+    names: NameGenerator,
+    //lexical scopes from the file root to the current position
+    scopes: Vec<Scope>,
+    //renamed items of the crate root and every inline or file module, keyed by module path
+    module_items: HashMap<Vec<String>, HashMap<String, ModuleItem>>,
+    module_path: Vec<String>,
+    rename_types: bool,
+    //renamed structs, enums and traits by new name, with the new names of their variants
+    types: HashMap<String, HashMap<String, String>>,
+    //renamed fields and methods, receivers aren't typed so a member keeps one name in the whole file
+    members: HashMap<String, String>,
+    //what `Self` refers to in the impls and traits around the current position
+    owners: Vec<Option<Owner>>,
+    //the type parameters around the current position, and whether they are bounded by a renamed
+    //trait, whose methods are renamed in paths like `T::make`
+    generics: Vec<(String, bool)>,
+    file: String,
+    current_file: String,
+    //original names of the fns, impls and traits around the current position, for symbol paths
+    item_path: Vec<String>,
+    symbol_counts: HashMap<String, usize>,
+    //original names of renamed symbols by new name
+    originals: HashMap<String, String>,
+    previous: Vec<Rename>,
+    //the names of `previous` still free to give again, by file, kind and symbol
+    reusable: HashMap<(String, SymbolKind, String), String>,
+    //items and modules marked `#[obfuscate(rename = false)]` around the current position, nothing
+    //declared inside them gets a new name but their uses of renamed names are still updated
+    frozen: usize,
+    //paths of the modules marked that way, their files keep their names too
+    frozen_modules: Vec<Vec<String>>,
 }
 
 impl VariableRenamer {
     pub fn new(config: RenameConfig) -> Self {
         VariableRenamer {
-            renamed_vars: HashMap::new(),
-            imported_functions: HashSet::new(),
+            renames: Vec::new(),
             enabled: config.enable_rename_obfuscation,
+            names: NameGenerator::new(config.name_style, seeded_rng(config.seed)),
+            scopes: Vec::new(),
+            module_items: HashMap::new(),
+            module_path: Vec::new(),
+            rename_types: config.rename_types,
+            types: HashMap::new(),
+            members: HashMap::new(),
+            owners: Vec::new(),
+            generics: Vec::new(),
+            file: config.file,
+            current_file: String::new(),
+            item_path: Vec::new(),
+            symbol_counts: HashMap::new(),
+            originals: HashMap::new(),
+            previous: config.previous,
+            reusable: HashMap::new(),
+            frozen: 0,
+            frozen_modules: Vec::new(),
         }
     }
-    //helper to process Macros tokenstream and check if it is an identifier or another macro or func call
-    fn process_tokens(&mut self, tokens: TokenStream) -> TokenStream {
-        tokens
-            .into_iter()
-            .map(|token| {
-                match token {
-                    TokenTree::Group(group) => {
-                        let modified_tokens = self.process_tokens(group.stream());
-                        TokenTree::Group(Group::new(group.delimiter(), modified_tokens))
-                    }
-                    TokenTree::Ident(ident) => {
-                        if let Some(new_name) = self.renamed_vars.get(&ident.to_string()) {
-                            TokenTree::Ident(Ident::new(new_name, ident.span()))
-                        } else {
-                            TokenTree::Ident(ident)
-                        }
-                    }
-                    _ => token,
-                }
+    #[allow(dead_code)]
+    pub fn rename(&mut self, code: &str) -> String {
+        self.rename_file(code, code)
+    }
+
+    //rename `code`, the result of the other passes on `original`
+    pub fn rename_file(&mut self, original: &str, code: &str) -> String {
+        let source = ModuleSource {
+            module: Vec::new(),
+            file: self.file.clone(),
+            code: code.to_string(),
+            original: original.to_string(),
+        };
+        self.rename_crate(&[source]).remove(0)
+    }
+
+    //rename the files of a crate so an item keeps one new name in the file defining it and in
+    //every file using it. All files are collected before any of them is renamed
+    pub fn rename_crate(&mut self, sources: &[ModuleSource]) -> Vec<String> {
+        let mut files: Vec<File> = sources
+            .iter()
+            .map(|source| parse_file(&source.code).expect("Failed to parse code"))
+            .collect();
+        let first_rename = self.renames.len();
+        self.module_items.clear();
+        self.types.clear();
+        self.members.clear();
+        self.symbol_counts.clear();
+        //new names must not capture or collide with any name the crate already uses
+        for file in files.iter() {
+            self.names.reserve_idents(quote!(#file));
+        }
+        //an earlier name can only be given again if the code doesn't use it by now
+        self.reusable.clear();
+        for rename in self.previous.iter() {
+            if sources.iter().any(|source| source.file == rename.file) && self.names.reserve(&rename.renamed) {
+                let key = (rename.file.clone(), rename.kind, rename.symbol.clone());
+                self.reusable.insert(key, rename.renamed.clone());
+            }
+        }
+        self.frozen_modules.clear();
+        for (source, file) in sources.iter().zip(files.iter()) {
+            //`#![obfuscate(no_rename)]` of a file freezes its module like the attribute on its `mod`
+            if is_disabled(&file.attrs, Pass::Rename) {
+                self.frozen_modules.push(source.module.clone());
+            }
+            collect_frozen_modules(&file.items, &source.module, &mut self.frozen_modules);
+        }
+        let frozen_files: Vec<bool> = sources.iter().map(|source| self.is_frozen_module(&source.module)).collect();
+        let members = if self.rename_types { renamable_members(&files, &frozen_files) } else { Vec::new() };
+        for ((source, file), &frozen) in sources.iter().zip(files.iter_mut()).zip(frozen_files.iter()) {
+            self.current_file = source.file.clone();
+            self.frozen = usize::from(frozen);
+            self.collect_module_items(&mut file.items, source.module.clone());
+        }
+        for (member, index) in members {
+            self.current_file = sources[index].file.clone();
+            let symbol = member.to_string();
+            let new_name = self.new_name(&member, SymbolKind::Member, symbol);
+            self.members.insert(member.to_string(), new_name);
+        }
+        let renamed = sources
+            .iter()
+            .zip(files.iter_mut())
+            .zip(frozen_files)
+            .map(|((source, file), frozen)| {
+                self.module_path = source.module.clone();
+                self.current_file = source.file.clone();
+                self.frozen = usize::from(frozen);
+                self.visit_file_mut(file);
+                quote!(#file).to_string()
             })
+            .collect();
+        self.frozen = 0;
+
+        //spans of reformatted code are all on one line, take the lines from the original code
+        if sources.iter().any(|source| source.code != source.original) {
+            let lines = self.locate(sources);
+            for rename in self.renames[first_rename..].iter_mut() {
+                rename.line = lines.get(&(rename.kind, rename.symbol.clone())).copied().unwrap_or(0);
+            }
+        }
+        renamed
+    }
+
+    //lines of the symbols of the original code, found by renaming it too
+    fn locate(&self, sources: &[ModuleSource]) -> HashMap<(SymbolKind, String), usize> {
+        let mut locator = VariableRenamer::new(RenameConfig {
+            enable_rename_obfuscation: true,
+            rename_types: self.rename_types,
+            name_style: NameStyle::Short,
+            ..RenameConfig::default()
+        });
+        let originals: Vec<ModuleSource> = sources
+            .iter()
+            .map(|source| ModuleSource {
+                module: source.module.clone(),
+                file: source.file.clone(),
+                code: source.original.clone(),
+                original: source.original.clone(),
+            })
+            .collect();
+        locator.rename_crate(&originals);
+        locator.renames
+            .into_iter()
+            .map(|rename| ((rename.kind, rename.symbol), rename.line))
             .collect()
     }
 
-    //scan use statements to identify imported functions and add them to blacklist
-    fn identify_imported_functions(&mut self, tree: &UseTree) {
-        match tree {
-            UseTree::Path(UsePath { ident: _, tree, .. }) => {
-                self.identify_imported_functions(tree);
+    fn new_name(&mut self, ident: &Ident, kind: SymbolKind, mut symbol: String) -> String {
+        let count = self.symbol_counts.entry(symbol.clone()).or_insert(0);
+        if *count > 0 {
+            symbol = format!("{}#{}", symbol, count);
+        }
+        *count += 1;
+        let renamed = match self.reusable.remove(&(self.current_file.clone(), kind, symbol.clone())) {
+            Some(renamed) => renamed,
+            None => self.names.next_name(kind),
+        };
+        self.originals.insert(renamed.clone(), ident.to_string());
+        self.renames.push(Rename {
+            original: ident.to_string(),
+            renamed: renamed.clone(),
+            kind,
+            symbol,
+            file: self.current_file.clone(),
+            line: ident.span().start().line,
+        });
+        renamed
+    }
+
+    //path of `name` defined at the current position
+    fn symbol(&self, name: &Ident) -> String {
+        let mut path = self.module_path.clone();
+        path.extend(self.item_path.iter().cloned());
+        path.push(name.to_string());
+        path.join("::")
+    }
+
+    fn is_frozen_module(&self, module: &[String]) -> bool {
+        self.frozen_modules.iter().any(|frozen| module.starts_with(frozen))
+    }
+
+    fn original_name(&self, ident: &Ident) -> String {
+        let name = ident.to_string();
+        self.originals.get(&name).cloned().unwrap_or(name)
+    }
+
+    fn push_scope(&mut self, kind: ScopeKind) {
+        self.scopes.push(Scope { kind, bindings: HashMap::new() });
+    }
+
+    fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn bind(&mut self, original: &str, name: String, item: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.bindings.insert(original.to_string(), Binding { name, item });
+        }
+    }
+
+    //resolve a bare name to the innermost binding visible from the current scope
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        let mut locals_visible = true;
+        for scope in self.scopes.iter().rev() {
+            if let Some(binding) = scope.bindings.get(name) {
+                if binding.item || locals_visible {
+                    return Some(binding);
+                }
             }
-            UseTree::Name(UseName { ident }) => {
-                self.imported_functions.insert(ident.to_string());
+            match scope.kind {
+                ScopeKind::Module => {
+                    return None;
+                }
+                ScopeKind::Fn => {
+                    locals_visible = false;
+                }
+                ScopeKind::Block => {}
             }
-            UseTree::Rename(UseRename { rename, .. }) => {
-                self.imported_functions.insert(rename.to_string());
+        }
+        None
+    }
+
+    //new name of a bare identifier, None unless it resolves to a renamed binding
+    fn renamed(&self, ident: &Ident) -> Option<Ident> {
+        let original = ident.to_string();
+        self.lookup(&original)
+            .filter(|binding| binding.name != original)
+            .map(|binding| Ident::new(&binding.name, ident.span()))
+    }
+
+    //like `renamed`, but only items can be the first segment of a longer path
+    fn renamed_item(&self, ident: &Ident) -> Option<Ident> {
+        let original = ident.to_string();
+        self.lookup(&original)
+            .filter(|binding| binding.item && binding.name != original)
+            .map(|binding| Ident::new(&binding.name, ident.span()))
+    }
+
+    //new name of a field or method
+    fn renamed_member(&self, ident: &Ident) -> Option<Ident> {
+        self.members.get(&ident.to_string()).map(|name| Ident::new(name, ident.span()))
+    }
+
+    //new name of a variant or associated item of `owner`
+    fn renamed_assoc(&self, owner: &Owner, ident: &Ident) -> Option<Ident> {
+        let variant = match owner {
+            Owner::Type(name) => self.types.get(name).and_then(|variants| variants.get(&ident.to_string())),
+            Owner::Implementor => None,
+        };
+        variant
+            .map(|name| Ident::new(name, ident.span()))
+            .or_else(|| self.renamed_member(ident))
+    }
+
+    fn self_owner(&self) -> Option<Owner> {
+        self.owners.last().cloned().flatten()
+    }
+
+    //bring the type parameters of `generics` into scope, after their bounds were renamed
+    fn push_generics(&mut self, generics: &Generics) -> usize {
+        let outer = self.generics.len();
+        let bounds = renamed_bounds(generics, |path| {
+            path.segments.last().is_some_and(|segment| self.types.contains_key(&segment.ident.to_string()))
+        });
+        self.generics.extend(bounds.into_iter().map(|(name, bound)| (name, bound.is_some())));
+        outer
+    }
+
+    fn is_bounded_generic(&self, ident: &Ident) -> bool {
+        self.generics.iter().rev().find(|(name, _)| ident == name).is_some_and(|(_, bounded)| *bounded)
+    }
+
+    //owner of the associated items behind `ident`, if it names a renamed type by its new name
+    fn owner_of(&self, ident: &Ident) -> Option<Owner> {
+        if ident == "Self" {
+            return self.self_owner();
+        }
+        let name = ident.to_string();
+        self.types.contains_key(&name).then_some(Owner::Type(name))
+    }
+
+    //module a path prefix like `crate::a`, `self::b`, `super` or `c` refers to
+    fn resolve_module<'a>(&self, segments: impl IntoIterator<Item = &'a Ident>) -> Option<Vec<String>> {
+        let mut module: Option<Vec<String>> = None;
+        for segment in segments {
+            let name = segment.to_string();
+            let first = module.is_none();
+            let mut current = module.unwrap_or_else(|| self.module_path.clone());
+            match name.as_str() {
+                "crate" if first => current.clear(),
+                "self" if first => {}
+                "super" => {
+                    current.pop()?;
+                }
+                _ => {
+                    current.push(name);
+                    if !self.module_items.contains_key(&current) {
+                        return None;
+                    }
+                }
             }
-            _ => {}
+            module = Some(current);
         }
+        module
     }
-    pub fn rename(&mut self, code: &str) -> String {
-        let ast = parse_file(code).expect("Failed to parse code");
-        let mut modified_ast = ast.clone();
-        self.visit_file_mut(&mut modified_ast);
-        let modified_code = quote!(#modified_ast).to_string();
-        modified_code
+
+    //new name of `ident` when imported or called through the module path `prefix`
+    fn renamed_in_module(&self, prefix: &[Ident], ident: &Ident) -> Option<Ident> {
+        self.resolve_module(prefix)
+            .and_then(|module| self.module_items.get(&module))
+            .and_then(|items| items.get(&ident.to_string()))
+            .filter(|item| self.is_visible(item))
+            .map(|item| Ident::new(&item.name, ident.span()))
     }
-}
 
-//check to see if the function is local, only rename local functions for now
-fn is_local_function(fn_item: &ItemFn) -> bool {
-    !matches!(fn_item.vis, Visibility::Public(_))
-}
+    //a path can't refer to an item that isn't visible from the current module
+    fn is_visible(&self, item: &ModuleItem) -> bool {
+        self.module_path.starts_with(&item.visible_in)
+    }
 
-impl VisitMut for VariableRenamer {
-    //visit use statements to identify imported functions
-    fn visit_item_use_mut(&mut self, i: &mut ItemUse) {
-        self.identify_imported_functions(&i.tree);
-        syn::visit_mut::visit_item_use_mut(self, i);
+    //new name of the item `ident` in the module `prefix` refers to, or in scope without a prefix
+    fn renamed_item_in(&self, prefix: &[Ident], ident: &Ident) -> Option<Ident> {
+        if prefix.is_empty() {
+            self.renamed_item(ident)
+        } else {
+            self.renamed_in_module(prefix, ident)
+        }
     }
 
-    fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
-        //rename function names unless it's main
-        let old_name = i.sig.ident.to_string();
-        if
-            old_name != "main" &&
-            is_local_function(i) &&
-            !self.imported_functions.contains(&old_name)
-        {
-            if !self.renamed_vars.contains_key(&old_name) {
-                let new_name = random_name();
-                self.renamed_vars.insert(old_name.clone(), new_name.clone());
-                i.sig.ident = Ident::new(&new_name, i.sig.ident.span());
+    //new name of the variant `ident` of the enum `prefix` refers to
+    fn renamed_variant(&self, prefix: &[Ident], ident: &Ident) -> Option<Ident> {
+        let variants = self.variants_of(prefix)?;
+        variants.get(&ident.to_string()).map(|name| Ident::new(name, ident.span()))
+    }
+
+    fn variants_of(&self, prefix: &[Ident]) -> Option<&HashMap<String, String>> {
+        let (owner, module) = prefix.split_last()?;
+        self.types.get(&self.renamed_item_in(module, owner)?.to_string())
+    }
+
+    //paths are `module::item::assoc`, where the module prefix and the associated item are optional
+    fn rename_path(&self, path: &mut Path) {
+        if path.leading_colon.is_some() {
+            return;
+        }
+        let idents: Vec<Ident> = path.segments
+            .iter()
+            .map(|segment| segment.ident.clone())
+            .collect();
+        let mut item = 0;
+        for end in 1..idents.len() {
+            if self.resolve_module(&idents[..end]).is_none() {
+                break;
             }
+            item = end;
         }
-        //rename function arguments
-        for input in &mut i.sig.inputs {
-            if let syn::FnArg::Typed(pat_type) = input {
-                if let syn::Pat::Ident(pat_ident) = &mut *pat_type.pat {
-                    let old_param = pat_ident.ident.to_string();
-                    let new_param = random_name();
-                    self.renamed_vars.insert(old_param.clone(), new_param.clone());
-                    pat_ident.ident = Ident::new(&new_param, pat_ident.ident.span());
-                }
+        let renamed = if idents.len() == 1 {
+            self.renamed(&idents[0])
+        } else {
+            self.renamed_item_in(&idents[..item], &idents[item])
+        };
+        let owner = match &renamed {
+            Some(renamed) => self.owner_of(renamed),
+            None if item == 0 && idents[0] == "Self" => self.self_owner(),
+            None if item == 0 && self.is_bounded_generic(&idents[0]) => Some(Owner::Implementor),
+            None => None,
+        };
+        if let Some(renamed) = renamed {
+            path.segments[item].ident = renamed;
+        }
+        if let (Some(owner), Some(segment)) = (owner, path.segments.iter_mut().nth(item + 1)) {
+            if let Some(renamed) = self.renamed_assoc(&owner, &segment.ident) {
+                segment.ident = renamed;
             }
         }
+    }
 
-        let len = i.block.stmts.len();
-        for (index, stmt) in i.block.stmts.iter_mut().enumerate() {
-            match stmt {
-                syn::Stmt::Local(local) => self.visit_local_mut(local),
+    //rename the path of a possibly qualified path like `<T as Trait>::method`, where only the part
+    //after `as` is renamed
+    fn visit_qualified_path(&mut self, qself: &mut Option<QSelf>, path: &mut Path) {
+        match qself {
+            Some(qself) => {
+                self.visit_type_mut(&mut qself.ty);
+                if qself.position > 0 {
+                    self.visit_path_mut(path);
+                } else {
+                    for segment in path.segments.iter_mut() {
+                        self.visit_path_arguments_mut(&mut segment.arguments);
+                    }
+                }
+            }
+            None => self.visit_path_mut(path),
+        }
+    }
 
-                //check if last statement is an expression
-                syn::Stmt::Expr(expr, _) if index == len - 1 => {
-                    self.visit_expr_mut(expr);
+    //assign new names to the private fns (and with `rename_types` the private types) of the file
+    //and of every inline module up front, so uses before the definition or through a module path resolve
+    fn collect_module_items(&mut self, items: &mut [Item], module: Vec<String>) {
+        let mut renamed = HashMap::new();
+        for item in items.iter_mut() {
+            match item {
+                Item::Fn(item_fn) if self.frozen == 0 && is_renamable_fn(item_fn, module.is_empty()) => {
+                    let original = item_fn.sig.ident.to_string();
+                    let symbol = item_symbol(&module, &[&original]);
+                    let new_name = self.new_name(&item_fn.sig.ident, SymbolKind::Fn, symbol);
+                    item_fn.sig.ident = Ident::new(&new_name, item_fn.sig.ident.span());
+                    renamed.insert(original, ModuleItem { name: new_name, visible_in: visibility_scope(&item_fn.vis, &module) });
                 }
+                Item::Struct(ItemStruct { vis, attrs, ident, .. }) |
+                Item::Trait(ItemTrait { vis, attrs, ident, .. }) if
+                    self.rename_types && self.frozen == 0 && is_renamable_type(vis, attrs)
+                => {
+                    let original = ident.to_string();
+                    let symbol = item_symbol(&module, &[&original]);
+                    let new_name = self.new_name(ident, SymbolKind::Type, symbol);
+                    *ident = Ident::new(&new_name, ident.span());
+                    self.types.insert(new_name.clone(), HashMap::new());
+                    renamed.insert(original, ModuleItem { name: new_name, visible_in: visibility_scope(vis, &module) });
+                }
+                Item::Enum(item_enum) if
+                    self.rename_types && self.frozen == 0 && is_renamable_type(&item_enum.vis, &item_enum.attrs)
+                => {
+                    let original = item_enum.ident.to_string();
+                    let symbol = item_symbol(&module, &[&original]);
+                    let new_name = self.new_name(&item_enum.ident, SymbolKind::Type, symbol);
+                    item_enum.ident = Ident::new(&new_name, item_enum.ident.span());
+                    let mut variants = HashMap::new();
+                    for variant in item_enum.variants.iter_mut() {
+                        let variant_name = variant.ident.to_string();
+                        let symbol = item_symbol(&module, &[&original, &variant_name]);
+                        let new_variant = self.new_name(&variant.ident, SymbolKind::Variant, symbol);
+                        variant.ident = Ident::new(&new_variant, variant.ident.span());
+                        variants.insert(variant_name, new_variant);
+                    }
+                    self.types.insert(new_name.clone(), variants);
+                    renamed.insert(original, ModuleItem { name: new_name, visible_in: visibility_scope(&item_enum.vis, &module) });
+                }
+                Item::Mod(ItemMod { attrs, ident, content: Some((_, items)), .. }) => {
+                    let mut child = module.clone();
+                    child.push(ident.to_string());
+                    let frozen = usize::from(is_disabled(attrs, Pass::Rename));
+                    self.frozen += frozen;
+                    self.collect_module_items(items, child);
+                    self.frozen -= frozen;
+                }
+                _ => {}
+            }
+        }
+        self.module_items.insert(module, renamed);
+    }
 
-                _ => syn::visit_mut::visit_stmt_mut(self, stmt),
+    fn enter_module(&mut self, items: &mut [Item]) {
+        self.push_scope(ScopeKind::Module);
+        let module_items = self.module_items.get(&self.module_path).cloned().unwrap_or_default();
+        for (original, item) in module_items {
+            self.bind(&original, item.name, true);
+        }
+        for item in items.iter_mut() {
+            if let Item::Use(item_use) = item {
+                self.hoist_use(item_use);
             }
         }
+        for item in items.iter_mut() {
+            self.visit_item_mut(item);
+        }
+        self.pop_scope();
     }
 
-    fn visit_macro_mut(&mut self, i: &mut Macro) {
-        i.tokens = self.process_tokens(i.tokens.clone());
+    //items of a block are visible in all of it, not just after their definition
+    fn hoist_block_items(&mut self, stmts: &mut [Stmt]) {
+        for stmt in stmts.iter_mut() {
+            match stmt {
+                Stmt::Item(Item::Fn(item_fn)) => {
+                    let original = item_fn.sig.ident.to_string();
+                    if self.frozen == 0 && is_renamable_fn(item_fn, false) {
+                        let symbol = self.symbol(&item_fn.sig.ident);
+                        let new_name = self.new_name(&item_fn.sig.ident, SymbolKind::Fn, symbol);
+                        item_fn.sig.ident = Ident::new(&new_name, item_fn.sig.ident.span());
+                        self.bind(&original, new_name, true);
+                    } else {
+                        self.bind(&original, original.clone(), true);
+                    }
+                }
+                //types local to a block keep their names but shadow renamed ones
+                Stmt::Item(
+                    Item::Struct(ItemStruct { ident, .. }) |
+                    Item::Enum(ItemEnum { ident, .. }) |
+                    Item::Union(ItemUnion { ident, .. }) |
+                    Item::Trait(ItemTrait { ident, .. }),
+                ) => {
+                    let original = ident.to_string();
+                    self.bind(&original, original.clone(), true);
+                }
+                _ => {}
+            }
+        }
+        for stmt in stmts.iter_mut() {
+            if let Stmt::Item(Item::Use(item_use)) = stmt {
+                self.hoist_use(item_use);
+            }
+        }
+    }
+
+    //imports of renamed items are renamed too and bring the new name into scope
+    fn hoist_use(&mut self, item_use: &mut ItemUse) {
+        if item_use.leading_colon.is_none() {
+            self.rename_use_tree(&mut item_use.tree, &mut Vec::new());
+        }
     }
 
-    fn visit_expr_mut(&mut self, expr: &mut Expr) {
-        //check expression type and rename vars accordingly
-        match expr {
-            Expr::Path(ExprPath { ref mut path, .. }) => {
-                if let Some(last_segment) = path.segments.last_mut() {
-                    let var_name = last_segment.ident.to_string();
-                    if let Some(new_name) = self.renamed_vars.get(&var_name) {
-                        last_segment.ident = Ident::new(new_name, last_segment.ident.span());
+    fn rename_use_tree(&mut self, tree: &mut UseTree, prefix: &mut Vec<Ident>) {
+        match tree {
+            UseTree::Path(use_path) => {
+                //the original name stays in the prefix to resolve what follows it
+                let original = use_path.ident.clone();
+                if let Some(renamed) = self.renamed_item_in(prefix, &use_path.ident) {
+                    use_path.ident = renamed;
+                }
+                prefix.push(original);
+                self.rename_use_tree(&mut use_path.tree, prefix);
+                prefix.pop();
+            }
+            UseTree::Group(group) => {
+                for tree in group.items.iter_mut() {
+                    self.rename_use_tree(tree, prefix);
+                }
+            }
+            UseTree::Name(use_name) if !prefix.is_empty() => {
+                let renamed = self
+                    .renamed_in_module(prefix, &use_name.ident)
+                    .or_else(|| self.renamed_variant(prefix, &use_name.ident));
+                if let Some(renamed) = renamed {
+                    let original = use_name.ident.to_string();
+                    self.bind(&original, renamed.to_string(), true);
+                    use_name.ident = renamed;
+                }
+            }
+            UseTree::Rename(use_rename) if !prefix.is_empty() => {
+                let renamed = self
+                    .renamed_in_module(prefix, &use_rename.ident)
+                    .or_else(|| self.renamed_variant(prefix, &use_rename.ident));
+                if let Some(renamed) = renamed {
+                    use_rename.ident = renamed;
+                }
+            }
+            UseTree::Glob(_) => {
+                //a glob only imports the items visible from here, and items or explicit imports of
+                //the scope shadow it
+                let items: Vec<(String, String)> = match self.resolve_module(prefix.iter()) {
+                    Some(module) => self.module_items
+                        .get(&module)
+                        .into_iter()
+                        .flatten()
+                        .filter(|(_, item)| self.is_visible(item))
+                        .map(|(original, item)| (original.clone(), item.name.clone()))
+                        .collect(),
+                    None => self
+                        .variants_of(prefix)
+                        .into_iter()
+                        .flatten()
+                        .map(|(original, renamed)| (original.clone(), renamed.clone()))
+                        .collect(),
+                };
+                for (original, renamed) in items {
+                    if !self.scopes.last().is_some_and(|scope| scope.bindings.contains_key(&original)) {
+                        self.bind(&original, renamed, true);
                     }
                 }
             }
-            Expr::Assign(expr_assign) => {
-                //check left of expression
-                if let Expr::Path(ExprPath { ref mut path, .. }) = *expr_assign.left {
-                    if let Some(last_segment) = path.segments.last_mut() {
-                        let var_name = last_segment.ident.to_string();
-                        if let Some(new_name) = self.renamed_vars.get(&var_name) {
-                            last_segment.ident = Ident::new(new_name, last_segment.ident.span());
+            _ => {}
+        }
+    }
+
+    //rename every binding of a pattern and bring it into the current scope
+    fn bind_pattern(&mut self, pat: &mut Pat) {
+        self.bind_subpattern(pat, &mut HashMap::new());
+    }
+
+    //`bound` holds the names already bound by this pattern, alternatives of an or-pattern
+    //bind the same names and have to agree on them
+    fn bind_subpattern(&mut self, pat: &mut Pat, bound: &mut HashMap<String, String>) {
+        match pat {
+            Pat::Ident(pat_ident) => {
+                if is_binding(pat_ident) {
+                    pat_ident.ident = self.bind_ident(&pat_ident.ident, bound);
+                } else if let Some(renamed) = self.renamed_item(&pat_ident.ident) {
+                    pat_ident.ident = renamed;
+                }
+                if let Some((_, subpat)) = &mut pat_ident.subpat {
+                    self.bind_subpattern(subpat, bound);
+                }
+            }
+            Pat::Struct(pat_struct) => {
+                self.visit_qualified_path(&mut pat_struct.qself, &mut pat_struct.path);
+                for field in pat_struct.fields.iter_mut() {
+                    //the shorthand `Point { x }` has to become `Point { x: renamed }`
+                    if field.colon_token.is_none() {
+                        field.colon_token = Some(Default::default());
+                    }
+                    if let Member::Named(member) = &mut field.member {
+                        if let Some(renamed) = self.renamed_member(member) {
+                            *member = renamed;
                         }
                     }
+                    self.bind_subpattern(&mut field.pat, bound);
                 }
-                // recursively visit right of assignment in case of more complex expression
-                self.visit_expr_mut(&mut *expr_assign.right);
-            }
-            //handle function call
-            Expr::Call(expr_call) => {
-                //rename function names
-                if let Expr::Path(expr_path) = &mut *expr_call.func {
-                    if let Some(last_segment) = expr_path.path.segments.last_mut() {
-                        let func_name = last_segment.ident.to_string();
-                        if let Some(new_name) = self.renamed_vars.get(&func_name) {
-                            last_segment.ident = Ident::new(new_name, last_segment.ident.span());
+            }
+            Pat::Or(pat_or) => {
+                for case in pat_or.cases.iter_mut() {
+                    self.bind_subpattern(case, bound);
+                }
+            }
+            Pat::Paren(pat_paren) => self.bind_subpattern(&mut pat_paren.pat, bound),
+            Pat::Reference(pat_reference) => self.bind_subpattern(&mut pat_reference.pat, bound),
+            Pat::Type(pat_type) => {
+                self.visit_type_mut(&mut pat_type.ty);
+                self.bind_subpattern(&mut pat_type.pat, bound);
+            }
+            Pat::Slice(pat_slice) => {
+                for elem in pat_slice.elems.iter_mut() {
+                    self.bind_subpattern(elem, bound);
+                }
+            }
+            Pat::Tuple(pat_tuple) => {
+                for elem in pat_tuple.elems.iter_mut() {
+                    self.bind_subpattern(elem, bound);
+                }
+            }
+            Pat::TupleStruct(pat_tuple_struct) => {
+                self.visit_qualified_path(&mut pat_tuple_struct.qself, &mut pat_tuple_struct.path);
+                for elem in pat_tuple_struct.elems.iter_mut() {
+                    self.bind_subpattern(elem, bound);
+                }
+            }
+            Pat::Path(pat_path) => self.visit_qualified_path(&mut pat_path.qself, &mut pat_path.path),
+            //literals, ranges, rest, wildcards and macros bind nothing
+            _ => {}
+        }
+    }
+
+    fn bind_ident(&mut self, ident: &Ident, bound: &mut HashMap<String, String>) -> Ident {
+        let original = ident.to_string();
+        let new_name = match bound.get(&original) {
+            Some(new_name) => new_name.clone(),
+            None if self.frozen > 0 => original.clone(),
+            None => {
+                let symbol = self.symbol(ident);
+                let new_name = self.new_name(ident, SymbolKind::Local, symbol);
+                bound.insert(original.clone(), new_name.clone());
+                new_name
+            }
+        };
+        self.bind(&original, new_name.clone(), false);
+        Ident::new(&new_name, ident.span())
+    }
+
+    fn visit_fn(&mut self, sig: &mut Signature, block: &mut Block) {
+        self.push_scope(ScopeKind::Fn);
+        self.visit_signature_types(sig);
+        let generics = self.push_generics(&sig.generics);
+        for input in sig.inputs.iter_mut() {
+            if let FnArg::Typed(pat_type) = input {
+                self.bind_pattern(&mut pat_type.pat);
+            }
+        }
+        self.visit_block_mut(block);
+        self.generics.truncate(generics);
+        self.pop_scope();
+    }
+
+    fn visit_signature_types(&mut self, sig: &mut Signature) {
+        self.visit_generics_mut(&mut sig.generics);
+        for input in sig.inputs.iter_mut() {
+            if let FnArg::Typed(pat_type) = input {
+                self.visit_type_mut(&mut pat_type.ty);
+            }
+        }
+        self.visit_return_type_mut(&mut sig.output);
+    }
+
+    //rename the arguments of a format macro like any other expression and the inline captures of its
+    //format string, like `x` in `{x}` or `{:width$}`. Names of named arguments stay as they are
+    fn rename_format_macro(&mut self, mac: &mut Macro) -> bool {
+        let Some(mut format_macro) = FormatMacro::parse(mac) else {
+            return false;
+        };
+        let format_index = format_macro.format_index;
+        let mut named = HashSet::new();
+        for (index, arg) in format_macro.args.iter_mut().enumerate() {
+            match arg {
+                Expr::Assign(ExprAssign { left, right, .. }) if index > format_index => {
+                    if let Expr::Path(ExprPath { path, .. }) = &**left {
+                        if let Some(name) = path.get_ident() {
+                            named.insert(name.to_string());
                         }
                     }
+                    self.visit_expr_mut(right);
+                }
+                _ if index != format_index => self.visit_expr_mut(arg),
+                _ => {}
+            }
+        }
+
+        let format_string = format_macro.format_string().clone();
+        if let Some(mut pieces) = parse_format_string(&format_string.value()) {
+            for piece in pieces.iter_mut() {
+                if let FormatPiece::Placeholder(placeholder) = piece {
+                    *placeholder = rename_placeholder(placeholder, |name| {
+                        if named.contains(name) {
+                            return None;
+                        }
+                        self.renamed(&Ident::new(name, format_string.span())).map(|renamed| renamed.to_string())
+                    });
+                }
+            }
+            let renamed_format = join_format_string(&pieces);
+            if renamed_format != format_string.value() {
+                format_macro.args[format_index] = Expr::Lit(ExprLit {
+                    attrs: Vec::new(),
+                    lit: Lit::Str(LitStr::new(&renamed_format, format_string.span())),
+                });
+            }
+        }
+        let args = &format_macro.args;
+        mac.tokens = quote!(#args);
+        true
+    }
+
+    //rename the transcribers of a `macro_rules!` definition, its matchers only hold metavariables,
+    //fragment specifiers and tokens call sites have to match
+    fn process_macro_rules(&mut self, tokens: TokenStream) -> TokenStream {
+        let mut output = Vec::new();
+        let mut after_arrow = false;
+        for token in tokens {
+            match token {
+                TokenTree::Group(group) if after_arrow => {
+                    let mut new_group = Group::new(group.delimiter(), self.process_tokens(group.stream()));
+                    new_group.set_span(group.span());
+                    output.push(TokenTree::Group(new_group));
+                    after_arrow = false;
                 }
-                //rename all function arguments
-                for arg in &mut expr_call.args {
-                    self.visit_expr_mut(arg);
+                token => {
+                    after_arrow = matches!(&token, TokenTree::Punct(punct) if punct.as_char() == '>') &&
+                        matches!(output.last(), Some(TokenTree::Punct(punct)) if punct.as_char() == '=');
+                    output.push(token);
                 }
             }
+        }
+        output.into_iter().collect()
+    }
 
-            _ => {}
+    //helper to process Macros tokenstream and rename identifiers and paths like `util::helper` that
+    //resolve to renamed bindings or items, field accesses, method calls and struct literal fields
+    //like `a.name` or `{ name: 1 }` only take the new name of a renamed member
+    fn process_tokens(&mut self, tokens: TokenStream) -> TokenStream {
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        let mut output = Vec::new();
+        //new names of the later segments of a path resolved at its first segment
+        let mut path_segments: HashMap<usize, Ident> = HashMap::new();
+        for (index, token) in tokens.iter().enumerate() {
+            let previous = index.checked_sub(1).map(|previous| &tokens[previous]);
+            let replaced = match token {
+                TokenTree::Group(group) => {
+                    let modified_tokens = self.process_tokens(group.stream());
+                    let mut new_group = Group::new(group.delimiter(), modified_tokens);
+                    new_group.set_span(group.span());
+                    TokenTree::Group(new_group)
+                }
+                //metavariables like `$x` of macro definitions, `$crate` starts a path like `crate`
+                TokenTree::Ident(ident) if is_punct(previous, '$') && ident != "crate" => TokenTree::Ident(ident.clone()),
+                TokenTree::Ident(ident) => {
+                    let struct_field =
+                        (previous.is_none() || is_punct(previous, ',')) &&
+                        matches!(tokens.get(index + 1), Some(TokenTree::Punct(punct)) if punct.as_char() == ':' && punct.spacing() == Spacing::Alone);
+                    let path_continuation = is_punct(previous, ':') && is_punct(index.checked_sub(2).map(|i| &tokens[i]), ':');
+                    let renamed = if path_continuation {
+                        path_segments.remove(&index)
+                    } else if is_punct(previous, '.') || struct_field {
+                        self.renamed_member(ident)
+                    } else {
+                        let indices = token_path(&tokens, index);
+                        let mut path = Path {
+                            leading_colon: None,
+                            segments: indices
+                                .iter()
+                                .filter_map(|&index| match &tokens[index] {
+                                    TokenTree::Ident(ident) => Some(PathSegment::from(ident.clone())),
+                                    _ => None,
+                                })
+                                .collect(),
+                        };
+                        self.rename_path(&mut path);
+                        let mut segments = indices.iter().zip(path.segments);
+                        let first = segments.next().map(|(_, segment)| segment.ident);
+                        for (&index, segment) in segments {
+                            path_segments.insert(index, segment.ident);
+                        }
+                        first.filter(|renamed| renamed != ident)
+                    };
+                    TokenTree::Ident(renamed.unwrap_or_else(|| ident.clone()))
+                }
+                _ => token.clone(),
+            };
+            output.push(replaced);
+        }
+        output.into_iter().collect()
+    }
+}
+
+//indices of the segments of the path `a::b::c` starting at `start`
+fn token_path(tokens: &[TokenTree], start: usize) -> Vec<usize> {
+    let mut indices = vec![start];
+    let mut last = start;
+    while
+        let (Some(TokenTree::Punct(first)), Some(TokenTree::Punct(second)), Some(TokenTree::Ident(_))) = (
+            tokens.get(last + 1),
+            tokens.get(last + 2),
+            tokens.get(last + 3),
+        )
+    {
+        if first.as_char() != ':' || first.spacing() != Spacing::Joint || second.as_char() != ':' {
+            break;
+        }
+        last += 3;
+        indices.push(last);
+    }
+    indices
+}
+
+fn is_punct(token: Option<&TokenTree>, ch: char) -> bool {
+    matches!(token, Some(TokenTree::Punct(punct)) if punct.as_char() == ch)
+}
+
+//only private functions are renamed, except the `main` entry point and opted out ones
+fn is_renamable_fn(fn_item: &ItemFn, root: bool) -> bool {
+    let entry_point = root && fn_item.sig.ident == "main";
+    !matches!(fn_item.vis, Visibility::Public(_)) && !entry_point && !is_disabled(&fn_item.attrs, Pass::Rename)
+}
+
+//paths of the inline and file modules marked `#[obfuscate(rename = false)]` among `items`
+fn collect_frozen_modules(items: &[Item], module: &[String], frozen_modules: &mut Vec<Vec<String>>) {
+    for item in items {
+        if let Item::Mod(item_mod) = item {
+            let mut child = module.to_vec();
+            child.push(item_mod.ident.to_string());
+            if is_disabled(&item_mod.attrs, Pass::Rename) {
+                frozen_modules.push(child.clone());
+            }
+            if let Some((_, items)) = &item_mod.content {
+                collect_frozen_modules(items, &child, frozen_modules);
+            }
         }
+    }
+}
 
-        syn::visit_mut::visit_expr_mut(self, expr);
+fn item_symbol(module: &[String], names: &[&str]) -> String {
+    module
+        .iter()
+        .map(String::as_str)
+        .chain(names.iter().copied())
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+//the module an item with visibility `vis` defined in `module` is visible in, `pub(crate)` is
+//visible in the whole crate, `pub(super)` in the parent module and `pub(in path)` in `path`
+fn visibility_scope(vis: &Visibility, module: &[String]) -> Vec<String> {
+    let mut scope = module.to_vec();
+    match vis {
+        Visibility::Public(_) => scope.clear(),
+        Visibility::Restricted(restricted) => {
+            for segment in restricted.path.segments.iter() {
+                match segment.ident.to_string().as_str() {
+                    "crate" => scope.clear(),
+                    "self" => {}
+                    "super" => {
+                        scope.pop();
+                    }
+                    name => scope.push(name.to_string()),
+                }
+            }
+        }
+        Visibility::Inherited => {}
     }
+    scope
+}
+
+//derives that don't depend on the names of a type, its fields or variants
+const PLAIN_DERIVES: &[&str] = &[
+    "Debug",
+    "Clone",
+    "Copy",
+    "PartialEq",
+    "Eq",
+    "PartialOrd",
+    "Ord",
+    "Hash",
+    "Default",
+];
+
+//only private types are renamed, and only if no derive like `Serialize` relies on their names and
+//they aren't opted out
+fn is_renamable_type(vis: &Visibility, attrs: &[Attribute]) -> bool {
+    let plain_derives = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("derive"))
+        .all(|attr| {
+            let mut plain = true;
+            let parsed = attr.parse_nested_meta(|meta| {
+                plain &= meta.path.segments
+                    .last()
+                    .is_some_and(|segment| PLAIN_DERIVES.contains(&segment.ident.to_string().as_str()));
+                Ok(())
+            });
+            parsed.is_ok() && plain
+        });
+    !matches!(vis, Visibility::Public(_)) && plain_derives && !is_disabled(attrs, Pass::Rename)
+}
 
-    //visit local variables
+//`None`, `MAX` or `Empty` in a pattern name a variant, constant or unit struct, not a new binding
+fn is_binding(pat_ident: &PatIdent) -> bool {
+    pat_ident.by_ref.is_some() ||
+        pat_ident.mutability.is_some() ||
+        pat_ident.subpat.is_some() ||
+        !pat_ident.ident.to_string().starts_with(|c: char| c.is_uppercase())
+}
+
+impl VisitMut for VariableRenamer {
+    fn visit_item_mut(&mut self, i: &mut Item) {
+        let frozen = usize::from(is_disabled(item_attrs(i), Pass::Rename));
+        self.frozen += frozen;
+        visit_mut::visit_item_mut(self, i);
+        self.frozen -= frozen;
+    }
+
+    fn visit_impl_item_mut(&mut self, i: &mut ImplItem) {
+        let frozen = usize::from(is_disabled(impl_item_attrs(i), Pass::Rename));
+        self.frozen += frozen;
+        visit_mut::visit_impl_item_mut(self, i);
+        self.frozen -= frozen;
+    }
+
+    fn visit_trait_item_mut(&mut self, i: &mut TraitItem) {
+        let frozen = usize::from(is_disabled(trait_item_attrs(i), Pass::Rename));
+        self.frozen += frozen;
+        visit_mut::visit_trait_item_mut(self, i);
+        self.frozen -= frozen;
+    }
+
+    //the file's items were already collected, see `rename_crate`
+    fn visit_file_mut(&mut self, file: &mut File) {
+        self.scopes.clear();
+        self.owners.clear();
+        self.enter_module(&mut file.items);
+    }
+
+    fn visit_item_mod_mut(&mut self, item_mod: &mut ItemMod) {
+        if let Some((_, items)) = &mut item_mod.content {
+            self.module_path.push(item_mod.ident.to_string());
+            self.enter_module(items);
+            self.module_path.pop();
+        }
+    }
+
+    //imports were already handled when their scope was entered
+    fn visit_item_use_mut(&mut self, _i: &mut ItemUse) {}
+
+    fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
+        self.item_path.push(self.original_name(&i.sig.ident));
+        self.visit_fn(&mut i.sig, &mut i.block);
+        self.item_path.pop();
+    }
+
+    fn visit_item_impl_mut(&mut self, i: &mut ItemImpl) {
+        self.visit_generics_mut(&mut i.generics);
+        let generics = self.push_generics(&i.generics);
+        if let Some((_, trait_path, _)) = &mut i.trait_ {
+            self.visit_path_mut(trait_path);
+        }
+        self.visit_type_mut(&mut i.self_ty);
+        let owner = match (&i.trait_, &*i.self_ty) {
+            (_, Type::Path(TypePath { qself: None, path })) if
+                path.segments.last().is_some_and(|segment| self.types.contains_key(&segment.ident.to_string()))
+            => {
+                path.segments.last().map(|segment| Owner::Type(segment.ident.to_string()))
+            }
+            (Some((_, trait_path, _)), _) if
+                trait_path.segments.last().is_some_and(|segment| self.types.contains_key(&segment.ident.to_string()))
+            => {
+                Some(Owner::Implementor)
+            }
+            _ => None,
+        };
+        let self_name = match &*i.self_ty {
+            Type::Path(TypePath { path, .. }) => path.segments.last().map(|segment| self.original_name(&segment.ident)),
+            _ => None,
+        };
+        self.item_path.push(self_name.unwrap_or_else(|| "impl".to_string()));
+        self.owners.push(owner);
+        for item in i.items.iter_mut() {
+            self.visit_impl_item_mut(item);
+        }
+        self.owners.pop();
+        self.generics.truncate(generics);
+        self.item_path.pop();
+    }
+
+    fn visit_item_trait_mut(&mut self, i: &mut ItemTrait) {
+        self.item_path.push(self.original_name(&i.ident));
+        self.owners.push(Some(Owner::Implementor));
+        self.visit_generics_mut(&mut i.generics);
+        let generics = self.push_generics(&i.generics);
+        for bound in i.supertraits.iter_mut() {
+            self.visit_type_param_bound_mut(bound);
+        }
+        for item in i.items.iter_mut() {
+            self.visit_trait_item_mut(item);
+        }
+        self.generics.truncate(generics);
+        self.owners.pop();
+        self.item_path.pop();
+    }
+
+    fn visit_impl_item_fn_mut(&mut self, i: &mut ImplItemFn) {
+        self.item_path.push(i.sig.ident.to_string());
+        if let Some(renamed) = self.renamed_member(&i.sig.ident) {
+            i.sig.ident = renamed;
+        }
+        self.visit_fn(&mut i.sig, &mut i.block);
+        self.item_path.pop();
+    }
+
+    fn visit_trait_item_fn_mut(&mut self, i: &mut TraitItemFn) {
+        self.item_path.push(i.sig.ident.to_string());
+        if let Some(renamed) = self.renamed_member(&i.sig.ident) {
+            i.sig.ident = renamed;
+        }
+        match &mut i.default {
+            Some(block) => self.visit_fn(&mut i.sig, block),
+            None => self.visit_signature_types(&mut i.sig),
+        }
+        self.item_path.pop();
+    }
+
+    fn visit_field_mut(&mut self, field: &mut Field) {
+        if let Some(ident) = &mut field.ident {
+            if let Some(renamed) = self.renamed_member(ident) {
+                *ident = renamed;
+            }
+        }
+        self.visit_type_mut(&mut field.ty);
+    }
+
+    //attributes like `#[derive(Debug)]` name nothing of this file
+    fn visit_attribute_mut(&mut self, _i: &mut Attribute) {}
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        self.push_scope(ScopeKind::Block);
+        self.hoist_block_items(&mut block.stmts);
+        for stmt in block.stmts.iter_mut() {
+            self.visit_stmt_mut(stmt);
+        }
+        self.pop_scope();
+    }
+
+    //visit local variables, the initializer still sees the binding the new one shadows
     fn visit_local_mut(&mut self, local: &mut Local) {
         if let Some(local_init) = &mut local.init {
-            self.visit_expr_mut(local_init.expr.as_mut());
+            self.visit_expr_mut(&mut local_init.expr);
+            if let Some((_, diverge)) = &mut local_init.diverge {
+                self.visit_expr_mut(diverge);
+            }
         }
-        //change variable name
-        if let syn::Pat::Ident(ref mut pat_ident) = local.pat {
-            let old_name = pat_ident.ident.to_string();
-            let new_name = random_name();
-            self.renamed_vars.insert(old_name, new_name.clone());
-            pat_ident.ident = Ident::new(&new_name, pat_ident.ident.span());
+        self.bind_pattern(&mut local.pat);
+    }
+
+    fn visit_expr_closure_mut(&mut self, closure: &mut ExprClosure) {
+        self.push_scope(ScopeKind::Block);
+        for input in closure.inputs.iter_mut() {
+            self.bind_pattern(input);
         }
+        self.visit_return_type_mut(&mut closure.output);
+        self.visit_expr_mut(&mut closure.body);
+        self.pop_scope();
     }
-}
 
-// Function to generate a random name
-fn random_name() -> String {
-    let mut rng = rand::thread_rng();
-    let name_length = rng.gen_range(3..=10);
+    fn visit_expr_for_loop_mut(&mut self, for_loop: &mut ExprForLoop) {
+        self.visit_expr_mut(&mut for_loop.expr);
+        self.push_scope(ScopeKind::Block);
+        self.bind_pattern(&mut for_loop.pat);
+        self.visit_block_mut(&mut for_loop.body);
+        self.pop_scope();
+    }
 
-    let mut last_char_was_underscore = false;
-    let mut name = String::new();
+    //bindings of `if let` are only visible in the then branch
+    fn visit_expr_if_mut(&mut self, expr_if: &mut ExprIf) {
+        self.push_scope(ScopeKind::Block);
+        self.visit_expr_mut(&mut expr_if.cond);
+        self.visit_block_mut(&mut expr_if.then_branch);
+        self.pop_scope();
+        if let Some((_, else_branch)) = &mut expr_if.else_branch {
+            self.visit_expr_mut(else_branch);
+        }
+    }
 
-    while name.len() < name_length {
-        let next_char = if rng.gen_bool(0.8) { rng.gen_range(b'a'..=b'z') as char } else { '_' };
+    fn visit_expr_while_mut(&mut self, expr_while: &mut ExprWhile) {
+        self.push_scope(ScopeKind::Block);
+        self.visit_expr_mut(&mut expr_while.cond);
+        self.visit_block_mut(&mut expr_while.body);
+        self.pop_scope();
+    }
+
+    fn visit_expr_let_mut(&mut self, expr_let: &mut ExprLet) {
+        self.visit_expr_mut(&mut expr_let.expr);
+        self.bind_pattern(&mut expr_let.pat);
+    }
 
-        // Ensure not two underscores in a row
-        if !(last_char_was_underscore && next_char == '_') {
-            name.push(next_char);
-            last_char_was_underscore = next_char == '_';
+    fn visit_arm_mut(&mut self, arm: &mut Arm) {
+        self.push_scope(ScopeKind::Block);
+        self.bind_pattern(&mut arm.pat);
+        if let Some((_, guard)) = &mut arm.guard {
+            self.visit_expr_mut(guard);
         }
+        self.visit_expr_mut(&mut arm.body);
+        self.pop_scope();
     }
-    // Ensure the name does not start or end with an underscore
-    if name.starts_with('_') {
-        name.remove(0);
-        name.insert(0, rng.gen_range(b'a'..=b'z') as char);
+
+    fn visit_expr_path_mut(&mut self, expr_path: &mut ExprPath) {
+        self.visit_qualified_path(&mut expr_path.qself, &mut expr_path.path);
+    }
+
+    fn visit_type_path_mut(&mut self, type_path: &mut TypePath) {
+        self.visit_qualified_path(&mut type_path.qself, &mut type_path.path);
     }
-    if name.ends_with('_') {
-        name.pop();
-        name.push(rng.gen_range(b'a'..=b'z') as char);
+
+    //rename the path itself, then the types in its generic arguments
+    fn visit_path_mut(&mut self, path: &mut Path) {
+        self.rename_path(path);
+        visit_mut::visit_path_mut(self, path);
     }
 
-    name
+    fn visit_expr_field_mut(&mut self, expr_field: &mut ExprField) {
+        self.visit_expr_mut(&mut expr_field.base);
+        if let Member::Named(member) = &mut expr_field.member {
+            if let Some(renamed) = self.renamed_member(member) {
+                *member = renamed;
+            }
+        }
+    }
+
+    fn visit_expr_method_call_mut(&mut self, call: &mut ExprMethodCall) {
+        self.visit_expr_mut(&mut call.receiver);
+        if let Some(renamed) = self.renamed_member(&call.method) {
+            call.method = renamed;
+        }
+        if let Some(turbofish) = &mut call.turbofish {
+            self.visit_angle_bracketed_generic_arguments_mut(turbofish);
+        }
+        for arg in call.args.iter_mut() {
+            self.visit_expr_mut(arg);
+        }
+    }
+
+    //the shorthand `Point { x }` has to become `Point { x: renamed }`
+    fn visit_field_value_mut(&mut self, field_value: &mut FieldValue) {
+        self.visit_expr_mut(&mut field_value.expr);
+        if let Member::Named(member) = &mut field_value.member {
+            if let Some(renamed) = self.renamed_member(member) {
+                *member = renamed;
+            }
+        }
+        if let (None, Member::Named(member), Expr::Path(expr_path)) = (
+            &field_value.colon_token,
+            &field_value.member,
+            &field_value.expr,
+        ) {
+            if !expr_path.path.is_ident(member) {
+                field_value.colon_token = Some(Default::default());
+            }
+        }
+    }
+
+    fn visit_macro_mut(&mut self, i: &mut Macro) {
+        if i.path.is_ident("macro_rules") {
+            i.tokens = self.process_macro_rules(i.tokens.clone());
+        } else if !self.rename_format_macro(i) {
+            i.tokens = self.process_tokens(i.tokens.clone());
+        }
+    }
 }
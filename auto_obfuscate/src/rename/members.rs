@@ -0,0 +1,883 @@
+use super::is_renamable_type;
+use crate::attributes::{ impl_item_attrs, is_disabled, item_attrs, trait_item_attrs, Pass };
+use proc_macro2::{ Spacing, TokenStream, TokenTree };
+use syn::{
+    punctuated::Punctuated,
+    visit::{ self, Visit },
+    Arm,
+    Block,
+    Expr,
+    ExprClosure,
+    ExprField,
+    ExprForLoop,
+    ExprIf,
+    ExprLet,
+    ExprMethodCall,
+    ExprPath,
+    ExprStruct,
+    ExprWhile,
+    Fields,
+    File,
+    FnArg,
+    GenericArgument,
+    Generics,
+    Ident,
+    ImplItem,
+    ImplItemFn,
+    Item,
+    ItemEnum,
+    ItemFn,
+    ItemImpl,
+    ItemMod,
+    ItemStruct,
+    ItemTrait,
+    ItemUnion,
+    Local,
+    Macro,
+    Member,
+    Pat,
+    PatStruct,
+    Path,
+    PathArguments,
+    ReturnType,
+    Signature,
+    Token,
+    TraitItem,
+    TraitItemFn,
+    Type,
+    TypeParamBound,
+    TypePath,
+    WherePredicate,
+};
+use syn::parse::Parser;
+use std::collections::{ HashMap, HashSet };
+
+//a field or method sharing its name with a common member of std types keeps it even if every use
+//the collector sees is typed, derives and macros it can't parse may still use the std one
+const STD_MEMBERS: &[&str] = &[
+    "new",
+    "default",
+    "len",
+    "is_empty",
+    "push",
+    "pop",
+    "get",
+    "get_mut",
+    "insert",
+    "remove",
+    "contains",
+    "clear",
+    "iter",
+    "iter_mut",
+    "into_iter",
+    "next",
+    "map",
+    "unwrap",
+    "clone",
+    "to_string",
+    "as_str",
+    "as_ref",
+    "as_mut",
+    "from",
+    "into",
+    "fmt",
+    "eq",
+    "cmp",
+    "hash",
+    "read",
+    "write",
+    "start",
+    "end",
+    "first",
+    "last",
+    "min",
+    "max",
+    "count",
+    "find",
+    "take",
+    "extend",
+    "key",
+    "value",
+    "keys",
+    "values",
+    "lock",
+    "join",
+    "parse",
+];
+
+//methods of a collection, iterator or `Option` that yield the same items
+const ITEM_ADAPTERS: &[&str] = &[
+    "iter",
+    "iter_mut",
+    "into_iter",
+    "drain",
+    "rev",
+    "skip",
+    "take",
+    "step_by",
+    "chain",
+    "filter",
+    "skip_while",
+    "take_while",
+    "peekable",
+    "cloned",
+    "copied",
+    "first",
+    "last",
+    "get",
+    "get_mut",
+    "pop",
+    "next",
+    "nth",
+    "find",
+    "max_by_key",
+    "min_by_key",
+    "as_ref",
+    "as_mut",
+];
+
+//methods of an `Option` that yield the value itself
+const ITEM_UNWRAPS: &[&str] = &["unwrap", "expect", "unwrap_or_default"];
+
+//methods of a collection, iterator or `Option` taking a closure called with one item
+const ITEM_CLOSURES: &[&str] = &[
+    "map",
+    "for_each",
+    "filter",
+    "filter_map",
+    "flat_map",
+    "inspect",
+    "any",
+    "all",
+    "find",
+    "position",
+    "skip_while",
+    "take_while",
+    "map_while",
+    "max_by_key",
+    "min_by_key",
+    "partition",
+    "retain",
+    "sort_by_key",
+    "and_then",
+    "is_some_and",
+];
+
+//what the collector knows of the type of an expression
+#[derive(Clone, PartialEq)]
+enum Known {
+    //a value of a renamed struct, enum or trait by its original name, `Self` in signatures
+    Type(String),
+    //a collection, iterator or `Option` of one, like `Vec<Shape>` or `shapes.iter()`
+    Items(String),
+}
+
+impl Known {
+    fn is_type(&self, types: &HashSet<String>) -> bool {
+        matches!(self, Known::Type(name) if types.contains(name))
+    }
+
+    //`Self` of a signature is the type the method is called on
+    fn with_self(self, owner: &str) -> Known {
+        match self {
+            Known::Type(name) if name == "Self" => Known::Type(owner.to_string()),
+            Known::Items(name) if name == "Self" => Known::Items(owner.to_string()),
+            known => known,
+        }
+    }
+}
+
+//names of the fields and methods that can be renamed, in the order they are declared. A name is
+//only renamed if every struct, enum, impl and trait declaring it is a renamed one, a field or method
+//that is also declared by a public type or an impl of a foreign trait keeps its name everywhere.
+//Every name comes with its first declaration and the index of the file declaring it
+pub(super) fn renamable_members(files: &[File], frozen_files: &[bool]) -> Vec<(Ident, usize)> {
+    let mut collector = MemberCollector {
+        types: HashSet::new(),
+        candidates: Vec::new(),
+        kept: HashSet::new(),
+        file: 0,
+        frozen: 0,
+        fields: HashMap::new(),
+        returns: HashMap::new(),
+        impls: HashMap::new(),
+        locals: Vec::new(),
+        generics: Vec::new(),
+        self_types: Vec::new(),
+    };
+    for (file, _) in files.iter().zip(frozen_files).filter(|(_, frozen)| !**frozen) {
+        collect_renamable_types(&file.items, &mut collector.types);
+    }
+    for file in files.iter() {
+        collector.collect_signatures(&file.items);
+    }
+    for (index, file) in files.iter().enumerate() {
+        collector.file = index;
+        collector.frozen = usize::from(frozen_files[index]);
+        collector.visit_file(file);
+    }
+    let mut seen = HashSet::new();
+    collector.candidates
+        .into_iter()
+        .filter(|(ident, _)| {
+            let name = ident.to_string();
+            !collector.kept.contains(&name) && seen.insert(name)
+        })
+        .collect()
+}
+
+fn collect_renamable_types(items: &[Item], types: &mut HashSet<String>) {
+    for item in items {
+        match item {
+            Item::Struct(ItemStruct { vis, attrs, ident, .. }) |
+            Item::Enum(ItemEnum { vis, attrs, ident, .. }) |
+            Item::Trait(ItemTrait { vis, attrs, ident, .. }) if is_renamable_type(vis, attrs) => {
+                types.insert(ident.to_string());
+            }
+            Item::Mod(ItemMod { attrs, content: Some((_, items)), .. }) if !is_disabled(attrs, Pass::Rename) => {
+                collect_renamable_types(items, types);
+            }
+            _ => {}
+        }
+    }
+}
+
+//the type parameters of `generics` with the first of their trait bounds `is_renamed` accepts
+pub(super) fn renamed_bounds(generics: &Generics, is_renamed: impl Fn(&Path) -> bool) -> Vec<(String, Option<String>)> {
+    let renamed_bound = |bounds: &Punctuated<TypeParamBound, Token![+]>| {
+        bounds.iter().find_map(|bound| match bound {
+            TypeParamBound::Trait(bound) if is_renamed(&bound.path) => {
+                bound.path.segments.last().map(|segment| segment.ident.to_string())
+            }
+            _ => None,
+        })
+    };
+    let mut params: Vec<(String, Option<String>)> = generics
+        .type_params()
+        .map(|param| (param.ident.to_string(), renamed_bound(&param.bounds)))
+        .collect();
+    for predicate in generics.where_clause.iter().flat_map(|where_clause| where_clause.predicates.iter()) {
+        if let WherePredicate::Type(predicate) = predicate {
+            let Type::Path(TypePath { qself: None, path }) = &predicate.bounded_ty else {
+                continue;
+            };
+            let bound = renamed_bound(&predicate.bounds);
+            for (name, renamed) in params.iter_mut() {
+                if renamed.is_none() && path.is_ident(name) {
+                    *renamed = bound.clone();
+                }
+            }
+        }
+    }
+    params
+}
+
+//members are renamed by name as well, so a field or method is only renamed if every use of it is on
+//a receiver or through a path the collector can tell is a renamed type: `self` of a renamed type,
+//locals and arguments of one, generics bounded by a renamed trait and what their methods return
+struct MemberCollector {
+    //private structs, enums and traits of the file root and inline modules, by name
+    types: HashSet<String>,
+    candidates: Vec<(Ident, usize)>,
+    kept: HashSet<String>,
+    //index of the file being visited
+    file: usize,
+    //opted out items around the current position, their fields and methods keep their names
+    frozen: usize,
+    //the types of the fields of renamed types, by type and field
+    fields: HashMap<(String, String), Known>,
+    //the return types of the methods of renamed types and traits, by type and method
+    returns: HashMap<(String, String), Known>,
+    //the renamed traits implemented by each renamed type
+    impls: HashMap<String, Vec<String>>,
+    //the locals of the fn being visited by scope, `None` for a local of any other type
+    locals: Vec<HashMap<String, Option<Known>>>,
+    //the type parameters around the current position with their renamed trait bound
+    generics: Vec<(String, Option<String>)>,
+    //what `Self` is in the impls and traits around the current position
+    self_types: Vec<Option<Known>>,
+}
+
+impl MemberCollector {
+    fn declare(&mut self, ident: &Ident, renamable: bool) {
+        let name = ident.to_string();
+        if renamable && self.frozen == 0 && !STD_MEMBERS.contains(&name.as_str()) {
+            self.candidates.push((ident.clone(), self.file));
+        } else {
+            self.kept.insert(name);
+        }
+    }
+
+    fn declare_fields(&mut self, fields: &Fields, renamable: bool) {
+        if let Fields::Named(fields) = fields {
+            for field in fields.named.iter() {
+                if let Some(ident) = &field.ident {
+                    self.declare(ident, renamable);
+                }
+            }
+        }
+    }
+
+    fn is_renamable(&self, path: &Path) -> bool {
+        path.segments.last().is_some_and(|segment| self.types.contains(&segment.ident.to_string()))
+    }
+
+    //the field types and method return types of renamed types and traits, before any body is
+    //visited so a method can be called before its definition
+    fn collect_signatures(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Struct(item_struct) if self.types.contains(&item_struct.ident.to_string()) => {
+                    for field in item_struct.fields.iter() {
+                        if let (Some(ident), Some(known)) = (&field.ident, self.type_of(&field.ty)) {
+                            self.fields.insert((item_struct.ident.to_string(), ident.to_string()), known);
+                        }
+                    }
+                }
+                Item::Impl(item_impl) => {
+                    let Type::Path(TypePath { qself: None, path }) = &*item_impl.self_ty else {
+                        continue;
+                    };
+                    let Some(owner) = path.segments.last().map(|segment| segment.ident.to_string()) else {
+                        continue;
+                    };
+                    if !self.types.contains(&owner) {
+                        continue;
+                    }
+                    if let Some((_, trait_path, _)) = &item_impl.trait_ {
+                        if let (true, Some(segment)) = (self.is_renamable(trait_path), trait_path.segments.last()) {
+                            self.impls.entry(owner.clone()).or_default().push(segment.ident.to_string());
+                        }
+                    }
+                    for item in item_impl.items.iter() {
+                        if let ImplItem::Fn(impl_fn) = item {
+                            self.collect_return(&owner, &impl_fn.sig);
+                        }
+                    }
+                }
+                Item::Trait(item_trait) if self.types.contains(&item_trait.ident.to_string()) => {
+                    for item in item_trait.items.iter() {
+                        if let TraitItem::Fn(trait_fn) = item {
+                            self.collect_return(&item_trait.ident.to_string(), &trait_fn.sig);
+                        }
+                    }
+                }
+                Item::Mod(ItemMod { content: Some((_, items)), .. }) => self.collect_signatures(items),
+                _ => {}
+            }
+        }
+    }
+
+    fn collect_return(&mut self, owner: &str, sig: &Signature) {
+        if let ReturnType::Type(_, ty) = &sig.output {
+            if let Some(known) = self.type_of(ty) {
+                self.returns.insert((owner.to_string(), sig.ident.to_string()), known);
+            }
+        }
+    }
+
+    //what a method of the renamed type or trait `owner` returns, trait methods it doesn't define
+    //itself are looked up in the renamed traits it implements
+    fn return_of(&self, owner: &str, method: &Ident) -> Option<Known> {
+        let method = method.to_string();
+        std::iter::once(owner)
+            .chain(self.impls.get(owner).into_iter().flatten().map(String::as_str))
+            .find_map(|owner| self.returns.get(&(owner.to_string(), method.clone())))
+            .map(|known| known.clone().with_self(owner))
+    }
+
+    //a path to a type like `Shape`, `shapes::Shape`, `Self` or a generic parameter `T`
+    fn path_type(&self, path: &Path) -> Option<Known> {
+        let ident = &path.segments.last()?.ident;
+        if path.segments.len() == 1 {
+            if ident == "Self" {
+                return self.self_types.last().cloned().unwrap_or(Some(Known::Type("Self".to_string())));
+            }
+            if let Some((_, bound)) = self.generics.iter().rev().find(|(name, _)| ident == name) {
+                return bound.clone().map(Known::Type);
+            }
+        }
+        let name = ident.to_string();
+        self.types.contains(&name).then_some(Known::Type(name))
+    }
+
+    fn type_of(&self, ty: &Type) -> Option<Known> {
+        match ty {
+            Type::Reference(reference) => self.type_of(&reference.elem),
+            Type::Paren(paren) => self.type_of(&paren.elem),
+            Type::Group(group) => self.type_of(&group.elem),
+            Type::Slice(slice) => self.items_of(&slice.elem),
+            Type::Array(array) => self.items_of(&array.elem),
+            Type::Path(TypePath { qself: None, path }) => {
+                let segment = path.segments.last()?;
+                let argument = match &segment.arguments {
+                    PathArguments::AngleBracketed(arguments) => arguments.args.iter().find_map(|arg| match arg {
+                        GenericArgument::Type(ty) => Some(ty),
+                        _ => None,
+                    }),
+                    _ => None,
+                };
+                match (segment.ident.to_string().as_str(), argument) {
+                    ("Vec" | "VecDeque" | "Option", Some(argument)) => self.items_of(argument),
+                    ("Box" | "Rc" | "Arc", Some(argument)) => self.type_of(argument),
+                    _ => self.path_type(path),
+                }
+            }
+            Type::ImplTrait(impl_trait) => self.bound_type(&impl_trait.bounds),
+            Type::TraitObject(trait_object) => self.bound_type(&trait_object.bounds),
+            _ => None,
+        }
+    }
+
+    fn items_of(&self, ty: &Type) -> Option<Known> {
+        match self.type_of(ty)? {
+            Known::Type(name) => Some(Known::Items(name)),
+            Known::Items(_) => None,
+        }
+    }
+
+    fn bound_type(&self, bounds: &Punctuated<TypeParamBound, Token![+]>) -> Option<Known> {
+        bounds.iter().find_map(|bound| match bound {
+            TypeParamBound::Trait(bound) if self.is_renamable(&bound.path) => self.path_type(&bound.path),
+            _ => None,
+        })
+    }
+
+    //the type of an expression if it is a renamed type or items of one
+    fn expr_type(&self, expr: &Expr) -> Option<Known> {
+        match expr {
+            Expr::Path(ExprPath { qself: None, path, .. }) => {
+                let ident = path.get_ident()?.to_string();
+                self.locals.iter().rev().find_map(|scope| scope.get(&ident)).cloned().flatten()
+            }
+            Expr::Paren(paren) => self.expr_type(&paren.expr),
+            Expr::Group(group) => self.expr_type(&group.expr),
+            Expr::Reference(reference) => self.expr_type(&reference.expr),
+            Expr::Unary(unary) if matches!(unary.op, syn::UnOp::Deref(_)) => self.expr_type(&unary.expr),
+            Expr::Struct(expr_struct) if expr_struct.qself.is_none() => self.path_type(&expr_struct.path),
+            Expr::Call(call) => {
+                let Expr::Path(ExprPath { qself: None, path, .. }) = &*call.func else {
+                    return None;
+                };
+                let (owner, method) = split_owner(path)?;
+                match self.path_type(&owner)? {
+                    Known::Type(owner) => self.return_of(&owner, method),
+                    Known::Items(_) => None,
+                }
+            }
+            Expr::MethodCall(call) => match self.expr_type(&call.receiver)? {
+                Known::Type(owner) => self.return_of(&owner, &call.method),
+                Known::Items(name) if ITEM_ADAPTERS.iter().any(|method| call.method == method) => Some(Known::Items(name)),
+                Known::Items(name) if ITEM_UNWRAPS.iter().any(|method| call.method == method) => Some(Known::Type(name)),
+                Known::Items(_) => None,
+            },
+            Expr::Index(index) => match self.expr_type(&index.expr)? {
+                Known::Items(name) => Some(Known::Type(name)),
+                Known::Type(_) => None,
+            },
+            Expr::Field(field) => match (self.expr_type(&field.base)?, &field.member) {
+                (Known::Type(owner), Member::Named(member)) => self.fields.get(&(owner, member.to_string())).cloned(),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    //a member used on or through something that isn't a renamed type keeps its name
+    fn keep_unless(&mut self, member: &Ident, owner: Option<Known>) {
+        if !owner.is_some_and(|owner| owner.is_type(&self.types)) {
+            self.kept.insert(member.to_string());
+        }
+    }
+
+    //the type of a struct literal or pattern like `Point { .. }` or `Shape::Rect { .. }`
+    fn struct_type(&self, path: &Path) -> Option<Known> {
+        self.path_type(path).or_else(|| split_owner(path).and_then(|(owner, _)| self.path_type(&owner)))
+    }
+
+    fn keep_fields<'a>(&mut self, path: &Path, members: impl Iterator<Item = &'a Member>) {
+        let owner = self.struct_type(path);
+        for member in members {
+            if let Member::Named(member) = member {
+                self.keep_unless(member, owner.clone());
+            }
+        }
+    }
+
+    fn push_scope(&mut self) {
+        self.locals.push(HashMap::new());
+    }
+
+    fn pop_scope(&mut self) {
+        self.locals.pop();
+    }
+
+    fn bind_pattern(&mut self, pat: &Pat, known: Option<Known>) {
+        self.visit_pat(pat);
+        self.bind_names(pat, known);
+    }
+
+    //bind the names of a pattern matching a value of type `known`, only a plain name keeps the type
+    fn bind_names(&mut self, pat: &Pat, known: Option<Known>) {
+        match pat {
+            Pat::Ident(pat_ident) if pat_ident.subpat.is_none() => {
+                if let Some(scope) = self.locals.last_mut() {
+                    scope.insert(pat_ident.ident.to_string(), known);
+                }
+            }
+            Pat::Reference(reference) => self.bind_names(&reference.pat, known),
+            Pat::Paren(paren) => self.bind_names(&paren.pat, known),
+            Pat::Type(pat_type) => {
+                let known = self.type_of(&pat_type.ty);
+                self.bind_names(&pat_type.pat, known);
+            }
+            //`Some(shape)` of an `Option<Shape>`
+            Pat::TupleStruct(tuple_struct) if
+                tuple_struct.qself.is_none() &&
+                tuple_struct.elems.len() == 1 &&
+                tuple_struct.path.is_ident("Some")
+            => {
+                let known = match known {
+                    Some(Known::Items(name)) => Some(Known::Type(name)),
+                    _ => None,
+                };
+                self.bind_names(&tuple_struct.elems[0], known);
+            }
+            _ => {
+                let mut names = PatNames(Vec::new());
+                names.visit_pat(pat);
+                if let Some(scope) = self.locals.last_mut() {
+                    for name in names.0 {
+                        scope.insert(name, None);
+                    }
+                }
+            }
+        }
+    }
+
+    //a fn body only sees its own arguments and locals
+    fn visit_fn(&mut self, sig: &Signature, block: Option<&Block>) {
+        let generics = self.generics.len();
+        let bounds = self.generic_bounds(&sig.generics);
+        self.generics.extend(bounds);
+        let locals = std::mem::take(&mut self.locals);
+        self.push_scope();
+        for input in sig.inputs.iter() {
+            match input {
+                FnArg::Receiver(_) => {
+                    let self_type = self.self_types.last().cloned().flatten();
+                    if let Some(scope) = self.locals.last_mut() {
+                        scope.insert("self".to_string(), self_type);
+                    }
+                }
+                FnArg::Typed(pat_type) => {
+                    let known = self.type_of(&pat_type.ty);
+                    self.bind_pattern(&pat_type.pat, known);
+                }
+            }
+        }
+        visit::visit_signature(self, sig);
+        if let Some(block) = block {
+            self.visit_block(block);
+        }
+        self.locals = locals;
+        self.generics.truncate(generics);
+    }
+
+    fn generic_bounds(&self, generics: &Generics) -> Vec<(String, Option<String>)> {
+        renamed_bounds(generics, |path| self.is_renamable(path))
+    }
+
+    fn visit_owner(&mut self, self_type: Option<Known>, generics: &Generics, visit: impl FnOnce(&mut Self)) {
+        let outer_generics = self.generics.len();
+        let bounds = self.generic_bounds(generics);
+        self.generics.extend(bounds);
+        self.self_types.push(self_type);
+        visit(self);
+        self.self_types.pop();
+        self.generics.truncate(outer_generics);
+    }
+
+    fn visit_closure(&mut self, closure: &ExprClosure, item: Option<Known>) {
+        self.push_scope();
+        for (index, input) in closure.inputs.iter().enumerate() {
+            self.bind_pattern(input, item.clone().filter(|_| index == 0));
+        }
+        self.visit_expr(&closure.body);
+        self.pop_scope();
+    }
+
+    //macros whose arguments aren't expressions are only scanned for `.member`, `Type::method` and
+    //`member: value`
+    fn visit_macro_tokens(&mut self, tokens: TokenStream) {
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        for (index, token) in tokens.iter().enumerate() {
+            let before = |offset: usize| index.checked_sub(offset).map(|index| &tokens[index]);
+            match token {
+                TokenTree::Group(group) => self.visit_macro_tokens(group.stream()),
+                TokenTree::Ident(ident) if matches!(before(1), Some(TokenTree::Punct(punct)) if punct.as_char() == '.') => {
+                    self.kept.insert(ident.to_string());
+                }
+                TokenTree::Ident(ident) if matches!(before(1), Some(TokenTree::Punct(punct)) if punct.as_char() == ':') => {
+                    let owner = match before(3) {
+                        Some(TokenTree::Ident(owner)) => self.path_type(&Path::from(owner.clone())),
+                        _ => None,
+                    };
+                    self.keep_unless(ident, owner);
+                }
+                TokenTree::Ident(ident) if
+                    matches!(before(1), None | Some(TokenTree::Punct(_))) &&
+                    matches!(tokens.get(index + 1), Some(TokenTree::Punct(punct)) if
+                        punct.as_char() == ':' && punct.spacing() == Spacing::Alone)
+                => {
+                    self.kept.insert(ident.to_string());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+//`a::Type` and `method` of `a::Type::method`
+fn split_owner(path: &Path) -> Option<(Path, &Ident)> {
+    if path.segments.len() < 2 {
+        return None;
+    }
+    let method = &path.segments.last()?.ident;
+    let mut owner_path = path.clone();
+    owner_path.segments.pop();
+    owner_path.segments.pop_punct();
+    Some((owner_path, method))
+}
+
+//the names a pattern binds
+struct PatNames(Vec<String>);
+
+impl<'ast> Visit<'ast> for PatNames {
+    fn visit_pat_ident(&mut self, pat_ident: &'ast syn::PatIdent) {
+        self.0.push(pat_ident.ident.to_string());
+        visit::visit_pat_ident(self, pat_ident);
+    }
+}
+
+impl<'ast> Visit<'ast> for MemberCollector {
+    fn visit_item(&mut self, item: &'ast Item) {
+        let frozen = usize::from(is_disabled(item_attrs(item), Pass::Rename));
+        self.frozen += frozen;
+        visit::visit_item(self, item);
+        self.frozen -= frozen;
+    }
+
+    fn visit_impl_item(&mut self, item: &'ast ImplItem) {
+        let frozen = usize::from(is_disabled(impl_item_attrs(item), Pass::Rename));
+        self.frozen += frozen;
+        visit::visit_impl_item(self, item);
+        self.frozen -= frozen;
+    }
+
+    fn visit_trait_item(&mut self, item: &'ast TraitItem) {
+        let frozen = usize::from(is_disabled(trait_item_attrs(item), Pass::Rename));
+        self.frozen += frozen;
+        visit::visit_trait_item(self, item);
+        self.frozen -= frozen;
+    }
+
+    fn visit_item_struct(&mut self, item_struct: &'ast ItemStruct) {
+        let renamable = self.types.contains(&item_struct.ident.to_string());
+        self.declare_fields(&item_struct.fields, renamable);
+        visit::visit_item_struct(self, item_struct);
+    }
+
+    fn visit_item_enum(&mut self, item_enum: &'ast ItemEnum) {
+        let renamable = self.types.contains(&item_enum.ident.to_string());
+        for variant in item_enum.variants.iter() {
+            self.declare_fields(&variant.fields, renamable);
+        }
+        visit::visit_item_enum(self, item_enum);
+    }
+
+    fn visit_item_union(&mut self, item_union: &'ast ItemUnion) {
+        for field in item_union.fields.named.iter() {
+            if let Some(ident) = &field.ident {
+                self.declare(ident, false);
+            }
+        }
+        visit::visit_item_union(self, item_union);
+    }
+
+    //methods of inherent impls follow their type, methods of trait impls follow the trait
+    fn visit_item_impl(&mut self, item_impl: &'ast ItemImpl) {
+        let renamable = match (&item_impl.trait_, &*item_impl.self_ty) {
+            (Some((_, trait_path, _)), _) => self.is_renamable(trait_path),
+            (None, Type::Path(TypePath { qself: None, path })) => self.is_renamable(path),
+            (None, _) => false,
+        };
+        for item in item_impl.items.iter() {
+            if let ImplItem::Fn(impl_fn) = item {
+                self.declare(&impl_fn.sig.ident, renamable);
+            }
+        }
+        let self_type = self.type_of(&item_impl.self_ty);
+        self.visit_owner(self_type, &item_impl.generics, |this| visit::visit_item_impl(this, item_impl));
+    }
+
+    fn visit_item_trait(&mut self, item_trait: &'ast ItemTrait) {
+        let renamable = self.types.contains(&item_trait.ident.to_string());
+        for item in item_trait.items.iter() {
+            if let TraitItem::Fn(trait_fn) = item {
+                self.declare(&trait_fn.sig.ident, renamable);
+            }
+        }
+        let self_type = renamable.then(|| Known::Type(item_trait.ident.to_string()));
+        self.visit_owner(self_type, &item_trait.generics, |this| visit::visit_item_trait(this, item_trait));
+    }
+
+    fn visit_item_fn(&mut self, item_fn: &'ast ItemFn) {
+        self.visit_fn(&item_fn.sig, Some(&item_fn.block));
+    }
+
+    fn visit_impl_item_fn(&mut self, impl_fn: &'ast ImplItemFn) {
+        self.visit_fn(&impl_fn.sig, Some(&impl_fn.block));
+    }
+
+    fn visit_trait_item_fn(&mut self, trait_fn: &'ast TraitItemFn) {
+        self.visit_fn(&trait_fn.sig, trait_fn.default.as_ref());
+    }
+
+    fn visit_block(&mut self, block: &'ast Block) {
+        self.push_scope();
+        visit::visit_block(self, block);
+        self.pop_scope();
+    }
+
+    //the initializer still sees the binding the new one shadows
+    fn visit_local(&mut self, local: &'ast Local) {
+        let mut known = None;
+        if let Some(local_init) = &local.init {
+            self.visit_expr(&local_init.expr);
+            if let Some((_, diverge)) = &local_init.diverge {
+                self.visit_expr(diverge);
+            }
+            known = self.expr_type(&local_init.expr);
+        }
+        self.bind_pattern(&local.pat, known);
+    }
+
+    fn visit_expr_closure(&mut self, closure: &'ast ExprClosure) {
+        self.visit_closure(closure, None);
+    }
+
+    fn visit_expr_for_loop(&mut self, for_loop: &'ast ExprForLoop) {
+        self.visit_expr(&for_loop.expr);
+        let item = match self.expr_type(&for_loop.expr) {
+            Some(Known::Items(name)) => Some(Known::Type(name)),
+            _ => None,
+        };
+        self.push_scope();
+        self.bind_pattern(&for_loop.pat, item);
+        self.visit_block(&for_loop.body);
+        self.pop_scope();
+    }
+
+    //bindings of `if let` are only visible in the then branch
+    fn visit_expr_if(&mut self, expr_if: &'ast ExprIf) {
+        self.push_scope();
+        self.visit_expr(&expr_if.cond);
+        self.visit_block(&expr_if.then_branch);
+        self.pop_scope();
+        if let Some((_, else_branch)) = &expr_if.else_branch {
+            self.visit_expr(else_branch);
+        }
+    }
+
+    fn visit_expr_while(&mut self, expr_while: &'ast ExprWhile) {
+        self.push_scope();
+        self.visit_expr(&expr_while.cond);
+        self.visit_block(&expr_while.body);
+        self.pop_scope();
+    }
+
+    fn visit_expr_let(&mut self, expr_let: &'ast ExprLet) {
+        self.visit_expr(&expr_let.expr);
+        let known = self.expr_type(&expr_let.expr);
+        self.bind_pattern(&expr_let.pat, known);
+    }
+
+    fn visit_arm(&mut self, arm: &'ast Arm) {
+        self.push_scope();
+        self.bind_pattern(&arm.pat, None);
+        if let Some((_, guard)) = &arm.guard {
+            self.visit_expr(guard);
+        }
+        self.visit_expr(&arm.body);
+        self.pop_scope();
+    }
+
+    fn visit_expr_method_call(&mut self, call: &'ast ExprMethodCall) {
+        self.visit_expr(&call.receiver);
+        let receiver = self.expr_type(&call.receiver);
+        let item = match &receiver {
+            Some(Known::Items(name)) if ITEM_CLOSURES.iter().any(|method| call.method == method) => {
+                Some(Known::Type(name.clone()))
+            }
+            _ => None,
+        };
+        self.keep_unless(&call.method, receiver);
+        for arg in call.args.iter() {
+            match arg {
+                Expr::Closure(closure) => self.visit_closure(closure, item.clone()),
+                arg => self.visit_expr(arg),
+            }
+        }
+    }
+
+    //`Type::method`, `T::method` or `<T as Trait>::method`
+    fn visit_expr_path(&mut self, expr_path: &'ast ExprPath) {
+        let path = &expr_path.path;
+        if let (Some(last), true) = (path.segments.last(), path.segments.len() > 1 || expr_path.qself.is_some()) {
+            let owner = match &expr_path.qself {
+                Some(qself) if qself.position > 0 => {
+                    let mut trait_path = path.clone();
+                    while trait_path.segments.len() > qself.position {
+                        trait_path.segments.pop();
+                    }
+                    trait_path.segments.pop_punct();
+                    self.path_type(&trait_path)
+                }
+                Some(qself) => self.type_of(&qself.ty),
+                None => split_owner(path).and_then(|(owner, _)| self.path_type(&owner)),
+            };
+            self.keep_unless(&last.ident, owner);
+        }
+        visit::visit_expr_path(self, expr_path);
+    }
+
+    fn visit_expr_field(&mut self, expr_field: &'ast ExprField) {
+        self.visit_expr(&expr_field.base);
+        if let Member::Named(member) = &expr_field.member {
+            let owner = self.expr_type(&expr_field.base);
+            self.keep_unless(member, owner);
+        }
+    }
+
+    fn visit_expr_struct(&mut self, expr_struct: &'ast ExprStruct) {
+        self.keep_fields(&expr_struct.path, expr_struct.fields.iter().map(|field| &field.member));
+        visit::visit_expr_struct(self, expr_struct);
+    }
+
+    fn visit_pat_struct(&mut self, pat_struct: &'ast PatStruct) {
+        self.keep_fields(&pat_struct.path, pat_struct.fields.iter().map(|field| &field.member));
+        visit::visit_pat_struct(self, pat_struct);
+    }
+
+    fn visit_macro(&mut self, mac: &'ast Macro) {
+        match Punctuated::<Expr, Token![,]>::parse_terminated.parse2(mac.tokens.clone()) {
+            Ok(args) => {
+                for arg in args.iter() {
+                    self.visit_expr(arg);
+                }
+            }
+            Err(_) => self.visit_macro_tokens(mac.tokens.clone()),
+        }
+    }
+}
@@ -27,6 +27,7 @@ fn test_variable_renamer() {
         "#;
     let rename_config = RenameConfig {
         enable_rename_obfuscation: true,
+        ..RenameConfig::default()
     };
     let mut renamer = VariableRenamer::new(rename_config);
     let modified_code = renamer.rename(code);
@@ -35,8 +36,8 @@ fn test_variable_renamer() {
     assert_ne!(modified_code, code);
 
     //check if names used are all valid rust variable names
-    for new_name in renamer.renamed_vars.values() {
-        assert!(is_valid_rust_var_name(new_name), "Invalid variable name: {}", new_name);
+    for rename in &renamer.renames {
+        assert!(is_valid_rust_var_name(&rename.renamed), "Invalid variable name: {}", rename.renamed);
     }
 
     //original names should not be found in modified code (except for sum TO DO: remove when string encryption is implemented)
@@ -74,6 +75,7 @@ fn test_nested_function_calls() {
         "#;
     let rename_config = RenameConfig {
         enable_rename_obfuscation: true,
+        ..RenameConfig::default()
     };
     let mut renamer = VariableRenamer::new(rename_config);
     let modified_code = renamer.rename(code);
@@ -102,6 +104,7 @@ fn test_nested_macros() {
     "#;
     let rename_config = RenameConfig {
         enable_rename_obfuscation: true,
+        ..RenameConfig::default()
     };
     let mut renamer = VariableRenamer::new(rename_config);
     let modified_code = renamer.rename(code);
@@ -134,6 +137,7 @@ fn test_user_defined_nested_macro() {
         "#;
     let rename_config = RenameConfig {
         enable_rename_obfuscation: true,
+        ..RenameConfig::default()
     };
     let mut renamer = VariableRenamer::new(rename_config);
     let modified_code = renamer.rename(code);
@@ -164,6 +168,7 @@ fn test_function_in_macro() {
         "#;
     let rename_config = RenameConfig {
         enable_rename_obfuscation: true,
+        ..RenameConfig::default()
     };
     let mut renamer = VariableRenamer::new(rename_config);
     let modified_code = renamer.rename(code);
@@ -179,3 +184,723 @@ fn test_function_in_macro() {
     let parse_result = syn::parse_file(&modified_code);
     assert!(parse_result.is_ok(), "Modified code is not valid Rust code");
 }
+
+//rename `code` with a fixed seed and compare it to `expected`, where `__0`, `__1`, ... stand for
+//the generated names in the order they were assigned
+fn assert_renamed(code: &str, expected: &str) {
+    check_renamed(false, code, expected);
+}
+
+//same as `assert_renamed` with private types and their members renamed too
+fn assert_types_renamed(code: &str, expected: &str) {
+    check_renamed(true, code, expected);
+}
+
+fn check_renamed(rename_types: bool, code: &str, expected: &str) {
+    let rename_config = RenameConfig {
+        enable_rename_obfuscation: true,
+        rename_types,
+        seed: Some(7),
+        ..RenameConfig::default()
+    };
+    let mut renamer = VariableRenamer::new(rename_config);
+    let modified_code = renamer.rename(code);
+
+    let expected_file = syn::parse_file(expected).expect("expected code is not valid Rust code");
+    let mut expected_code = quote!(#expected_file).to_string();
+    for (index, rename) in renamer.renames.iter().enumerate().rev() {
+        expected_code = expected_code.replace(&format!("__{}", index), &rename.renamed);
+    }
+    assert_eq!(modified_code, expected_code);
+}
+
+#[test]
+fn test_shadowing() {
+    assert_renamed(
+        r#"
+            fn main() {
+                let x = 1;
+                let y = x + 1;
+                let x = y * 2;
+                println!("{}", x);
+            }
+        "#,
+        r#"
+            fn main() {
+                let __0 = 1;
+                let __1 = __0 + 1;
+                let __2 = __1 * 2;
+                println!("{}", __2);
+            }
+        "#
+    );
+}
+
+#[test]
+fn test_same_local_in_different_functions() {
+    assert_renamed(
+        r#"
+            fn first() -> i32 { let value = 1; value }
+            fn second() -> i32 { let value = 2; value }
+            fn main() { println!("{}", first() + second()); }
+        "#,
+        r#"
+            fn __0() -> i32 { let __2 = 1; __2 }
+            fn __1() -> i32 { let __3 = 2; __3 }
+            fn main() { println!("{}", __0() + __1()); }
+        "#
+    );
+}
+
+#[test]
+fn test_local_named_like_field_and_method() {
+    assert_renamed(
+        r#"
+            struct Counter { count: i32 }
+            impl Counter {
+                fn count(&self) -> i32 { self.count }
+            }
+            fn main() {
+                let count = Counter { count: 1 };
+                let total = count.count();
+                println!("{}", total + count.count);
+            }
+        "#,
+        r#"
+            struct Counter { count: i32 }
+            impl Counter {
+                fn count(&self) -> i32 { self.count }
+            }
+            fn main() {
+                let __0 = Counter { count: 1 };
+                let __1 = __0.count();
+                println!("{}", __1 + __0.count);
+            }
+        "#
+    );
+}
+
+#[test]
+fn test_closure_and_match_bindings() {
+    assert_renamed(
+        r#"
+            fn main() {
+                let x = 5;
+                let add = |x: i32| x + 1;
+                let y = match Some(x) {
+                    Some(x) => add(x),
+                    None => x,
+                };
+                let point = Point { x, y };
+            }
+        "#,
+        r#"
+            fn main() {
+                let __0 = 5;
+                let __2 = |__1: i32| __1 + 1;
+                let __4 = match Some(__0) {
+                    Some(__3) => __2(__3),
+                    None => __0,
+                };
+                let __5 = Point { x: __0, y: __4 };
+            }
+        "#
+    );
+}
+
+#[test]
+fn test_tuple_slice_and_reference_patterns() {
+    assert_renamed(
+        r#"
+            fn swap((a, b): (i32, i32)) -> (i32, i32) { (b, a) }
+            fn main() {
+                let (first, mut second) = swap((1, 2));
+                let [head, .., tail] = [1, 2, 3];
+                let &(ref left, _) = &(first, second);
+                second += head + tail + *left;
+            }
+        "#,
+        r#"
+            fn __0((__1, __2): (i32, i32)) -> (i32, i32) { (__2, __1) }
+            fn main() {
+                let (__3, mut __4) = __0((1, 2));
+                let [__5, .., __6] = [1, 2, 3];
+                let &(ref __7, _) = &(__3, __4);
+                __4 += __5 + __6 + *__7;
+            }
+        "#
+    );
+}
+
+#[test]
+fn test_struct_patterns() {
+    assert_renamed(
+        r#"
+            fn main() {
+                let Point { x, y: height } = origin();
+                let Point { ref mut x, .. } = origin();
+                *x += height;
+            }
+        "#,
+        r#"
+            fn main() {
+                let Point { x: __0, y: __1 } = origin();
+                let Point { x: ref mut __2, .. } = origin();
+                *__2 += __1;
+            }
+        "#
+    );
+}
+
+#[test]
+fn test_match_arm_patterns() {
+    assert_renamed(
+        r#"
+            fn main() {
+                let value = Some(3);
+                match value {
+                    Some(n @ 1..=9) if n > MIN => println!("{}", n),
+                    Some(big) | Other(big) => println!("{}", big),
+                    None => {}
+                }
+            }
+        "#,
+        r#"
+            fn main() {
+                let __0 = Some(3);
+                match __0 {
+                    Some(__1 @ 1..=9) if __1 > MIN => println!("{}", __1),
+                    Some(__2) | Other(__2) => println!("{}", __2),
+                    None => {}
+                }
+            }
+        "#
+    );
+}
+
+#[test]
+fn test_loop_and_conditional_patterns() {
+    assert_renamed(
+        r#"
+            fn main() {
+                let mut stack = vec![(1, 'a')];
+                for (index, (count, letter)) in stack.iter().enumerate() {
+                    println!("{} {} {}", index, count, letter);
+                }
+                while let Some((count, _)) = stack.pop() {
+                    if let Some(next) = count.checked_sub(1) {
+                        println!("{}", next);
+                    } else if let Ok(next) = u8::try_from(count) {
+                        println!("{}", next);
+                    }
+                }
+                let Some(last) = stack.last() else { return };
+            }
+        "#,
+        r#"
+            fn main() {
+                let mut __0 = vec![(1, 'a')];
+                for (__1, (__2, __3)) in __0.iter().enumerate() {
+                    println!("{} {} {}", __1, __2, __3);
+                }
+                while let Some((__4, _)) = __0.pop() {
+                    if let Some(__5) = __4.checked_sub(1) {
+                        println!("{}", __5);
+                    } else if let Ok(__6) = u8::try_from(__4) {
+                        println!("{}", __6);
+                    }
+                }
+                let Some(__7) = __0.last() else { return };
+            }
+        "#
+    );
+}
+
+#[test]
+fn test_format_macros() {
+    assert_renamed(
+        r#"
+            use std::fmt::Write;
+            fn main() {
+                let name = "crab";
+                let width = 8;
+                let mut out = String::new();
+                println!("{name} {name:?} {0} {name:>width$} {{name}}", width);
+                let _ = write!(out, "{label}: {name:.*}", 2, label = name);
+                eprintln!("{width$}", width = name.len());
+            }
+        "#,
+        r#"
+            use std::fmt::Write;
+            fn main() {
+                let __0 = "crab";
+                let __1 = 8;
+                let mut __2 = String::new();
+                println!("{__0} {__0:?} {0} {__0:>__1$} {{name}}", __1);
+                let _ = write!(__2, "{label}: {__0:.*}", 2, label = __0);
+                eprintln!("{width$}", width = __0.len());
+            }
+        "#
+    );
+}
+
+#[test]
+fn test_macro_rules_and_other_macros() {
+    assert_renamed(
+        r#"
+            fn helper(x: i32) -> i32 { x }
+            macro_rules! call {
+                ($x:expr, $f:ident) => { helper($x) + $f($crate::helper($x)) };
+            }
+            fn main() {
+                let x = 1;
+                let total = vec![call!(x, helper), helper(x)];
+                assert_eq!(total.len(), 2, "{x}");
+                let point = Some(Point { x: x });
+                let y: Vec<i32> = vec![x];
+            }
+        "#,
+        r#"
+            fn __0(__1: i32) -> i32 { __1 }
+            macro_rules! call {
+                ($x:expr, $f:ident) => { __0($x) + $f($crate::__0($x)) };
+            }
+            fn main() {
+                let __2 = 1;
+                let __3 = vec![call!(__2, __0), __0(__2)];
+                assert_eq!(__3.len(), 2, "{__2}");
+                let __4 = Some(Point { x: __2 });
+                let __5: Vec<i32> = vec![__2];
+            }
+        "#
+    );
+}
+
+#[test]
+fn test_items_and_modules() {
+    assert_renamed(
+        r#"
+            mod util {
+                fn helper() -> i32 { 1 }
+                pub fn api() -> i32 { helper() + self::helper() }
+                mod checks {
+                    use super::helper;
+                    fn check() -> i32 { helper() + super::helper() }
+                }
+            }
+            use util::api;
+            fn main() {
+                let helper = 2;
+                fn inner() -> i32 { let helper = 3; helper }
+                println!("{}", api() + inner() + helper);
+            }
+        "#,
+        r#"
+            mod util {
+                fn __0() -> i32 { 1 }
+                pub fn api() -> i32 { __0() + self::__0() }
+                mod checks {
+                    use super::__0;
+                    fn __1() -> i32 { __0() + super::__0() }
+                }
+            }
+            use util::api;
+            fn main() {
+                let __3 = 2;
+                fn __2() -> i32 { let __4 = 3; __4 }
+                println!("{}", api() + __2() + __3);
+            }
+        "#
+    );
+}
+
+#[test]
+fn test_structs_fields_and_methods() {
+    assert_types_renamed(
+        r#"
+            struct Point { x: i32, y: i32 }
+            impl Point {
+                fn origin() -> Self { Self { x: 0, y: 0 } }
+                fn shifted(&self, dx: i32) -> Point { Point { x: self.x + dx, ..*self } }
+            }
+            fn main() {
+                let p = Point::origin().shifted(2);
+                let Point { x, y } = p;
+                println!("{} {}", p.x, x + y);
+            }
+        "#,
+        r#"
+            struct __0 { __1: i32, __2: i32 }
+            impl __0 {
+                fn __3() -> Self { Self { __1: 0, __2: 0 } }
+                fn __4(&self, __5: i32) -> __0 { __0 { __1: self.__1 + __5, ..*self } }
+            }
+            fn main() {
+                let __6 = __0::__3().__4(2);
+                let __0 { __1: __7, __2: __8 } = __6;
+                println!("{} {}", __6.__1, __7 + __8);
+            }
+        "#
+    );
+}
+
+#[test]
+fn test_enums_traits_and_imports() {
+    assert_types_renamed(
+        r#"
+            mod shapes {
+                #[derive(Debug, Clone)]
+                pub(crate) enum Shape { Circle(f64), Rect { w: f64, h: f64 }, Empty }
+                trait Area {
+                    fn area(&self) -> f64;
+                    fn double(&self) -> f64 { self.area() * 2.0 }
+                }
+                impl Area for Shape {
+                    fn area(&self) -> f64 {
+                        match self {
+                            Shape::Circle(r) => r * r,
+                            Self::Rect { w, h } => w * h,
+                            Shape::Empty => 0.0,
+                        }
+                    }
+                }
+                pub fn total(shapes: &[Shape]) -> f64 { shapes.iter().map(|s| s.double()).sum() }
+            }
+            use shapes::Shape;
+            use shapes::Shape::*;
+            fn main() {
+                let list = vec![Circle(1.0), Shape::Rect { w: 2.0, h: 3.0 }, Empty];
+                println!("{}", shapes::total(&list));
+            }
+        "#,
+        r#"
+            mod shapes {
+                #[derive(Debug, Clone)]
+                pub(crate) enum __0 { __1(f64), __2 { __5: f64, __6: f64 }, __3 }
+                trait __4 {
+                    fn __7(&self) -> f64;
+                    fn __8(&self) -> f64 { self.__7() * 2.0 }
+                }
+                impl __4 for __0 {
+                    fn __7(&self) -> f64 {
+                        match self {
+                            __0::__1(__9) => __9 * __9,
+                            Self::__2 { __5: __10, __6: __11 } => __10 * __11,
+                            __0::__3 => 0.0,
+                        }
+                    }
+                }
+                pub fn total(__12: &[__0]) -> f64 { __12.iter().map(|__13| __13.__8()).sum() }
+            }
+            use shapes::__0;
+            use shapes::__0::*;
+            fn main() {
+                let __14 = vec![__1(1.0), __0::__2 { __5: 2.0, __6: 3.0 }, __3];
+                println!("{}", shapes::total(&__14));
+            }
+        "#
+    );
+}
+
+#[test]
+fn test_public_and_foreign_names_are_kept() {
+    assert_types_renamed(
+        r#"
+            use std::fmt;
+            pub struct Config { name: String }
+            #[derive(Serialize)]
+            struct Wire { id: u32 }
+            struct Stack { items: Vec<i32>, name: String }
+            impl Stack {
+                fn len(&self) -> usize { self.items.len() }
+                fn top(&self) -> Option<&i32> { self.items.last() }
+            }
+            impl fmt::Display for Stack {
+                fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{}", self.name) }
+            }
+            fn main() {
+                let stack = Stack { items: vec![1], name: String::new() };
+                println!("{} {:?}", stack.len(), stack.top());
+            }
+        "#,
+        r#"
+            use std::fmt;
+            pub struct Config { name: String }
+            #[derive(Serialize)]
+            struct Wire { id: u32 }
+            struct __0 { __1: Vec<i32>, name: String }
+            impl __0 {
+                fn len(&self) -> usize { self.__1.len() }
+                fn __2(&self) -> Option<&i32> { self.__1.last() }
+            }
+            impl fmt::Display for __0 {
+                fn fmt(&self, __3: &mut fmt::Formatter) -> fmt::Result { write!(__3, "{}", self.name) }
+            }
+            fn main() {
+                let __4 = __0 { __1: vec![1], name: String::new() };
+                println!("{} {:?}", __4.len(), __4.__2());
+            }
+        "#
+    );
+}
+
+#[test]
+fn test_members_used_on_foreign_receivers_are_kept() {
+    assert_types_renamed(
+        r#"
+            struct Text { body: String }
+            impl Text {
+                fn trim(&self) -> &str { self.body.trim() }
+                fn shout(&self) -> String { self.trim().to_uppercase() }
+            }
+            fn main() {
+                let text = Text { body: String::from(" hi ") };
+                for line in "a \n b".lines() {
+                    println!("{}", line.trim());
+                }
+                println!("{}", text.shout());
+            }
+        "#,
+        r#"
+            struct __0 { __1: String }
+            impl __0 {
+                fn trim(&self) -> &str { self.__1.trim() }
+                fn __2(&self) -> String { self.trim().to_uppercase() }
+            }
+            fn main() {
+                let __3 = __0 { __1: String::from(" hi ") };
+                for __4 in "a \n b".lines() {
+                    println!("{}", __4.trim());
+                }
+                println!("{}", __3.__2());
+            }
+        "#
+    );
+}
+
+#[test]
+fn test_methods_called_through_trait_bounds() {
+    assert_types_renamed(
+        r#"
+            trait Maker { fn make(seed: u64) -> Self; }
+            struct Widget { seed: u64 }
+            impl Maker for Widget { fn make(seed: u64) -> Self { Widget { seed } } }
+            fn build<T: Maker>(seed: u64) -> T { T::make(seed) }
+            fn rebuild<T>(seed: u64) -> T where T: Maker { <T as Maker>::make(seed) }
+            fn main() {
+                let widget: Widget = build(1);
+                let other: Widget = rebuild(widget.seed);
+                println!("{}", other.seed);
+            }
+        "#,
+        r#"
+            trait __0 { fn __4(seed: u64) -> Self; }
+            struct __1 { __5: u64 }
+            impl __0 for __1 { fn __4(__6: u64) -> Self { __1 { __5: __6 } } }
+            fn __2<T: __0>(__7: u64) -> T { T::__4(__7) }
+            fn __3<T>(__8: u64) -> T where T: __0 { <T as __0>::__4(__8) }
+            fn main() {
+                let __9: __1 = __2(1);
+                let __10: __1 = __3(__9.__5);
+                println!("{}", __10.__5);
+            }
+        "#
+    );
+}
+
+#[test]
+fn test_opted_out_names_are_kept() {
+    assert_types_renamed(
+        r#"
+            #[obfuscate(rename = false)]
+            struct Wire { id: u32 }
+            impl Wire {
+                fn checksum(&self) -> u32 { helper(self.id) }
+            }
+            #[obfuscate(no_rename)]
+            mod ffi {
+                fn export(raw: u32) -> u32 { super::helper(raw) }
+            }
+            fn helper(value: u32) -> u32 { let doubled = value * 2; doubled }
+            #[obfuscate(skip)]
+            fn reflected(count: u32) -> u32 { let total = helper(count); total }
+        "#,
+        r#"
+            #[obfuscate(rename = false)]
+            struct Wire { id: u32 }
+            impl Wire {
+                fn checksum(&self) -> u32 { __0(self.id) }
+            }
+            #[obfuscate(no_rename)]
+            mod ffi {
+                fn export(raw: u32) -> u32 { super::__0(raw) }
+            }
+            fn __0(__1: u32) -> u32 { let __2 = __1 * 2; __2 }
+            #[obfuscate(skip)]
+            fn reflected(count: u32) -> u32 { let total = __0(count); total }
+        "#
+    );
+}
+
+#[test]
+fn test_crate_wide_renaming() {
+    let modules = [
+        (Vec::new(), "mod util; mod app; fn main() { app::run(); }"),
+        (
+            vec!["util".to_string()],
+            r#"
+                pub(crate) fn helper() -> i32 { private_part() }
+                fn private_part() -> i32 { 1 }
+                pub(super) fn parent_only() -> i32 { 2 }
+                pub fn api() -> i32 { 3 }
+            "#,
+        ),
+        (
+            vec!["app".to_string()],
+            r#"
+                use crate::util::helper;
+                use crate::util::*;
+                pub(crate) fn run() -> i32 { helper() + crate::util::parent_only() + api() + private_part() }
+                fn private_part() -> i32 { 4 }
+            "#,
+        ),
+    ];
+    let expected = [
+        "mod util; mod app; fn main() { app::__3(); }",
+        r#"
+            pub(crate) fn __0() -> i32 { __1() }
+            fn __1() -> i32 { 1 }
+            pub(super) fn __2() -> i32 { 2 }
+            pub fn api() -> i32 { 3 }
+        "#,
+        r#"
+            use crate::util::__0;
+            use crate::util::*;
+            pub(crate) fn __3() -> i32 { __0() + crate::util::__2() + api() + __4() }
+            fn __4() -> i32 { 4 }
+        "#,
+    ];
+    let rename_config = RenameConfig {
+        enable_rename_obfuscation: true,
+        seed: Some(7),
+        ..RenameConfig::default()
+    };
+    let mut renamer = VariableRenamer::new(rename_config);
+    let sources: Vec<ModuleSource> = modules
+        .iter()
+        .map(|(module, code)| ModuleSource {
+            module: module.clone(),
+            file: format!("{}.rs", module.join("/")),
+            code: code.to_string(),
+            original: code.to_string(),
+        })
+        .collect();
+    let renamed = renamer.rename_crate(&sources);
+
+    for (modified_code, expected) in renamed.iter().zip(expected) {
+        let expected_file = syn::parse_file(expected).expect("expected code is not valid Rust code");
+        let mut expected_code = quote!(#expected_file).to_string();
+        for (index, rename) in renamer.renames.iter().enumerate().rev() {
+            expected_code = expected_code.replace(&format!("__{}", index), &rename.renamed);
+        }
+        assert_eq!(modified_code, &expected_code);
+    }
+}
+
+#[test]
+fn test_new_names_avoid_existing_identifiers() {
+    let rename_config = RenameConfig {
+        enable_rename_obfuscation: true,
+        name_style: NameStyle::Short,
+        ..RenameConfig::default()
+    };
+    let mut renamer = VariableRenamer::new(rename_config);
+    let modified_code = renamer.rename("fn main() { let a = 1; let b = a; let c = e(b); }");
+    let expected_file: File = syn::parse_str("fn main() { let d = 1; let f = d; let g = e(f); }").unwrap();
+    assert_eq!(modified_code, quote!(#expected_file).to_string());
+}
+
+#[test]
+fn test_renames_record_symbols_and_lines() {
+    let original = "enum Shape {\n    Circle(f64),\n}\nfn area(shape: Shape) -> f64 {\n    let Shape::Circle(r) = shape;\n    let r = r * r;\n    r * 3.0\n}\nfn main() {\n    let x = area(Shape::Circle(1.0));\n    println!(\"{}\", x);\n}\n";
+    //what the other passes hand over, all on one line
+    let file = syn::parse_file(original).unwrap();
+    let reformatted = quote!(#file).to_string();
+    let rename_config = RenameConfig {
+        enable_rename_obfuscation: true,
+        rename_types: true,
+        file: "src/main.rs".to_string(),
+        ..RenameConfig::default()
+    };
+    let mut renamer = VariableRenamer::new(rename_config);
+    renamer.rename_file(original, &reformatted);
+
+    let symbols: Vec<(SymbolKind, &str, &str, usize)> = renamer.renames
+        .iter()
+        .map(|rename| (rename.kind, rename.original.as_str(), rename.symbol.as_str(), rename.line))
+        .collect();
+    assert_eq!(symbols, [
+        (SymbolKind::Type, "Shape", "Shape", 1),
+        (SymbolKind::Variant, "Circle", "Shape::Circle", 2),
+        (SymbolKind::Fn, "area", "area", 4),
+        (SymbolKind::Local, "shape", "area::shape", 4),
+        (SymbolKind::Local, "r", "area::r", 5),
+        (SymbolKind::Local, "r", "area::r#1", 6),
+        (SymbolKind::Local, "x", "main::x", 10),
+    ]);
+    assert!(renamer.renames.iter().all(|rename| rename.file == "src/main.rs"));
+}
+
+#[test]
+fn test_previous_names_are_reused() {
+    let renamer_with = |seed: u64, previous: Vec<Rename>| {
+        VariableRenamer::new(RenameConfig {
+            enable_rename_obfuscation: true,
+            rename_types: true,
+            seed: Some(seed),
+            file: "src/main.rs".to_string(),
+            previous,
+            ..RenameConfig::default()
+        })
+    };
+    let v1 = r#"
+        struct Point { x: i32 }
+        fn helper(p: &Point) -> i32 { let sum = p.x + 1; sum }
+        fn main() { let p = Point { x: 1 }; println!("{}", helper(&p)); }
+    "#;
+    let mut renamer = renamer_with(1, Vec::new());
+    renamer.rename(v1);
+    let previous = renamer.renames;
+    let old_name = |symbol: &str| previous.iter().find(|rename| rename.symbol == symbol).unwrap().renamed.clone();
+
+    //a new fn and local, and a local that happens to be called like the old name of `helper::sum`
+    let v2 = format!(
+        r#"
+        struct Point {{ x: i32 }}
+        fn helper(p: &Point) -> i32 {{ let sum = p.x + 1; sum }}
+        fn twice(n: i32) -> i32 {{ n * 2 }}
+        fn main() {{ let p = Point {{ x: 1 }}; let {} = twice(helper(&p)); println!("{{}}", {}); }}
+    "#,
+        old_name("helper::sum"),
+        old_name("helper::sum")
+    );
+    let mut renamer = renamer_with(2, previous.clone());
+    renamer.rename(&v2);
+
+    for rename in renamer.renames.iter() {
+        match rename.symbol.as_str() {
+            "Point" | "x" | "helper" | "helper::p" | "main::p" => {
+                assert_eq!(rename.renamed, old_name(&rename.symbol), "{}", rename.symbol);
+            }
+            _ => {
+                assert!(
+                    previous.iter().all(|old| old.renamed != rename.renamed),
+                    "{} got the earlier name {}",
+                    rename.symbol,
+                    rename.renamed
+                );
+            }
+        }
+    }
+    assert_eq!(renamer.renames.len(), previous.len() + 3);
+}
@@ -1,18 +1,54 @@
 use syn::{
     visit_mut::VisitMut,
+    Attribute,
+    ExprConst,
+    GenericArgument,
+    ImplItem,
+    ImplItemConst,
+    Pat,
+    TraitItem,
+    TraitItemConst,
+    TraitItemFn,
     visit::Visit,
     parse_file,
-    parse_str,
     Expr,
+    ExprAssign,
     ExprLit,
+    ExprPath,
+    LitByteStr,
     LitStr,
     Lit,
     File,
+    Item,
+    ItemConst,
+    ItemStatic,
+    ImplItemFn,
+    ItemFn,
+    ItemMod,
+    ItemUse,
     Local,
+    Macro,
+    PatIdent,
+    StaticMutability,
+    Type,
+    TypeArray,
+    ExprRepeat,
+    UseTree,
+    Visibility,
     parse_quote,
 };
-use quote::quote;
-use proc_macro2::{ TokenStream, TokenTree };
+use quote::{ format_ident, quote, ToTokens };
+use proc_macro2::{ Delimiter, Group, Ident, Span, TokenStream, TokenTree };
+use crate::obfuscate::seeded_rng;
+use crate::attributes::{ impl_item_attrs, is_disabled, item_attrs, trait_item_attrs, Pass };
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+use labyrinth_core::cipher::CipherKind;
+use labyrinth_core::codegen::{ secret, EncryptedLiteral };
+use std::collections::{ HashMap, HashSet };
+
+pub mod format;
+use format::{ parse_format_string, FormatMacro, FormatPiece };
 
 #[cfg(test)]
 mod string_tests;
@@ -20,103 +56,519 @@ mod string_tests;
 #[derive(Clone)]
 pub struct StringConfig {
     pub enable_string_obfuscation: bool,
+    //emit `cryptify::encrypt_string!` and friends, or what they expand to
+    pub use_macro: bool,
     pub percentage: u8,
+    pub strategy: SelectionStrategy,
+    pub seed: Option<u64>,
+    pub report: bool,
 }
 impl StringConfig {
     pub fn default() -> Self {
         Self {
             enable_string_obfuscation: true,
+            use_macro: true,
             percentage: 100,
+            strategy: SelectionStrategy::FirstN,
+            seed: None,
+            report: false,
+        }
+    }
+}
+
+//how `percentage` picks the eligible literals that get encrypted
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectionStrategy {
+    //uniformly at random, reproducible when a seed is set
+    Random,
+    //the first literals in source order
+    FirstN,
+    //the literals carrying the most information first, i.e. long and high entropy ones
+    Priority,
+}
+
+impl SelectionStrategy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "random" => Some(Self::Random),
+            "first" => Some(Self::FirstN),
+            "priority" => Some(Self::Priority),
+            _ => None,
+        }
+    }
+}
+
+//an eligible literal as written in the source
+#[derive(Clone, Debug)]
+pub struct StringLiteral {
+    pub literal: String,
+    pub line: usize,
+    pub column: usize,
+    value: Vec<u8>,
+}
+
+impl StringLiteral {
+    fn new(lit: &Lit) -> Self {
+        let value = match lit {
+            Lit::Str(lit_str) => lit_str.value().into_bytes(),
+            Lit::ByteStr(lit_bytes) => lit_bytes.value(),
+            _ => Vec::new(),
+        };
+        let start = lit.span().start();
+        Self {
+            literal: lit.to_token_stream().to_string(),
+            line: start.line,
+            column: start.column + 1,
+            value,
         }
     }
+
+    //shannon entropy of the bytes times their count, roughly the information the literal carries
+    fn score(&self) -> f64 {
+        let mut counts = [0usize; 256];
+        for byte in &self.value {
+            counts[*byte as usize] += 1;
+        }
+        let len = self.value.len() as f64;
+        let entropy: f64 = counts
+            .iter()
+            .filter(|count| **count > 0)
+            .map(|count| {
+                let p = (*count as f64) / len;
+                -p * p.log2()
+            })
+            .sum();
+        entropy * len
+    }
 }
 
 pub struct StringObfuscator {
     pub enabled: bool,
-    #[allow(dead_code)]
+    use_macro: bool,
+    //what the per-literal keys are derived from without macros, like `CRYPTIFY_KEY` at compile time with them
+    secret: String,
+    rng: StdRng,
     percentage: u8,
-    encrypted_count: usize,
-    strings_to_encrypt: usize,
-    num_strings_encrypted: usize,
+    strategy: SelectionStrategy,
+    seed: Option<u64>,
+    //eligible literals of the current file in visiting order
+    candidates: Vec<StringLiteral>,
+    //indices into candidates chosen for encryption, None while the candidates are being collected
+    selected: Option<HashSet<usize>>,
+    next_candidate: usize,
+    lazy_items: HashSet<LazyItem>,
+    scope_items: ScopeItems,
+    //the scope being visited
+    scope: Scope,
 }
 
 impl StringObfuscator {
     pub fn new(config: StringConfig) -> Self {
         Self {
             enabled: config.enable_string_obfuscation,
+            use_macro: config.use_macro,
+            secret: secret(),
+            rng: seeded_rng(config.seed),
             percentage: config.percentage,
-            encrypted_count: 0,
-            strings_to_encrypt: 0,
-            num_strings_encrypted: 0,
+            strategy: config.strategy,
+            seed: config.seed,
+            candidates: Vec::new(),
+            selected: None,
+            next_candidate: 0,
+            lazy_items: HashSet::new(),
+            scope_items: ScopeItems::default(),
+            scope: Vec::new(),
         }
     }
-    #[allow(dead_code)]
-    fn process_macro_tokens(&self, tokens: TokenStream) -> TokenStream {
-        tokens
-            .into_iter()
-            .map(|token| {
-                (
-                    match token {
-                        TokenTree::Literal(lit) => {
-                            //convert literal obj to string
-                            let lit_str = lit.to_string();
-
-                            //replace literal obj with macro call
-                            if let Ok(lit_str) = parse_str::<LitStr>(&lit_str) {
-                                let macro_call: TokenStream =
-                                    quote! {
-                        cryptify::encrypt_string!(#lit_str)
-                    };
-                                return macro_call;
-                            }
-
-                            TokenTree::Literal(lit)
-                        }
-                        //handle nested groups in macro
-                        TokenTree::Group(group) => {
-                            let new_stream = self.process_macro_tokens(group.stream());
-                            TokenTree::Group(proc_macro2::Group::new(group.delimiter(), new_stream))
+    //every eligible literal passes through here in the same order on both passes, the first pass
+    //only records it and the second one reports whether it was selected
+    fn try_encrypt(&mut self, lit: impl Into<Lit>) -> bool {
+        let Some(selected) = &self.selected else {
+            self.candidates.push(StringLiteral::new(&lit.into()));
+            return false;
+        };
+        let index = self.next_candidate;
+        self.next_candidate += 1;
+        selected.contains(&index)
+    }
+
+    //pick `percentage` of the candidates according to the strategy
+    fn select(&self) -> HashSet<usize> {
+        let total = self.candidates.len();
+        let amount = (((self.percentage as f64) / 100.0) * (total as f64)).ceil() as usize;
+        match self.strategy {
+            SelectionStrategy::FirstN => (0..amount).collect(),
+            SelectionStrategy::Random => {
+                sample(&mut seeded_rng(self.seed), total, amount).into_iter().collect()
+            }
+            SelectionStrategy::Priority => {
+                let mut order: Vec<usize> = (0..total).collect();
+                //stable, so equally scored literals keep their source order
+                order.sort_by(|a, b| self.candidates[*b].score().total_cmp(&self.candidates[*a].score()));
+                order.into_iter().take(amount).collect()
+            }
+        }
+    }
+
+    //literals chosen in the last processed file, in source order
+    pub fn encrypted_literals(&self) -> Vec<&StringLiteral> {
+        let Some(selected) = &self.selected else {
+            return Vec::new();
+        };
+        self.candidates
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| selected.contains(index))
+            .map(|(_, literal)| literal)
+            .collect()
+    }
+
+    pub fn report(&self) -> String {
+        let encrypted = self.encrypted_literals();
+        let mut report = format!(
+            "encrypted {} of {} eligible string literals",
+            encrypted.len(),
+            self.candidates.len()
+        );
+        for literal in encrypted {
+            report.push_str(&format!("\n  {}:{} {}", literal.line, literal.column, literal.literal));
+        }
+        report
+    }
+
+    //`cryptify::encrypt_string!(lit)`, or what it expands to when macros are disabled
+    fn encrypt_string(&mut self, lit: &LitStr) -> Expr {
+        if self.use_macro {
+            return parse_quote!(cryptify::encrypt_string!(#lit));
+        }
+        let decrypt = self.encrypt_literal(lit.value().as_bytes(), lit.span()).decrypt_string();
+        parse_quote!(#decrypt)
+    }
+
+    fn encrypt_static(&mut self, lit: &LitStr) -> Expr {
+        if self.use_macro {
+            return parse_quote!(cryptify::encrypt_static!(#lit));
+        }
+        let decrypt = self.encrypt_literal(lit.value().as_bytes(), lit.span()).decrypt_static_str();
+        parse_quote!(#decrypt)
+    }
+
+    fn encrypt_bytes(&mut self, lit: &LitByteStr) -> Expr {
+        if self.use_macro {
+            return parse_quote!(cryptify::encrypt_bytes!(#lit));
+        }
+        let decrypt = self.encrypt_literal(&lit.value(), lit.span()).decrypt_bytes();
+        parse_quote!(#decrypt)
+    }
+
+    //without macros literals are encrypted with the cipher the macros default to
+    fn encrypt_literal(&mut self, plaintext: &[u8], span: Span) -> EncryptedLiteral {
+        EncryptedLiteral::new(plaintext, span, CipherKind::Xor, self.secret.as_bytes(), &mut self.rng)
+    }
+
+    //rewrite a format macro so its text and plain literal arguments are encrypted, while every
+    //placeholder (including inline captures like `{name}`) stays in a literal format string
+    fn encrypt_format_macro(&mut self, mac: &mut Macro) -> bool {
+        let Some(mut format_macro) = FormatMacro::parse(mac) else {
+            return false;
+        };
+        let format_index = format_macro.format_index;
+
+        //handle nested macros and lazy items in the arguments first,
+        //plain literal arguments are left for the pass below
+        for (index, arg) in format_macro.args.iter_mut().enumerate() {
+            let value = match arg {
+                Expr::Assign(ExprAssign { right, .. }) if index > format_index => &mut **right,
+                _ => arg,
+            };
+            if index != format_index && !matches!(value, Expr::Lit(ExprLit { lit: Lit::Str(_), .. })) {
+                self.visit_expr_mut(value);
+            }
+        }
+
+        let format_string = format_macro.format_string().clone();
+        if let Some(pieces) = parse_format_string(&format_string.value()) {
+            let has_text = pieces.iter().any(|piece| matches!(piece, FormatPiece::Text(_)));
+            if has_text && self.try_encrypt(format_string.clone()) {
+                let mut new_format = String::new();
+                let mut text_args: Vec<Expr> = Vec::new();
+                for piece in pieces {
+                    match piece {
+                        FormatPiece::Text(text) => {
+                            //named arguments don't shift the implicit positional counter of `{}`
+                            let name = format_ident!("__cryptify_{}", text_args.len());
+                            new_format.push_str(&format!("{{{}}}", name));
+                            let encrypted = self.encrypt_string(&LitStr::new(&text, format_string.span()));
+                            text_args.push(parse_quote!(#name = #encrypted));
                         }
-                        _ => token,
+                        FormatPiece::Placeholder(placeholder) => new_format.push_str(&placeholder),
                     }
-                ).into()
-            })
-            .collect()
+                }
+                format_macro.args[format_index] = Expr::Lit(ExprLit {
+                    attrs: Vec::new(),
+                    lit: Lit::Str(LitStr::new(&new_format, format_string.span())),
+                });
+                for text_arg in text_args {
+                    format_macro.args.push(text_arg);
+                }
+            }
+        }
+
+        let mut literal_args: Vec<&mut Expr> = format_macro.literal_args_mut().collect();
+        for arg in literal_args.iter_mut() {
+            if let Expr::Lit(ExprLit { lit: Lit::Str(lit_str), .. }) = &**arg {
+                if self.try_encrypt(lit_str.clone()) {
+                    **arg = self.encrypt_string(&lit_str.clone());
+                }
+            }
+        }
+
+        let args = &format_macro.args;
+        mac.tokens = quote!(#args);
+        true
+    }
+
+    //replace a selected const/static literal item with a lazily decrypted static of the same name
+    fn lazy_item(&mut self, item: &Item) -> Option<Item> {
+        let (attrs, vis, ident, expr) = match item {
+            Item::Const(ItemConst { attrs, vis, ident, expr, .. }) => (attrs, vis, ident, expr),
+            Item::Static(ItemStatic { attrs, vis, ident, expr, .. }) => (attrs, vis, ident, expr),
+            _ => {
+                return None;
+            }
+        };
+        if !self.lazy_items.contains(&(self.scope.clone(), ident.to_string())) {
+            return None;
+        }
+        let init: Expr = match &**expr {
+            Expr::Lit(ExprLit { lit: Lit::Str(lit_str), .. }) => {
+                let decrypt = self.encrypt_string(lit_str);
+                parse_quote! {
+                    cryptify::Lazy::<String>::new(|| #decrypt)
+                }
+            }
+            Expr::Lit(ExprLit { lit: Lit::ByteStr(lit_bytes), .. }) => {
+                let decrypt = self.encrypt_bytes(lit_bytes);
+                parse_quote! {
+                    cryptify::Lazy::<Vec<u8>>::new(|| #decrypt)
+                }
+            }
+            _ => {
+                return None;
+            }
+        };
+        let target: Type = match &**expr {
+            Expr::Lit(ExprLit { lit: Lit::Str(_), .. }) => parse_quote!(String),
+            _ => parse_quote!(Vec<u8>),
+        };
+        Some(
+            parse_quote! {
+            #(#attrs)*
+            #vis static #ident: cryptify::Lazy<#target> = #init;
+        }
+        )
+    }
+
+    //whether a bare `name` used in the current scope refers to a lazy item
+    fn is_lazy_item(&self, name: &str) -> bool {
+        !self.lazy_items.is_empty() &&
+            self.scope_items
+                .resolve(&self.scope, name)
+                .is_some_and(|scope| self.lazy_items.contains(&(scope, name.to_string())))
+    }
+
+    //visit a fn body as a scope of its own, items declared in it are only visible there
+    fn visit_fn_scope(&mut self, name: &Ident, visit: impl FnOnce(&mut Self)) {
+        self.scope.push(fn_scope(name));
+        visit(self);
+        self.scope.pop();
+    }
+
+    //rewrite bare uses of lazy items inside macro tokens, e.g. `println!("{}", NAME)`
+    fn process_lazy_item_tokens(&self, tokens: TokenStream) -> TokenStream {
+        let mut output = Vec::new();
+        let mut previous: Option<TokenTree> = None;
+        for token in tokens {
+            let replaced = match &token {
+                TokenTree::Group(group) => {
+                    let mut new_group = Group::new(
+                        group.delimiter(),
+                        self.process_lazy_item_tokens(group.stream())
+                    );
+                    new_group.set_span(group.span());
+                    TokenTree::Group(new_group)
+                }
+                //skip field accesses and path segments like `a.NAME` or `module::NAME`
+                TokenTree::Ident(ident) if
+                    self.is_lazy_item(&ident.to_string()) &&
+                    !matches!(&previous, Some(TokenTree::Punct(punct)) if punct.as_char() == '.' || punct.as_char() == ':')
+                => {
+                    TokenTree::Group(Group::new(Delimiter::Parenthesis, quote!(&*#ident)))
+                }
+                _ => token.clone(),
+            };
+            previous = Some(token);
+            output.push(replaced);
+        }
+        output.into_iter().collect()
     }
 
     pub fn obfuscate_strings(&mut self, code: &str) -> String {
         let ast = parse_file(code).expect("Failed to parse code");
+        if is_disabled(&ast.attrs, Pass::Strings) {
+            return code.to_string();
+        }
 
-        let total_strings = count_string_literals(&ast);
-        let strings_to_encrypt = (
-            ((self.percentage as f32) / 100.0) *
-            (total_strings as f32)
-        ).ceil() as usize;
-        self.encrypted_count = 0;
-        self.strings_to_encrypt = strings_to_encrypt;
+        //const/static items that can become lazily decrypted statics come first, then a dry run
+        //of the visitor collects every literal it would encrypt
+        let (lazy_candidates, scope_items) = collect_lazy_item_candidates(&ast);
+        let lazy_count = lazy_candidates.len();
+        self.scope_items = scope_items;
+        self.candidates = lazy_candidates
+            .iter()
+            .map(|(_, lit)| StringLiteral::new(lit))
+            .collect();
+        self.selected = None;
+        self.lazy_items.clear();
+        self.visit_file_mut(&mut ast.clone());
+
+        let selected = self.select();
+        self.lazy_items = lazy_candidates
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| selected.contains(index))
+            .map(|(_, (item, _))| item)
+            .collect();
+        //the second pass only sees the literals after the lazy items, selected or not
+        self.next_candidate = lazy_count;
+        self.selected = Some(selected);
 
         let mut modified_ast = ast.clone();
         self.visit_file_mut(&mut modified_ast);
-        let modified_code = quote!(#modified_ast).to_string();
-        modified_code
+        quote!(#modified_ast).to_string()
     }
 }
 
 impl VisitMut for StringObfuscator {
-    //replace all string literals with call to obfuscation macro
-    fn visit_local_mut(&mut self, local: &mut Local) {
-        if let Some(local_init) = &mut local.init {
-            if self.num_strings_encrypted >= self.strings_to_encrypt {
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        if is_disabled(item_attrs(item), Pass::Strings) {
+            return;
+        }
+        if let Some(lazy) = self.lazy_item(item) {
+            *item = lazy;
+            return;
+        }
+        syn::visit_mut::visit_item_mut(self, item);
+    }
+
+    fn visit_impl_item_mut(&mut self, item: &mut ImplItem) {
+        if !is_disabled(impl_item_attrs(item), Pass::Strings) {
+            syn::visit_mut::visit_impl_item_mut(self, item);
+        }
+    }
+
+    fn visit_trait_item_mut(&mut self, item: &mut TraitItem) {
+        if !is_disabled(trait_item_attrs(item), Pass::Strings) {
+            syn::visit_mut::visit_trait_item_mut(self, item);
+        }
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        match expr {
+            //uses of a lazy item deref to the `&'static str` or `&'static [u8]` the item used to be
+            Expr::Path(ExprPath { qself: None, path, .. }) => {
+                if let Some(ident) = path.get_ident() {
+                    if self.is_lazy_item(&ident.to_string()) {
+                        *expr = parse_quote!((&*#ident));
+                        return;
+                    }
+                }
+            }
+            //any other string literal is `&'static str`, which the lazily decrypted form matches
+            Expr::Lit(ExprLit { lit: Lit::Str(lit_str), attrs }) => {
+                if attrs.is_empty() && self.try_encrypt(lit_str.clone()) {
+                    *expr = self.encrypt_static(&lit_str.clone());
+                }
                 return;
             }
-            self.num_strings_encrypted += 1;
+            _ => {}
+        }
+        syn::visit_mut::visit_expr_mut(self, expr);
+    }
+
+    //const contexts can't call the decryption code
+    fn visit_item_const_mut(&mut self, _i: &mut ItemConst) {}
 
+    fn visit_item_static_mut(&mut self, _i: &mut ItemStatic) {}
 
-            //match on local variables that contain string literal assignments
+    fn visit_impl_item_const_mut(&mut self, _i: &mut ImplItemConst) {}
+
+    fn visit_trait_item_const_mut(&mut self, _i: &mut TraitItemConst) {}
+
+    fn visit_expr_const_mut(&mut self, _i: &mut ExprConst) {}
+
+    fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
+        if i.sig.constness.is_none() {
+            self.visit_fn_scope(&i.sig.ident.clone(), |this| syn::visit_mut::visit_item_fn_mut(this, i));
+        }
+    }
+
+    fn visit_impl_item_fn_mut(&mut self, i: &mut ImplItemFn) {
+        if i.sig.constness.is_none() {
+            self.visit_fn_scope(&i.sig.ident.clone(), |this| syn::visit_mut::visit_impl_item_fn_mut(this, i));
+        }
+    }
+
+    fn visit_trait_item_fn_mut(&mut self, i: &mut TraitItemFn) {
+        if i.sig.constness.is_none() {
+            self.visit_fn_scope(&i.sig.ident.clone(), |this| syn::visit_mut::visit_trait_item_fn_mut(this, i));
+        }
+    }
+
+    fn visit_item_mod_mut(&mut self, i: &mut ItemMod) {
+        self.scope.push(i.ident.to_string());
+        syn::visit_mut::visit_item_mod_mut(self, i);
+        self.scope.pop();
+    }
+
+    fn visit_expr_repeat_mut(&mut self, i: &mut ExprRepeat) {
+        self.visit_expr_mut(&mut i.expr);
+    }
+
+    fn visit_generic_argument_mut(&mut self, i: &mut GenericArgument) {
+        if !matches!(i, GenericArgument::Const(_)) {
+            syn::visit_mut::visit_generic_argument_mut(self, i);
+        }
+    }
+
+    fn visit_type_mut(&mut self, _i: &mut Type) {}
+
+    //string patterns must stay literals
+    fn visit_pat_mut(&mut self, _i: &mut Pat) {}
+
+    //doc comments, `#[path = "..."]`, `#[cfg(feature = "...")]` and friends
+    fn visit_attribute_mut(&mut self, _i: &mut Attribute) {}
+
+    fn visit_macro_mut(&mut self, mac: &mut Macro) {
+        if self.encrypt_format_macro(mac) {
+            return;
+        }
+        if !self.lazy_items.is_empty() {
+            mac.tokens = self.process_lazy_item_tokens(mac.tokens.clone());
+        }
+    }
+
+    //a literal bound by an untyped let decrypts to an owned `String`, every other
+    //literal reached through the recursion goes through `visit_expr_mut`
+    fn visit_local_mut(&mut self, local: &mut Local) {
+        if let Some(local_init) = &mut local.init {
             if let Expr::Lit(ExprLit { lit: Lit::Str(lit_str), .. }) = &*local_init.expr {
-                let encrypted = quote! { cryptify::encrypt_string!(#lit_str) };
-                let new_expr: Expr = parse_quote!(#encrypted);
-                *local_init.expr = *Box::new(new_expr);
+                if !matches!(local.pat, Pat::Type(_)) {
+                    if self.try_encrypt(lit_str.clone()) {
+                        *local_init.expr = self.encrypt_string(&lit_str.clone());
+                    }
+                    return;
+                }
             }
         }
 
@@ -124,24 +576,296 @@ impl VisitMut for StringObfuscator {
     }
 }
 
-struct StringLiteralCounter {
-    count: usize,
+//finds private `const`/`static` items holding a plain string or byte string literal that can safely
+//become lazily decrypted statics, i.e. they are never used in patterns or other const contexts
+#[derive(Default)]
+struct LazyItemCollector {
+    candidates: Vec<(LazyItem, Lit)>,
+    blocked: HashSet<String>,
+    const_depth: usize,
+    scope: Scope,
+    scope_items: ScopeItems,
+}
+
+impl LazyItemCollector {
+    fn declare(&mut self, name: &Ident) {
+        self.scope_items.declared.entry(self.scope.clone()).or_default().insert(name.to_string());
+    }
+
+    fn visit_fn_scope(&mut self, name: &Ident, visit: impl FnOnce(&mut Self)) {
+        self.scope.push(fn_scope(name));
+        visit(self);
+        self.scope.pop();
+    }
+
+    //record the glob imports of a `use` tree, its named imports can't be told apart from uses of a
+    //lazy item elsewhere so they keep the items they import from becoming one
+    fn visit_use_path(&mut self, tree: &UseTree, mut path: Scope) {
+        match tree {
+            UseTree::Path(use_path) => {
+                let segment = use_path.ident.to_string();
+                match segment.as_str() {
+                    "crate" => path.clear(),
+                    "self" => {}
+                    "super" => {
+                        path.pop();
+                        path = module_of(&path);
+                    }
+                    _ => path.push(segment),
+                }
+                self.visit_use_path(&use_path.tree, path);
+            }
+            UseTree::Name(use_name) => {
+                self.blocked.insert(use_name.ident.to_string());
+            }
+            UseTree::Rename(use_rename) => {
+                self.blocked.insert(use_rename.ident.to_string());
+            }
+            UseTree::Glob(_) => {
+                self.scope_items.globs.entry(self.scope.clone()).or_default().push(path);
+            }
+            UseTree::Group(group) => group.items.iter().for_each(|tree| self.visit_use_path(tree, path.clone())),
+        }
+    }
+
+    fn visit_const_context(&mut self, visit: impl FnOnce(&mut Self)) {
+        self.const_depth += 1;
+        visit(self);
+        self.const_depth -= 1;
+    }
 }
 
-impl StringLiteralCounter {
-    fn new() -> Self {
-        Self { count: 0 }
+//`&str`, `&'static str`, `&[u8]` or `&'static [u8]`
+fn is_literal_ref_type(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(reference) if reference.mutability.is_none() =>
+            match &*reference.elem {
+                Type::Path(path) => path.qself.is_none() && path.path.is_ident("str"),
+                Type::Slice(slice) =>
+                    matches!(&*slice.elem, Type::Path(path) if path.qself.is_none() && path.path.is_ident("u8")),
+                _ => false,
+            }
+        _ => false,
     }
 }
 
-impl<'ast> Visit<'ast> for StringLiteralCounter {
-    fn visit_lit_str(&mut self, _lit_str: &'ast LitStr) {
-        self.count += 1;
+fn literal_item(vis: &Visibility, ty: &Type, expr: &Expr) -> Option<Lit> {
+    match expr {
+        Expr::Lit(ExprLit { lit: lit @ (Lit::Str(_) | Lit::ByteStr(_)), .. }) if
+            matches!(vis, Visibility::Inherited) && is_literal_ref_type(ty)
+        => Some(lit.clone()),
+        _ => None,
     }
 }
 
-fn count_string_literals(ast: &File) -> usize {
-    let mut counter = StringLiteralCounter::new();
-    counter.visit_file(ast);
-    counter.count
+impl<'ast> Visit<'ast> for LazyItemCollector {
+    //items opted out of string encryption keep their constants, and the ones they use stay plain
+    //constants as well
+    fn visit_item(&mut self, item: &'ast Item) {
+        if is_disabled(item_attrs(item), Pass::Strings) {
+            let candidates = self.candidates.len();
+            self.visit_const_context(|this| syn::visit::visit_item(this, item));
+            self.candidates.truncate(candidates);
+        } else {
+            syn::visit::visit_item(self, item);
+        }
+    }
+
+    fn visit_impl_item(&mut self, item: &'ast ImplItem) {
+        if is_disabled(impl_item_attrs(item), Pass::Strings) {
+            self.visit_const_context(|this| syn::visit::visit_impl_item(this, item));
+        } else {
+            syn::visit::visit_impl_item(self, item);
+        }
+    }
+
+    fn visit_trait_item(&mut self, item: &'ast TraitItem) {
+        if is_disabled(trait_item_attrs(item), Pass::Strings) {
+            self.visit_const_context(|this| syn::visit::visit_trait_item(this, item));
+        } else {
+            syn::visit::visit_trait_item(self, item);
+        }
+    }
+
+    fn visit_item_const(&mut self, item: &'ast ItemConst) {
+        self.declare(&item.ident);
+        if let Some(lit) = literal_item(&item.vis, &item.ty, &item.expr) {
+            self.candidates.push(((self.scope.clone(), item.ident.to_string()), lit));
+        }
+        self.visit_const_context(|this| syn::visit::visit_item_const(this, item));
+    }
+
+    fn visit_item_static(&mut self, item: &'ast ItemStatic) {
+        self.declare(&item.ident);
+        if let Some(lit) = literal_item(&item.vis, &item.ty, &item.expr) {
+            if matches!(item.mutability, StaticMutability::None) {
+                self.candidates.push(((self.scope.clone(), item.ident.to_string()), lit));
+            }
+        }
+        self.visit_const_context(|this| syn::visit::visit_item_static(this, item));
+    }
+
+    fn visit_item_fn(&mut self, item: &'ast ItemFn) {
+        self.declare(&item.sig.ident);
+        self.visit_fn_scope(&item.sig.ident, |this| {
+            if item.sig.constness.is_some() {
+                this.visit_const_context(|this| syn::visit::visit_item_fn(this, item));
+            } else {
+                syn::visit::visit_item_fn(this, item);
+            }
+        });
+    }
+
+    fn visit_impl_item_fn(&mut self, item: &'ast ImplItemFn) {
+        self.visit_fn_scope(&item.sig.ident, |this| {
+            if item.sig.constness.is_some() {
+                this.visit_const_context(|this| syn::visit::visit_impl_item_fn(this, item));
+            } else {
+                syn::visit::visit_impl_item_fn(this, item);
+            }
+        });
+    }
+
+    fn visit_trait_item_fn(&mut self, item: &'ast TraitItemFn) {
+        self.visit_fn_scope(&item.sig.ident, |this| syn::visit::visit_trait_item_fn(this, item));
+    }
+
+    fn visit_item_mod(&mut self, item: &'ast ItemMod) {
+        self.scope.push(item.ident.to_string());
+        syn::visit::visit_item_mod(self, item);
+        self.scope.pop();
+    }
+
+    fn visit_item_use(&mut self, item: &'ast ItemUse) {
+        let module = module_of(&self.scope);
+        self.visit_use_path(&item.tree, module);
+    }
+
+    fn visit_expr_repeat(&mut self, expr: &'ast ExprRepeat) {
+        self.visit_expr(&expr.expr);
+        self.visit_const_context(|this| this.visit_expr(&expr.len));
+    }
+
+    fn visit_type_array(&mut self, ty: &'ast TypeArray) {
+        self.visit_type(&ty.elem);
+        self.visit_const_context(|this| this.visit_expr(&ty.len));
+    }
+
+    //only bare names are rewritten, so items used through a path like `module::NAME` stay as they are
+    fn visit_expr_path(&mut self, expr: &'ast ExprPath) {
+        if self.const_depth > 0 || expr.qself.is_some() || expr.path.segments.len() > 1 {
+            if let Some(segment) = expr.path.segments.last() {
+                self.blocked.insert(segment.ident.to_string());
+            }
+        }
+        syn::visit::visit_expr_path(self, expr);
+    }
+
+    //macro arguments aren't parsed, so any identifier in them may be a use
+    fn visit_macro(&mut self, mac: &'ast Macro) {
+        if self.const_depth > 0 {
+            block_idents(mac.tokens.clone(), &mut self.blocked);
+        } else {
+            block_path_idents(mac.tokens.clone(), &mut self.blocked);
+        }
+    }
+
+    //constants used as match patterns parse as ident patterns
+    fn visit_pat_ident(&mut self, pat: &'ast PatIdent) {
+        self.blocked.insert(pat.ident.to_string());
+        syn::visit::visit_pat_ident(self, pat);
+    }
+}
+
+fn block_idents(tokens: TokenStream, blocked: &mut HashSet<String>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => {
+                blocked.insert(ident.to_string());
+            }
+            TokenTree::Group(group) => block_idents(group.stream(), blocked),
+            _ => {}
+        }
+    }
+}
+
+//`module::NAME` inside macro tokens
+fn block_path_idents(tokens: TokenStream, blocked: &mut HashSet<String>) {
+    let mut previous: Option<TokenTree> = None;
+    for token in tokens {
+        match &token {
+            TokenTree::Ident(ident) if matches!(&previous, Some(TokenTree::Punct(punct)) if punct.as_char() == ':') => {
+                blocked.insert(ident.to_string());
+            }
+            TokenTree::Group(group) => block_path_idents(group.stream(), blocked),
+            _ => {}
+        }
+        previous = Some(token);
+    }
+}
+
+//the lazy item candidates of a file, and where its items are declared
+fn collect_lazy_item_candidates(ast: &File) -> (Vec<(LazyItem, Lit)>, ScopeItems) {
+    let mut collector = LazyItemCollector::default();
+    collector.visit_file(ast);
+    let LazyItemCollector { candidates, blocked, scope_items, .. } = collector;
+    let candidates = candidates
+        .into_iter()
+        .filter(|((_, name), _)| !blocked.contains(name))
+        .collect();
+    (candidates, scope_items)
+}
+
+//where an item is declared: the inline modules around it, then `name()` for each fn whose body
+//declares it
+type Scope = Vec<String>;
+
+//a const/static item by the scope it is declared in and its name
+type LazyItem = (Scope, String);
+
+fn fn_scope(name: &Ident) -> String {
+    format!("{}()", name)
+}
+
+fn is_fn_scope(segment: &str) -> bool {
+    segment.ends_with("()")
+}
+
+//the module a scope is in
+fn module_of(scope: &[String]) -> Scope {
+    let depth = scope.iter().rposition(|segment| !is_fn_scope(segment)).map_or(0, |index| index + 1);
+    scope[..depth].to_vec()
+}
+
+//the consts, statics and fns of every scope of a file and the modules each scope glob imports,
+//to find what a bare name refers to
+#[derive(Default)]
+struct ScopeItems {
+    declared: HashMap<Scope, HashSet<String>>,
+    globs: HashMap<Scope, Vec<Scope>>,
+}
+
+impl ScopeItems {
+    fn declares(&self, scope: &[String], name: &str) -> bool {
+        self.declared.get(scope).is_some_and(|names| names.contains(name))
+    }
+
+    //the scope of the item `name` refers to when used in `scope`: the innermost fn body declaring
+    //it, or else the module, where the items it declares come before the ones it glob imports
+    fn resolve(&self, scope: &[String], name: &str) -> Option<Scope> {
+        for depth in (0..=scope.len()).rev() {
+            let current = &scope[..depth];
+            if self.declares(current, name) {
+                return Some(current.to_vec());
+            }
+            let imported = self.globs.get(current).into_iter().flatten().find(|module| self.declares(module, name));
+            if imported.is_some() {
+                return imported.cloned();
+            }
+            if depth == 0 || !is_fn_scope(&current[depth - 1]) {
+                return None;
+            }
+        }
+        None
+    }
 }
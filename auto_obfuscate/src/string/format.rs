@@ -0,0 +1,179 @@
+use syn::{ punctuated::Punctuated, Expr, ExprAssign, ExprLit, Lit, LitStr, Macro, Token };
+
+//format-string macros and the position of their format string argument
+const FORMAT_MACROS: &[(&str, usize)] = &[
+    ("print", 0),
+    ("println", 0),
+    ("eprint", 0),
+    ("eprintln", 0),
+    ("format", 0),
+    ("panic", 0),
+    ("unreachable", 0),
+    ("todo", 0),
+    ("unimplemented", 0),
+    ("format_args", 0),
+    ("write", 1),
+    ("writeln", 1),
+    ("assert", 1),
+    ("debug_assert", 1),
+    ("assert_eq", 2),
+    ("assert_ne", 2),
+    ("debug_assert_eq", 2),
+    ("debug_assert_ne", 2),
+];
+
+//whether a macro is one of the format macros, which only borrow their arguments
+pub fn is_format_macro(mac: &Macro) -> bool {
+    mac.path.segments.last().is_some_and(|segment| FORMAT_MACROS.iter().any(|(name, _)| segment.ident == name))
+}
+
+pub type FormatArgs = Punctuated<Expr, Token![,]>;
+
+//a format macro invocation whose format string is a plain literal
+pub struct FormatMacro {
+    pub args: FormatArgs,
+    pub format_index: usize,
+}
+
+impl FormatMacro {
+    pub fn parse(mac: &Macro) -> Option<Self> {
+        let name = mac.path.segments.last()?.ident.to_string();
+        let format_index = FORMAT_MACROS.iter()
+            .find(|(macro_name, _)| *macro_name == name)
+            .map(|(_, index)| *index)?;
+        let args = mac.parse_body_with(FormatArgs::parse_terminated).ok()?;
+        match args.iter().nth(format_index) {
+            Some(Expr::Lit(ExprLit { lit: Lit::Str(_), .. })) => Some(Self { args, format_index }),
+            _ => None,
+        }
+    }
+
+    pub fn format_string(&self) -> &LitStr {
+        match &self.args[self.format_index] {
+            Expr::Lit(ExprLit { lit: Lit::Str(lit_str), .. }) => lit_str,
+            _ => unreachable!("checked in FormatMacro::parse"),
+        }
+    }
+
+    //plain string literals passed as arguments, positional or named
+    pub fn literal_args_mut(&mut self) -> impl Iterator<Item = &mut Expr> {
+        let format_index = self.format_index;
+        self.args
+            .iter_mut()
+            .skip(format_index + 1)
+            .map(|arg| {
+                match arg {
+                    Expr::Assign(ExprAssign { right, .. }) => &mut **right,
+                    _ => arg,
+                }
+            })
+            .filter(|arg| matches!(arg, Expr::Lit(ExprLit { lit: Lit::Str(_), .. })))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FormatPiece {
+    //literal text with `{{`/`}}` escapes already resolved
+    Text(String),
+    //a placeholder kept verbatim, braces included, e.g. `{}`, `{0:>8}` or `{name}`
+    Placeholder(String),
+}
+
+//split a format string into text and placeholders, None if it is malformed
+pub fn parse_format_string(format: &str) -> Option<Vec<FormatPiece>> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                if !text.is_empty() {
+                    pieces.push(FormatPiece::Text(std::mem::take(&mut text)));
+                }
+                let mut placeholder = String::from('{');
+                loop {
+                    let next = chars.next()?;
+                    placeholder.push(next);
+                    if next == '}' {
+                        break;
+                    }
+                    if next == '{' {
+                        return None;
+                    }
+                }
+                pieces.push(FormatPiece::Placeholder(placeholder));
+            }
+            '}' => {
+                return None;
+            }
+            _ => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(FormatPiece::Text(text));
+    }
+    Some(pieces)
+}
+
+//join pieces back into a format string, the inverse of `parse_format_string`
+pub fn join_format_string(pieces: &[FormatPiece]) -> String {
+    let mut format = String::new();
+    for piece in pieces {
+        match piece {
+            FormatPiece::Text(text) => format.push_str(&text.replace('{', "{{").replace('}', "}}")),
+            FormatPiece::Placeholder(placeholder) => format.push_str(placeholder),
+        }
+    }
+    format
+}
+
+//rewrite the names a placeholder refers to, its argument like `name` in `{name:?}` and the counts
+//of its spec like `width` and `prec` in `{:>width$.prec$}`. Positions and implicit arguments are kept
+pub fn rename_placeholder(placeholder: &str, mut rename: impl FnMut(&str) -> Option<String>) -> String {
+    let inner = &placeholder[1..placeholder.len() - 1];
+    let (argument, spec) = match inner.split_once(':') {
+        Some((argument, spec)) => (argument, Some(spec)),
+        None => (inner, None),
+    };
+    let mut renamed = String::from('{');
+    match rename_if_name(argument, &mut rename) {
+        Some(new_name) => renamed.push_str(&new_name),
+        None => renamed.push_str(argument),
+    }
+    if let Some(spec) = spec {
+        renamed.push(':');
+        //a name is a run of identifier characters followed by `$`, a fill character can't be one
+        //as it is always followed by the alignment
+        let mut rest = spec;
+        while let Some(start) = rest.find(is_ident_char) {
+            renamed.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = rest.find(|c: char| !is_ident_char(c)).unwrap_or(rest.len());
+            let run = &rest[..end];
+            let new_name = if rest[end..].starts_with('$') { rename_if_name(run, &mut rename) } else { None };
+            renamed.push_str(new_name.as_deref().unwrap_or(run));
+            rest = &rest[end..];
+        }
+        renamed.push_str(rest);
+    }
+    renamed.push('}');
+    renamed
+}
+
+fn rename_if_name(name: &str, rename: &mut impl FnMut(&str) -> Option<String>) -> Option<String> {
+    let is_name = name.starts_with(|c: char| c.is_alphabetic() || c == '_') && name.chars().all(is_ident_char);
+    if is_name { rename(name) } else { None }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
@@ -30,11 +30,90 @@ fn test_no_macro() {
     let string_config = StringConfig::default();
     let mut string_obfuscator = StringObfuscator::new(string_config);
     let obfuscated_code = string_obfuscator.obfuscate_strings(code);
-    assert!(!obfuscated_code.contains("encrypt_string"));
+    //format strings are split into encrypted arguments, call arguments decrypt to `&'static str`
+    assert!(obfuscated_code.contains("println ! (\"{__cryptify_0}\" , __cryptify_0 = cryptify :: encrypt_string ! (\"Hello, world!\"))"));
+    assert!(obfuscated_code.contains("Regex :: new (cryptify :: encrypt_static ! (r\"\\b\\w+\\b\"))"));
     let parse_result = syn::parse_file(&obfuscated_code);
     assert!(parse_result.is_ok(), "Modified code is not valid Rust code");
 }
 
+#[test]
+fn test_expression_positions() {
+    let code =
+        r#"
+        struct User { name: &'static str }
+
+        fn greeting(formal: bool) -> &'static str {
+            if formal { "Good day" } else { return "hey"; }
+        }
+
+        fn main() {
+            let typed: &str = "typed";
+            let user = User { name: "ferris" };
+            let kind = match user.name {
+                "ferris" => "crab",
+                _ => "unknown",
+            };
+            let config = std::env::var("HOME").expect("HOME is not set");
+            map.insert("key", greeting(user.name == "admin"));
+        }
+    "#;
+    let string_config = StringConfig::default();
+    let mut string_obfuscator = StringObfuscator::new(string_config);
+    let obfuscated_code = string_obfuscator.obfuscate_strings(code);
+
+    for literal in ["Good day", "hey", "typed", "ferris", "crab", "unknown", "HOME", "HOME is not set", "key", "admin"] {
+        assert!(
+            obfuscated_code.contains(&format!("cryptify :: encrypt_static ! (\"{}\")", literal)),
+            "{} was not encrypted",
+            literal
+        );
+    }
+    //patterns have to stay literals
+    assert!(obfuscated_code.contains("\"ferris\" => cryptify :: encrypt_static ! (\"crab\")"));
+    let parse_result = syn::parse_file(&obfuscated_code);
+    assert!(parse_result.is_ok(), "Modified code is not valid Rust code");
+}
+
+#[test]
+fn test_const_contexts_are_skipped() {
+    let code =
+        r#"
+        /// documented
+        #[cfg(feature = "extra")]
+        fn main() {
+            let data = include_str!("data.txt");
+            let text = concat!("a", "b");
+            let sized = [0u8; { const fn len(s: &str) -> usize { s.len() } len("four") }];
+            let _ = const { "block" };
+        }
+
+        pub const PUBLIC: &str = "public";
+
+        impl Thing {
+            const NAME: &'static str = "thing";
+            const fn name() -> &'static str { "const fn" }
+        }
+    "#;
+    let string_config = StringConfig::default();
+    let mut string_obfuscator = StringObfuscator::new(string_config);
+    let obfuscated_code = string_obfuscator.obfuscate_strings(code);
+
+    for kept in [
+        "# [cfg (feature = \"extra\")]",
+        "include_str ! (\"data.txt\")",
+        "concat ! (\"a\" , \"b\")",
+        "len (\"four\")",
+        "const { \"block\" }",
+        "pub const PUBLIC : & str = \"public\"",
+        "const NAME : & 'static str = \"thing\"",
+        "{ \"const fn\" }",
+    ] {
+        assert!(obfuscated_code.contains(kept), "{} was rewritten", kept);
+    }
+    assert!(!obfuscated_code.contains("encrypt_static"));
+}
+
 #[test]
 fn test_percentage() {
     let code =
@@ -65,9 +144,332 @@ fn test_percentage() {
     let obfuscated_code = string_obfuscator.obfuscate_strings(code);
     assert_ne!(code, obfuscated_code);
     assert!(obfuscated_code.contains("encrypt_string ! (\"h\")"));
-    assert!(obfuscated_code.contains("let i = \"i\""));
+    //16 literals including the format strings, 80% rounds up to 13
+    assert_eq!(obfuscated_code.matches("encrypt_string !").count(), 13);
+    assert_eq!(obfuscated_code.matches("println ! (\"Hello\")").count(), 3);
     println!("{}", obfuscated_code);
 
     let parse_result = syn::parse_file(&obfuscated_code);
     assert!(parse_result.is_ok(), "Modified code is not valid Rust code");
 }
+
+#[test]
+fn test_percentage_counts_eligible_literals_only() {
+    let code =
+        r#"
+    #[doc = "not eligible"]
+    fn main() {
+        let data = include_str!("data.txt");
+        let count = 1;
+        let a = "a";
+        let b = "b";
+        let c = "c";
+        let d = "d";
+    }
+"#;
+
+    let mut string_config = StringConfig::default();
+    string_config.percentage = 50;
+    let mut string_obfuscator = StringObfuscator::new(string_config);
+    //the same obfuscator is reused for several files
+    for _ in 0..2 {
+        let obfuscated_code = string_obfuscator.obfuscate_strings(code);
+        assert_eq!(obfuscated_code.matches("encrypt_string !").count(), 2);
+        assert!(obfuscated_code.contains("let a = cryptify :: encrypt_string ! (\"a\")"));
+        assert!(obfuscated_code.contains("let b = cryptify :: encrypt_string ! (\"b\")"));
+        assert_eq!(
+            string_obfuscator.report(),
+            "encrypted 2 of 4 eligible string literals\n  6:17 \"a\"\n  7:17 \"b\""
+        );
+    }
+}
+
+#[test]
+fn test_selection_strategies() {
+    let code =
+        r#"
+    const KEY: &str = "k3Y-9f!Qz";
+    fn main() {
+        let a = "aaaaaaaaaaaaaaaaaaaa";
+        let b = "short";
+        println!("{}", "the quick brown fox");
+        let c = "c";
+    }
+"#;
+
+    let encrypted = |strategy: SelectionStrategy, seed: Option<u64>| {
+        let mut string_config = StringConfig::default();
+        string_config.percentage = 40;
+        string_config.strategy = strategy;
+        string_config.seed = seed;
+        let mut string_obfuscator = StringObfuscator::new(string_config);
+        let obfuscated_code = string_obfuscator.obfuscate_strings(code);
+        assert!(syn::parse_file(&obfuscated_code).is_ok(), "Modified code is not valid Rust code");
+        string_obfuscator
+            .encrypted_literals()
+            .into_iter()
+            .map(|literal| literal.literal.clone())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(encrypted(SelectionStrategy::FirstN, None), ["\"k3Y-9f!Qz\"", "\"aaaaaaaaaaaaaaaaaaaa\""]);
+    //a repeated character carries no information however long the literal is
+    assert_eq!(encrypted(SelectionStrategy::Priority, None), ["\"k3Y-9f!Qz\"", "\"the quick brown fox\""]);
+
+    let random = encrypted(SelectionStrategy::Random, Some(7));
+    assert_eq!(random.len(), 2);
+    assert_eq!(random, encrypted(SelectionStrategy::Random, Some(7)));
+}
+
+#[test]
+fn test_selection_with_unselected_items() {
+    let code =
+        r#"
+    const A: &str = "a";
+    const B: &str = "b";
+    fn main() {
+        let first = "k3Y-9f!Qz";
+        let second = "Zq!f9-Y3k#x";
+    }
+"#;
+    let mut string_config = StringConfig::default();
+    string_config.percentage = 50;
+    string_config.strategy = SelectionStrategy::Priority;
+    let mut string_obfuscator = StringObfuscator::new(string_config);
+    let obfuscated_code = string_obfuscator.obfuscate_strings(code);
+
+    let reported: Vec<String> = string_obfuscator
+        .encrypted_literals()
+        .into_iter()
+        .map(|literal| literal.literal.clone())
+        .collect();
+    assert_eq!(reported, ["\"k3Y-9f!Qz\"", "\"Zq!f9-Y3k#x\""]);
+    //what the report lists is what got encrypted, and nothing else
+    for literal in reported {
+        assert!(obfuscated_code.contains(&format!("cryptify :: encrypt_string ! ({})", literal)), "{}", obfuscated_code);
+    }
+    assert!(obfuscated_code.contains("const A : & str = \"a\""));
+    assert!(obfuscated_code.contains("const B : & str = \"b\""));
+}
+
+#[test]
+fn test_const_and_static_items() {
+    let code =
+        r#"
+        const GREETING: &str = "Hello";
+        static MAGIC: &'static [u8] = b"\x7fELF";
+        pub const EXPORTED: &str = "exported";
+        const LIMIT: usize = 10;
+
+        fn main() {
+            println!("{}{:?}", GREETING, MAGIC);
+            let len = GREETING.len();
+            check(MAGIC);
+        }
+    "#;
+    let string_config = StringConfig::default();
+    let mut string_obfuscator = StringObfuscator::new(string_config);
+    let obfuscated_code = string_obfuscator.obfuscate_strings(code);
+
+    assert!(obfuscated_code.contains("static GREETING : cryptify :: Lazy < String >"));
+    assert!(obfuscated_code.contains("cryptify :: encrypt_string ! (\"Hello\")"));
+    assert!(obfuscated_code.contains("static MAGIC : cryptify :: Lazy < Vec < u8 > >"));
+    assert!(obfuscated_code.contains("cryptify :: encrypt_bytes ! (b\"\\x7fELF\")"));
+    //uses deref to the original reference type
+    assert!(obfuscated_code.contains("(& * GREETING) . len ()"));
+    assert!(obfuscated_code.contains("check ((& * MAGIC))"));
+    assert!(obfuscated_code.contains("println ! (\"{}{:?}\" , (& * GREETING) , (& * MAGIC))"));
+    //public items may be used from other files and other consts are not strings
+    assert!(obfuscated_code.contains("pub const EXPORTED : & str = \"exported\""));
+    assert!(obfuscated_code.contains("const LIMIT : usize = 10"));
+
+    let parse_result = syn::parse_file(&obfuscated_code);
+    assert!(parse_result.is_ok(), "Modified code is not valid Rust code");
+}
+
+#[test]
+fn test_lazy_items_are_scoped() {
+    let code =
+        r#"
+        const GREETING: &str = "Hello";
+        mod secret {
+            const NAME: &str = "secret";
+            pub fn get() -> &'static str {
+                NAME
+            }
+        }
+        mod other {
+            const NAME: usize = 3;
+            pub fn get() -> usize {
+                fn nested() -> u8 {
+                    const GREETING: u8 = 1;
+                    GREETING
+                }
+                NAME + nested() as usize
+            }
+        }
+        mod tests {
+            use super::*;
+            fn check() -> usize {
+                GREETING.len()
+            }
+        }
+        const SHADOWED: &str = "shadowed";
+        fn main() {
+            let SHADOWED = 5;
+            println!("{}", SHADOWED);
+        }
+    "#;
+    let string_config = StringConfig::default();
+    let mut string_obfuscator = StringObfuscator::new(string_config);
+    let obfuscated_code = string_obfuscator.obfuscate_strings(code);
+
+    assert!(obfuscated_code.contains("static GREETING : cryptify :: Lazy < String >"));
+    assert!(obfuscated_code.contains("static NAME : cryptify :: Lazy < String >"));
+    assert!(obfuscated_code.contains("pub fn get () -> & 'static str { (& * NAME) }"));
+    //same names declared in other scopes are left alone
+    assert!(obfuscated_code.contains("NAME + nested () as usize"), "{}", obfuscated_code);
+    assert!(obfuscated_code.contains("const GREETING : u8 = 1 ; GREETING }"), "{}", obfuscated_code);
+    //glob imports see the lazy item
+    assert!(obfuscated_code.contains("(& * GREETING) . len ()"));
+    //an item a local shadows stays a constant
+    assert!(obfuscated_code.contains("const SHADOWED : & str = \"shadowed\""));
+    assert!(obfuscated_code.contains("println ! (\"{}\" , SHADOWED)"), "{}", obfuscated_code);
+
+    let parse_result = syn::parse_file(&obfuscated_code);
+    assert!(parse_result.is_ok(), "Modified code is not valid Rust code");
+}
+
+#[test]
+fn test_const_items_in_const_contexts_are_skipped() {
+    let code =
+        r#"
+        const PATTERN: &str = "quit";
+        const BASE: &str = "base";
+        const DERIVED: &str = BASE;
+
+        fn main() {
+            match input() {
+                PATTERN => {}
+                _ => {}
+            }
+        }
+    "#;
+    let string_config = StringConfig::default();
+    let mut string_obfuscator = StringObfuscator::new(string_config);
+    let obfuscated_code = string_obfuscator.obfuscate_strings(code);
+
+    assert!(obfuscated_code.contains("const PATTERN : & str = \"quit\""));
+    assert!(obfuscated_code.contains("const BASE : & str = \"base\""));
+    assert!(!obfuscated_code.contains("cryptify :: Lazy"));
+}
+
+#[test]
+fn test_format_macros() {
+    let code =
+        r#"
+        fn main() {
+            let name = "crab";
+            println!("Hello {}, you are {age:>3} years old {{not a placeholder}}", name, age = 7);
+            eprintln!("{}", format!("{name} says {0:?} and {}", "hi"));
+            panic!("fatal: {}", code);
+            write!(f, "written {:.*}", 2, 1.5).unwrap();
+        }
+    "#;
+    let string_config = StringConfig::default();
+    let mut string_obfuscator = StringObfuscator::new(string_config);
+    let obfuscated_code = string_obfuscator.obfuscate_strings(code);
+
+    //text is moved into encrypted named arguments and placeholders stay as they were
+    assert!(obfuscated_code.contains(
+        "println ! (\"{__cryptify_0}{}{__cryptify_1}{age:>3}{__cryptify_2}\" , name , age = 7 , __cryptify_0 = cryptify :: encrypt_string ! (\"Hello \") , __cryptify_1 = cryptify :: encrypt_string ! (\", you are \") , __cryptify_2 = cryptify :: encrypt_string ! (\" years old {not a placeholder}\"))"
+    ));
+    //inline captures are untouched and plain literal arguments are encrypted
+    //nested format macros are handled, the outer one has no text to encrypt
+    assert!(obfuscated_code.contains(
+        "eprintln ! (\"{}\" , format ! (\"{name}{__cryptify_0}{0:?}{__cryptify_1}{}\" , cryptify :: encrypt_string ! (\"hi\") ,"
+    ));
+    assert!(obfuscated_code.contains("panic ! (\"{__cryptify_0}{}\" , code , __cryptify_0 = cryptify :: encrypt_string ! (\"fatal: \"))"));
+    assert!(obfuscated_code.contains("write ! (f , \"{__cryptify_0}{:.*}\" , 2 , 1.5 , __cryptify_0 = cryptify :: encrypt_string ! (\"written \"))"));
+
+    let parse_result = syn::parse_file(&obfuscated_code);
+    assert!(parse_result.is_ok(), "Modified code is not valid Rust code");
+}
+
+#[test]
+fn test_parse_format_string() {
+    use super::format::{ parse_format_string, FormatPiece::* };
+
+    assert_eq!(
+        parse_format_string("a {} b {x:?} {{c}}"),
+        Some(
+            vec![
+                Text("a ".to_string()),
+                Placeholder("{}".to_string()),
+                Text(" b ".to_string()),
+                Placeholder("{x:?}".to_string()),
+                Text(" {c}".to_string())
+            ]
+        )
+    );
+    assert_eq!(parse_format_string(""), Some(vec![]));
+    assert_eq!(parse_format_string("unclosed {"), None);
+    assert_eq!(parse_format_string("stray }"), None);
+}
+
+#[test]
+fn test_rename_placeholder() {
+    use super::format::{ join_format_string, parse_format_string, rename_placeholder };
+
+    let rename = |name: &str| (name != "kept").then(|| name.to_uppercase());
+    assert_eq!(rename_placeholder("{}", rename), "{}");
+    assert_eq!(rename_placeholder("{0:?}", rename), "{0:?}");
+    assert_eq!(rename_placeholder("{name}", rename), "{NAME}");
+    assert_eq!(rename_placeholder("{kept:#?}", rename), "{kept:#?}");
+    assert_eq!(rename_placeholder("{x:w>width$.prec$e}", rename), "{X:w>WIDTH$.PREC$e}");
+    assert_eq!(rename_placeholder("{:1$.*}", rename), "{:1$.*}");
+
+    let pieces = parse_format_string("{{a}} {b} }}").unwrap();
+    assert_eq!(join_format_string(&pieces), "{{a}} {b} }}");
+}
+
+#[test]
+fn test_disable_macro() {
+    let code =
+        r#"
+        static BANNER: &[u8] = b"banner";
+        fn main() {
+            let b = "Hello, world!";
+            println!("Hello, {}!", "world");
+            let word_re = Regex::new(r"\b\w+\b").unwrap();
+        }
+    "#;
+    let mut string_config = StringConfig::default();
+    string_config.use_macro = false;
+    let mut string_obfuscator = StringObfuscator::new(string_config);
+    let obfuscated_code = string_obfuscator.obfuscate_strings(code);
+    //the macros are written out, with the cipher they default to
+    assert!(!obfuscated_code.contains("encrypt_"));
+    assert_eq!(obfuscated_code.matches("cryptify :: decrypt_string :: < cryptify :: cipher :: Xor >").count(), 5);
+    assert!(obfuscated_code.contains("cryptify :: Lazy :: < Vec < u8 > > :: new (| | cryptify :: decrypt_bytes :: < cryptify :: cipher :: Xor >"));
+    assert!(obfuscated_code.contains("static DECRYPTED"));
+    assert!(!obfuscated_code.contains("Hello"));
+
+    //the local decrypts back to the literal it replaced
+    let file = syn::parse_file(&obfuscated_code).expect("Modified code is not valid Rust code");
+    let Item::Fn(main) = &file.items[1] else {
+        panic!("expected fn main");
+    };
+    let syn::Stmt::Local(Local { init: Some(init), .. }) = &main.block.stmts[0] else {
+        panic!("expected let statement");
+    };
+    let Expr::Call(call) = &*init.expr else {
+        panic!("expected decrypt_string call");
+    };
+    let [mut data, key, nonce] = [0, 1, 2].map(|index| match &call.args[index] {
+        Expr::Lit(ExprLit { lit: Lit::ByteStr(lit), .. }) => lit.value(),
+        _ => panic!("expected byte string argument"),
+    });
+    CipherKind::Xor.apply_keystream(&key, &nonce, &mut data);
+    assert_eq!(data, b"Hello, world!");
+}
@@ -1,16 +1,169 @@
 //re-export proc macro crate
 pub use labyrinth_macros::*;
+//re-export cipher backends so expansions can name them
+pub use labyrinth_core::cipher;
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::OnceLock;
+use zeroize::Zeroize;
 
 /// A helper decryption function meant to decrypt encrypted strings at runtime
 ///
 /// # Parameters
-/// - `input`: The encrypted string literal
-///
-pub fn decrypt_string(encrypted: &str) -> String {
-    let key = std::env::var("CRYPTIFY_KEY").unwrap_or_else(|_| "xnasff3wcedj".to_string());
-    encrypted
-        .chars()
-        .zip(key.chars().cycle())
-        .map(|(encrypted_char, key_char)| ((encrypted_char as u8) ^ (key_char as u8)) as char)
-        .collect()
+/// - `encrypted`: The encrypted UTF-8 bytes emitted by `encrypt_string!`
+/// - `key`: The per-literal key derived at compile time
+/// - `nonce`: The per-literal nonce, empty for ciphers without one
+///
+pub fn decrypt_string<C: cipher::Cipher>(encrypted: &[u8], key: &[u8], nonce: &[u8]) -> String {
+    let decrypted = cipher::decrypt::<C>(encrypted, key, nonce);
+    String::from_utf8_lossy(&decrypted).into_owned()
+}
+
+/// A helper decryption function meant to decrypt byte strings emitted by `encrypt_bytes!` at runtime
+///
+/// # Parameters
+/// - `encrypted`: The encrypted bytes
+/// - `key`: The per-literal key derived at compile time
+/// - `nonce`: The per-literal nonce, empty for ciphers without one
+///
+pub fn decrypt_bytes<C: cipher::Cipher>(encrypted: &[u8], key: &[u8], nonce: &[u8]) -> Vec<u8> {
+    cipher::decrypt::<C>(encrypted, key, nonce)
+}
+
+/// Decrypts a string emitted by `encrypt_string_guarded!` into a `SecretStr` that is wiped on drop
+///
+/// # Parameters
+/// - `encrypted`: The encrypted UTF-8 bytes
+/// - `key`: The per-literal key derived at compile time
+/// - `nonce`: The per-literal nonce, empty for ciphers without one
+///
+pub fn decrypt_secret<C: cipher::Cipher>(encrypted: &[u8], key: &[u8], nonce: &[u8]) -> SecretStr {
+    let decrypted = cipher::decrypt::<C>(encrypted, key, nonce);
+    let inner = match String::from_utf8(decrypted) {
+        Ok(inner) => inner,
+        Err(err) => {
+            let mut bytes = err.into_bytes();
+            let inner = String::from_utf8_lossy(&bytes).into_owned();
+            bytes.zeroize();
+            inner
+        }
+    };
+    SecretStr { inner }
+}
+
+/// Decrypts a string emitted by `with_decrypted!` in place and hands it to `f`, wiping the buffer afterwards
+///
+/// # Parameters
+/// - `buffer`: Stack copy of the encrypted UTF-8 bytes, decrypted in place
+/// - `key`: The per-literal key derived at compile time
+/// - `nonce`: The per-literal nonce, empty for ciphers without one
+/// - `f`: Closure that gets the decrypted string for the duration of the call
+///
+pub fn with_decrypted<C: cipher::Cipher, R>(
+    buffer: &mut [u8],
+    key: &[u8],
+    nonce: &[u8],
+    f: impl FnOnce(&str) -> R
+) -> R {
+    //wipes the buffer even if the closure panics
+    struct WipeOnDrop<'a>(&'a mut [u8]);
+    impl Drop for WipeOnDrop<'_> {
+        fn drop(&mut self) {
+            self.0.zeroize();
+        }
+    }
+
+    let guard = WipeOnDrop(buffer);
+    C::apply_keystream(key, nonce, guard.0);
+    match std::str::from_utf8(guard.0) {
+        Ok(decrypted) => f(decrypted),
+        Err(_) => f(&String::from_utf8_lossy(guard.0)),
+    }
+}
+
+/// A value decrypted on first access and kept for the rest of the program, usable in `static` items
+///
+/// Derefs to the target of the decrypted value, so `&*LAZY` is a `&'static str` for `Lazy<String>`
+/// and a `&'static [u8]` for `Lazy<Vec<u8>>`.
+///
+/// # Example
+/// ```ignore
+/// static GREETING: cryptify::Lazy<String> = cryptify::Lazy::new(|| cryptify::encrypt_string!("hello"));
+/// let greeting: &'static str = &*GREETING;
+/// ```
+pub struct Lazy<T: Deref + 'static> {
+    cell: OnceLock<T>,
+    init: fn() -> T,
+}
+
+impl<T: Deref + 'static> Lazy<T> {
+    pub const fn new(init: fn() -> T) -> Self {
+        Self { cell: OnceLock::new(), init }
+    }
+}
+
+impl<T: Deref + 'static> Deref for Lazy<T> {
+    type Target = T::Target;
+
+    fn deref(&self) -> &T::Target {
+        self.cell.get_or_init(self.init)
+    }
+}
+
+impl<T: Deref + 'static> fmt::Display for Lazy<T> where T::Target: fmt::Display {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: Deref + 'static> fmt::Debug for Lazy<T> where T::Target: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// A decrypted string that overwrites its heap buffer with zeroes when dropped
+///
+/// Derefs to `&str`. `Debug` output is redacted, `Display` prints the plaintext.
+pub struct SecretStr {
+    inner: String,
+}
+
+impl SecretStr {
+    pub fn as_str(&self) -> &str {
+        &self.inner
+    }
+}
+
+impl Deref for SecretStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.inner
+    }
+}
+
+impl AsRef<str> for SecretStr {
+    fn as_ref(&self) -> &str {
+        &self.inner
+    }
+}
+
+impl fmt::Display for SecretStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.inner)
+    }
+}
+
+impl fmt::Debug for SecretStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretStr(***)")
+    }
+}
+
+impl Drop for SecretStr {
+    fn drop(&mut self) {
+        self.inner.zeroize();
+    }
 }
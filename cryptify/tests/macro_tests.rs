@@ -1,13 +1,176 @@
 //integration tests testing whole crate
+use proptest::prelude::*;
+
 #[test]
 fn test_encrypt_macro() {
     let decrypted: String = cryptify::encrypt_string!("Hello World");
     assert_eq!("Hello World", decrypted);
 }
 
+#[test]
+fn test_encrypt_macro_unicode() {
+    let decrypted: String = cryptify::encrypt_string!("héllo wörld, 日本語 🦀");
+    assert_eq!("héllo wörld, 日本語 🦀", decrypted);
+
+    let escaped: String = cryptify::encrypt_string!("tab\tnewline\n\u{1F512} nul\0");
+    assert_eq!("tab\tnewline\n\u{1F512} nul\0", escaped);
+
+    let raw: String = cryptify::encrypt_string!(r#"raw "quoted" ñ"#);
+    assert_eq!(r#"raw "quoted" ñ"#, raw);
+}
+
+#[test]
+fn test_encrypt_macro_ignores_runtime_key() {
+    //the key is baked in at compile time, so the runtime environment must not affect decryption
+    std::env::set_var("CRYPTIFY_KEY", "some-other-runtime-key");
+    let decrypted: String = cryptify::encrypt_string!("Hello World");
+    std::env::remove_var("CRYPTIFY_KEY");
+    assert_eq!("Hello World", decrypted);
+}
+
+#[test]
+fn test_encrypt_macro_same_literal_twice() {
+    let first: String = cryptify::encrypt_string!("repeated literal");
+    let second: String = cryptify::encrypt_string!("repeated literal");
+    assert_eq!("repeated literal", first);
+    assert_eq!(first, second);
+}
+
+#[test]
+fn test_encrypt_macro_cipher_argument() {
+    let xor: String = cryptify::encrypt_string!(cipher = "xor", "héllo xor");
+    let rc4: String = cryptify::encrypt_string!(cipher = "rc4", "héllo rc4");
+    let aes: String = cryptify::encrypt_string!(cipher = "aes-ctr", "héllo aes 🦀");
+    let chacha: String = cryptify::encrypt_string!(cipher = "chacha20", "héllo chacha 🦀");
+    assert_eq!("héllo xor", xor);
+    assert_eq!("héllo rc4", rc4);
+    assert_eq!("héllo aes 🦀", aes);
+    assert_eq!("héllo chacha 🦀", chacha);
+}
+
+#[test]
+fn test_encrypt_guarded_macro() {
+    let secret: cryptify::SecretStr = cryptify::encrypt_string_guarded!("guarded 🔒 secret");
+    assert_eq!(&*secret, "guarded 🔒 secret");
+    assert_eq!(secret.len(), "guarded 🔒 secret".len());
+    assert_eq!(format!("{}", secret), "guarded 🔒 secret");
+    assert_eq!(format!("{:?}", secret), "SecretStr(***)");
+
+    let chacha = cryptify::encrypt_string_guarded!(cipher = "chacha20", "guarded chacha");
+    assert_eq!(chacha.as_str(), "guarded chacha");
+}
+
+#[test]
+fn test_with_decrypted_macro() {
+    let len = cryptify::with_decrypted!("scoped 日本語", |decrypted| {
+        assert_eq!(decrypted, "scoped 日本語");
+        decrypted.len()
+    });
+    assert_eq!(len, "scoped 日本語".len());
+
+    let upper = cryptify::with_decrypted!(cipher = "aes-ctr", "scoped aes", |s: &str| s.to_uppercase());
+    assert_eq!(upper, "SCOPED AES");
+}
+
+#[test]
+fn test_with_decrypted_wipes_buffer() {
+    use cryptify::cipher::*;
+    let key = derive_key(b"xnasff3wcedj", 1, Rc4::KEY_LEN);
+    let mut buffer = b"wipe me".to_vec();
+    Rc4::apply_keystream(&key, &[], &mut buffer);
+
+    let seen = cryptify::with_decrypted::<Rc4, _>(&mut buffer, &key, &[], |s| s.to_string());
+    assert_eq!(seen, "wipe me");
+    assert!(buffer.iter().all(|byte| *byte == 0));
+}
+
+#[test]
+fn test_with_decrypted_wipes_buffer_on_panic() {
+    use cryptify::cipher::*;
+    let key = derive_key(b"xnasff3wcedj", 2, Xor::KEY_LEN);
+    let mut buffer = b"wipe me too".to_vec();
+    Xor::apply_keystream(&key, &[], &mut buffer);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        cryptify::with_decrypted::<Xor, ()>(&mut buffer, &key, &[], |_| panic!("closure failed"))
+    }));
+    assert!(result.is_err());
+    assert!(buffer.iter().all(|byte| *byte == 0));
+}
+
+#[test]
+fn test_encrypt_bytes_macro() {
+    let decrypted: Vec<u8> = cryptify::encrypt_bytes!(b"\x00\xffMAGIC\r\n");
+    assert_eq!(decrypted, b"\x00\xffMAGIC\r\n");
+
+    let chacha = cryptify::encrypt_bytes!(cipher = "chacha20", b"protocol");
+    assert_eq!(chacha, b"protocol");
+}
+
+fn static_greeting() -> &'static str {
+    cryptify::encrypt_static!("static héllo")
+}
+
+#[test]
+fn test_encrypt_static_macro() {
+    let first = static_greeting();
+    let second = static_greeting();
+    assert_eq!(first, "static héllo");
+    //decrypted once and shared between evaluations
+    assert_eq!(first.as_ptr(), second.as_ptr());
+
+    let magic: &'static [u8] = cryptify::encrypt_static!(cipher = "rc4", b"\x7fELF");
+    assert_eq!(magic, b"\x7fELF");
+}
+
+static LAZY_NAME: cryptify::Lazy<String> = cryptify::Lazy::new(|| cryptify::encrypt_string!("lazy name"));
+static LAZY_MAGIC: cryptify::Lazy<Vec<u8>> = cryptify::Lazy::new(|| cryptify::encrypt_bytes!(b"MZ"));
+
+#[test]
+fn test_lazy_static_items() {
+    let name: &'static str = &LAZY_NAME;
+    assert_eq!(name, "lazy name");
+    assert_eq!(LAZY_NAME.len(), 9);
+    assert_eq!(&*LAZY_MAGIC, b"MZ");
+    assert_eq!(format!("{} {:?}", LAZY_NAME, LAZY_MAGIC), "lazy name [77, 90]");
+}
+
+#[test]
+fn test_encrypt_int_macro() {
+    let inferred: u64 = cryptify::encrypt_int!(0xDEAD_BEEF);
+    assert_eq!(inferred, 0xDEAD_BEEF);
+
+    let small: i8 = cryptify::encrypt_int!(127);
+    assert_eq!(small, 127);
+
+    let negated: i32 = -cryptify::encrypt_int!(42);
+    assert_eq!(negated, -42);
+
+    assert_eq!(cryptify::encrypt_int!(255u8), 255u8);
+    assert_eq!(cryptify::encrypt_int!(0usize), 0usize);
+    assert_eq!(cryptify::encrypt_int!(170141183460469231731687303715884105727i128), i128::MAX);
+    assert_eq!(cryptify::encrypt_int!(340282366920938463463374607431768211455u128), u128::MAX);
+
+    //unconstrained literals fall back to i32 like plain ones
+    let fallback = cryptify::encrypt_int!(7);
+    assert_eq!(fallback, 7i32);
+}
+
 #[test]
 fn test_flow_macro() {
     //manually test for now with cargo expand
     cryptify::flow_stmt!();
     assert_eq!(1, 1);
 }
+
+proptest! {
+    #[test]
+    fn prop_decrypt_string_round_trips_unicode(original in any::<String>(), seed in any::<u64>()) {
+        use cryptify::cipher::*;
+        let key = derive_key(b"xnasff3wcedj", seed, ChaCha20::KEY_LEN);
+        let nonce = derive_key(b"nonce", seed, ChaCha20::NONCE_LEN);
+        let mut encrypted = original.clone().into_bytes();
+        ChaCha20::apply_keystream(&key, &nonce, &mut encrypted);
+        prop_assert_eq!(cryptify::decrypt_string::<ChaCha20>(&encrypted, &key, &nonce), original);
+    }
+}
@@ -0,0 +1,193 @@
+//! Stream ciphers used by `encrypt_string!` at compile time and `cryptify::decrypt_string` at runtime.
+//!
+//! Every backend is a keystream cipher, so encryption and decryption are the same operation.
+use aes::cipher::{ KeyIvInit, StreamCipher };
+use crate::seed::{ fnv1a, splitmix64 };
+
+#[cfg(test)]
+mod cipher_tests;
+
+type Aes128CtrImpl = ctr::Ctr128BE<aes::Aes128>;
+
+/// A cipher backend selectable with `encrypt_string!(cipher = "...", "text")`.
+pub trait Cipher {
+    /// Name accepted by the `cipher` macro argument
+    const NAME: &'static str;
+    /// Length in bytes of the per-literal key
+    const KEY_LEN: usize;
+    /// Length in bytes of the per-literal nonce, zero if the cipher has none
+    const NONCE_LEN: usize;
+
+    /// XORs the keystream for `key` and `nonce` into `data`. Applying it twice restores the input.
+    fn apply_keystream(key: &[u8], nonce: &[u8], data: &mut [u8]);
+}
+
+/// Repeating-free XOR against a keystream expanded from a 64 bit key. Fastest, weakest.
+pub struct Xor;
+
+/// RC4 stream cipher, dropping the first 256 bytes of keystream.
+pub struct Rc4;
+
+/// AES-128 in counter mode.
+pub struct Aes128Ctr;
+
+/// ChaCha20 with a 96 bit nonce.
+pub struct ChaCha20;
+
+impl Cipher for Xor {
+    const NAME: &'static str = "xor";
+    const KEY_LEN: usize = 8;
+    const NONCE_LEN: usize = 0;
+
+    fn apply_keystream(key: &[u8], _nonce: &[u8], data: &mut [u8]) {
+        let seed = u64::from_le_bytes(key.try_into().expect("xor key must be 8 bytes"));
+        let mut state = seed;
+        let keystream = std::iter::repeat_with(move || splitmix64(&mut state).to_le_bytes()).flatten();
+        for (byte, key_byte) in data.iter_mut().zip(keystream) {
+            *byte ^= key_byte;
+        }
+    }
+}
+
+impl Cipher for Rc4 {
+    const NAME: &'static str = "rc4";
+    const KEY_LEN: usize = 16;
+    const NONCE_LEN: usize = 0;
+
+    fn apply_keystream(key: &[u8], _nonce: &[u8], data: &mut [u8]) {
+        assert!(!key.is_empty(), "rc4 key must not be empty");
+        let mut state: [u8; 256] = std::array::from_fn(|i| i as u8);
+        let mut j: u8 = 0;
+        for i in 0..256 {
+            j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+            state.swap(i, j as usize);
+        }
+
+        let (mut i, mut j) = (0u8, 0u8);
+        let mut next_byte = || {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(state[i as usize]);
+            state.swap(i as usize, j as usize);
+            state[state[i as usize].wrapping_add(state[j as usize]) as usize]
+        };
+        //discard the biased start of the keystream (RC4-drop[256])
+        for _ in 0..256 {
+            next_byte();
+        }
+        for byte in data.iter_mut() {
+            *byte ^= next_byte();
+        }
+    }
+}
+
+impl Cipher for Aes128Ctr {
+    const NAME: &'static str = "aes-ctr";
+    const KEY_LEN: usize = 16;
+    const NONCE_LEN: usize = 16;
+
+    fn apply_keystream(key: &[u8], nonce: &[u8], data: &mut [u8]) {
+        let mut cipher = Aes128CtrImpl::new_from_slices(key, nonce).expect(
+            "aes-ctr needs a 16 byte key and nonce"
+        );
+        cipher.apply_keystream(data);
+    }
+}
+
+impl Cipher for ChaCha20 {
+    const NAME: &'static str = "chacha20";
+    const KEY_LEN: usize = 32;
+    const NONCE_LEN: usize = 12;
+
+    fn apply_keystream(key: &[u8], nonce: &[u8], data: &mut [u8]) {
+        let mut cipher = chacha20::ChaCha20::new_from_slices(key, nonce).expect(
+            "chacha20 needs a 32 byte key and 12 byte nonce"
+        );
+        cipher.apply_keystream(data);
+    }
+}
+
+/// Runtime handle on one of the built-in ciphers, used where the backend is picked by name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CipherKind {
+    Xor,
+    Rc4,
+    Aes128Ctr,
+    ChaCha20,
+}
+
+impl CipherKind {
+    pub const ALL: [CipherKind; 4] = [
+        CipherKind::Xor,
+        CipherKind::Rc4,
+        CipherKind::Aes128Ctr,
+        CipherKind::ChaCha20,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CipherKind::Xor => Xor::NAME,
+            CipherKind::Rc4 => Rc4::NAME,
+            CipherKind::Aes128Ctr => Aes128Ctr::NAME,
+            CipherKind::ChaCha20 => ChaCha20::NAME,
+        }
+    }
+
+    //name of the implementing type, used when emitting `cryptify::cipher::<type>` paths
+    pub fn type_name(self) -> &'static str {
+        match self {
+            CipherKind::Xor => "Xor",
+            CipherKind::Rc4 => "Rc4",
+            CipherKind::Aes128Ctr => "Aes128Ctr",
+            CipherKind::ChaCha20 => "ChaCha20",
+        }
+    }
+
+    pub fn key_len(self) -> usize {
+        match self {
+            CipherKind::Xor => Xor::KEY_LEN,
+            CipherKind::Rc4 => Rc4::KEY_LEN,
+            CipherKind::Aes128Ctr => Aes128Ctr::KEY_LEN,
+            CipherKind::ChaCha20 => ChaCha20::KEY_LEN,
+        }
+    }
+
+    pub fn nonce_len(self) -> usize {
+        match self {
+            CipherKind::Xor => Xor::NONCE_LEN,
+            CipherKind::Rc4 => Rc4::NONCE_LEN,
+            CipherKind::Aes128Ctr => Aes128Ctr::NONCE_LEN,
+            CipherKind::ChaCha20 => ChaCha20::NONCE_LEN,
+        }
+    }
+
+    pub fn apply_keystream(self, key: &[u8], nonce: &[u8], data: &mut [u8]) {
+        match self {
+            CipherKind::Xor => Xor::apply_keystream(key, nonce, data),
+            CipherKind::Rc4 => Rc4::apply_keystream(key, nonce, data),
+            CipherKind::Aes128Ctr => Aes128Ctr::apply_keystream(key, nonce, data),
+            CipherKind::ChaCha20 => ChaCha20::apply_keystream(key, nonce, data),
+        }
+    }
+}
+
+/// Decrypts `encrypted` with cipher `C`, returning the plaintext bytes.
+pub fn decrypt<C: Cipher>(encrypted: &[u8], key: &[u8], nonce: &[u8]) -> Vec<u8> {
+    let mut decrypted = encrypted.to_vec();
+    C::apply_keystream(key, nonce, &mut decrypted);
+    decrypted
+}
+
+/// Derives `len` bytes of per-literal key material from the build-wide secret and a call site seed.
+///
+/// The secret is hashed first so it never ends up in the binary itself.
+pub fn derive_key(secret: &[u8], seed: u64, len: usize) -> Vec<u8> {
+    let mut state = fnv1a(secret) ^ seed;
+    std::iter::repeat_with(|| splitmix64(&mut state).to_le_bytes())
+        .flatten()
+        .take(len)
+        .collect()
+}
@@ -0,0 +1,69 @@
+#[cfg(test)]
+use super::*;
+use proptest::prelude::*;
+
+fn hex(input: &str) -> Vec<u8> {
+    (0..input.len()).step_by(2).map(|i| u8::from_str_radix(&input[i..i + 2], 16).unwrap()).collect()
+}
+
+#[test]
+fn test_aes_ctr_known_answer() {
+    //NIST SP 800-38A F.5.1, first block
+    let key = hex("2b7e151628aed2a6abf7158809cf4f3c");
+    let nonce = hex("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+    let mut data = hex("6bc1bee22e409f96e93d7e117393172a");
+    Aes128Ctr::apply_keystream(&key, &nonce, &mut data);
+    assert_eq!(data, hex("874d6191b620e3261bef6864990db6ce"));
+}
+
+#[test]
+fn test_chacha20_known_answer() {
+    //RFC 8439 section 2.4.2, which starts at block counter 1
+    let key: Vec<u8> = (0..32).collect();
+    let nonce = hex("000000000000004a00000000");
+    let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip";
+    let mut data = vec![0u8; 64];
+    data.extend_from_slice(plaintext);
+    ChaCha20::apply_keystream(&key, &nonce, &mut data);
+    assert_eq!(data[64..80], hex("6e2e359a2568f98041ba0728dd0d6981")[..]);
+}
+
+#[test]
+fn test_cipher_names() {
+    for kind in CipherKind::ALL {
+        assert_eq!(CipherKind::from_name(kind.name()), Some(kind));
+    }
+    assert_eq!(CipherKind::from_name("chacha20"), Some(CipherKind::ChaCha20));
+    assert_eq!(CipherKind::from_name("des"), None);
+}
+
+#[test]
+fn test_derive_key_depends_on_secret_and_seed() {
+    let key = derive_key(b"xnasff3wcedj", 1, 32);
+    assert_eq!(key.len(), 32);
+    assert_eq!(key, derive_key(b"xnasff3wcedj", 1, 32));
+    assert_ne!(key, derive_key(b"xnasff3wcedj", 2, 32));
+    assert_ne!(key, derive_key(b"testkey", 1, 32));
+}
+
+proptest! {
+    #[test]
+    fn prop_every_cipher_round_trips_unicode(original in any::<String>(), seed in any::<u64>()) {
+        for kind in CipherKind::ALL {
+            let key = derive_key(b"xnasff3wcedj", seed, kind.key_len());
+            let nonce = derive_key(b"nonce", seed, kind.nonce_len());
+            let mut encrypted = original.clone().into_bytes();
+            kind.apply_keystream(&key, &nonce, &mut encrypted);
+            if original.len() >= 16 {
+                prop_assert_ne!(&encrypted[..], original.as_bytes());
+            }
+            let decrypted = match kind {
+                CipherKind::Xor => decrypt::<Xor>(&encrypted, &key, &nonce),
+                CipherKind::Rc4 => decrypt::<Rc4>(&encrypted, &key, &nonce),
+                CipherKind::Aes128Ctr => decrypt::<Aes128Ctr>(&encrypted, &key, &nonce),
+                CipherKind::ChaCha20 => decrypt::<ChaCha20>(&encrypted, &key, &nonce),
+            };
+            prop_assert_eq!(String::from_utf8(decrypted).unwrap(), original.clone());
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! What the string and integer macros of `cryptify` expand to, generated in one place for the procedural
+//! macros and for `rust-obfuscator`, which writes the same code out itself when run with `--disable_macro`.
+//!
+//! Expansions call into the runtime side of `cryptify` by their full path, so they only compile in a crate
+//! depending on it.
+use proc_macro2::{ Ident, Literal, Span, TokenStream };
+use quote::quote;
+use rand::Rng;
+use std::env;
+use crate::cipher::{ derive_key, CipherKind };
+
+#[cfg(test)]
+mod codegen_tests;
+
+/// The secret per-literal keys are derived from when `CRYPTIFY_KEY` isn't set.
+pub const DEFAULT_SECRET: &str = "xnasff3wcedj";
+
+/// The build-wide secret, `CRYPTIFY_KEY` or the default one.
+pub fn secret() -> String {
+    env::var("CRYPTIFY_KEY").unwrap_or_else(|_| DEFAULT_SECRET.to_string())
+}
+
+/// A literal encrypted under its own key and nonce, ready to be passed to a cryptify runtime function.
+pub struct EncryptedLiteral {
+    pub cipher_type: Ident,
+    pub encrypted: Literal,
+    pub key: Literal,
+    pub nonce: Literal,
+}
+
+impl EncryptedLiteral {
+    /// Encrypts `plaintext` with a fresh key derived from `secret` and a fresh nonce. The generated tokens
+    /// point to `span`, the literal they replace.
+    pub fn new<R: Rng + ?Sized>(plaintext: &[u8], span: Span, cipher: CipherKind, secret: &[u8], rng: &mut R) -> Self {
+        let key = derive_key(secret, rng.gen(), cipher.key_len());
+        let nonce: Vec<u8> = (0..cipher.nonce_len()).map(|_| rng.gen()).collect();
+
+        let mut encrypted_bytes = plaintext.to_vec();
+        cipher.apply_keystream(&key, &nonce, &mut encrypted_bytes);
+
+        let byte_string = |bytes: &[u8]| {
+            let mut literal = Literal::byte_string(bytes);
+            literal.set_span(span);
+            literal
+        };
+        Self {
+            cipher_type: Ident::new(cipher.type_name(), span),
+            encrypted: byte_string(&encrypted_bytes),
+            key: byte_string(&key),
+            nonce: byte_string(&nonce),
+        }
+    }
+
+    /// `encrypt_string!`, an expression of type `String`.
+    pub fn decrypt_string(&self) -> TokenStream {
+        let Self { cipher_type, encrypted, key, nonce } = self;
+        quote! {
+            cryptify::decrypt_string::<cryptify::cipher::#cipher_type>(#encrypted, #key, #nonce)
+        }
+    }
+
+    /// `encrypt_string_guarded!`, an expression of type `cryptify::SecretStr`.
+    pub fn decrypt_secret(&self) -> TokenStream {
+        let Self { cipher_type, encrypted, key, nonce } = self;
+        quote! {
+            cryptify::decrypt_secret::<cryptify::cipher::#cipher_type>(#encrypted, #key, #nonce)
+        }
+    }
+
+    /// `encrypt_bytes!`, an expression of type `Vec<u8>`.
+    pub fn decrypt_bytes(&self) -> TokenStream {
+        let Self { cipher_type, encrypted, key, nonce } = self;
+        quote! {
+            cryptify::decrypt_bytes::<cryptify::cipher::#cipher_type>(#encrypted, #key, #nonce)
+        }
+    }
+
+    /// `with_decrypted!`, passing the decrypted string to the closure `body`.
+    pub fn with_decrypted(&self, body: TokenStream) -> TokenStream {
+        let Self { cipher_type, encrypted, key, nonce } = self;
+        //copy the ciphertext into a stack array so decryption never touches the heap
+        quote! {
+            cryptify::with_decrypted::<cryptify::cipher::#cipher_type, _>(&mut { *#encrypted }, #key, #nonce, #body)
+        }
+    }
+
+    /// `encrypt_static!` for a string literal, an expression of type `&'static str`.
+    pub fn decrypt_static_str(&self) -> TokenStream {
+        self.decrypt_static(quote!(decrypt_string), quote!(String))
+    }
+
+    /// `encrypt_static!` for a byte string literal, an expression of type `&'static [u8]`.
+    pub fn decrypt_static_bytes(&self) -> TokenStream {
+        self.decrypt_static(quote!(decrypt_bytes), quote!(Vec<u8>))
+    }
+
+    fn decrypt_static(&self, decrypt_fn: TokenStream, target: TokenStream) -> TokenStream {
+        let Self { cipher_type, encrypted, key, nonce } = self;
+        quote! {
+            {
+                static DECRYPTED: cryptify::Lazy<#target> = cryptify::Lazy::new(|| {
+                    cryptify::#decrypt_fn::<cryptify::cipher::#cipher_type>(#encrypted, #key, #nonce)
+                });
+                &*DECRYPTED
+            }
+        }
+    }
+}
+
+/// `encrypt_int!`, the integer `value` written with `suffix` hidden behind `(a - b) ^ k`, or `None` if
+/// `suffix` isn't one of an integer type or `value` doesn't fit it, like the `128` of `-128i8`.
+pub fn encrypt_int<R: Rng + ?Sized>(value: u128, suffix: &str, rng: &mut R) -> Option<TokenStream> {
+    let bits = int_literal_bits(suffix, value)?;
+    let (a, b, k) = encode_int(value, bits, rng);
+    //generated parts belong to the expansion, not the user's literal
+    let [a, b, k] = [a, b, k].map(|part| format!("{}{}", part, suffix).parse::<Literal>().unwrap());
+    Some(quote! {
+        ((std::hint::black_box(#a) - std::hint::black_box(#b)) ^ std::hint::black_box(#k))
+    })
+}
+
+/// Number of low bits the encoded parts may use without overflowing the literal's type, `None` if
+/// `value` itself doesn't fit in them.
+pub fn int_literal_bits(suffix: &str, value: u128) -> Option<u32> {
+    let value_bits = 128 - value.leading_zeros();
+    let bits = match suffix {
+        "u8" => 8,
+        "i8" => 7,
+        "u16" => 16,
+        "i16" => 15,
+        "u32" => 32,
+        "i32" => 31,
+        "u64" => 64,
+        "i64" => 63,
+        "u128" => 128,
+        "i128" => 127,
+        //assume the smallest pointer width in use, unless the value itself needs a 64-bit one
+        "usize" => value_bits.max(32),
+        "isize" => value_bits.max(31),
+        //an unsuffixed literal fits its inferred type, so does anything with the same bit length (7 covers i8)
+        "" => value_bits.max(7),
+        _ => {
+            return None;
+        }
+    };
+    let max_bits = match suffix {
+        "usize" => 64,
+        "isize" => 63,
+        _ => 128,
+    };
+    (value_bits <= bits && bits <= max_bits).then_some(bits)
+}
+
+/// Splits `value` into `(a, b, k)` with `(a - b) ^ k == value`, all below `2^bits` and `a >= b`, or
+/// below `2^n` for the `n` bits `value` needs if it has more.
+pub fn encode_int<R: Rng + ?Sized>(value: u128, bits: u32, rng: &mut R) -> (u128, u128, u128) {
+    let bits = bits.max(128 - value.leading_zeros());
+    let mask = if bits >= 128 { u128::MAX } else { (1u128 << bits) - 1 };
+    let k = rng.gen::<u128>() & mask;
+    let masked = value ^ k;
+    let b = rng.gen_range(0..=mask - masked);
+    (masked + b, b, k)
+}
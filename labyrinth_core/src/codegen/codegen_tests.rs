@@ -0,0 +1,214 @@
+#[cfg(test)]
+use super::*;
+use proptest::prelude::*;
+use syn::{ Expr, ExprCall, ExprLit, ExprPath, GenericArgument, Lit, PathArguments, Type };
+
+fn encrypt_string(original: &str, cipher: CipherKind, secret: &[u8], rng: &mut impl rand::Rng) -> TokenStream {
+    EncryptedLiteral::new(original.as_bytes(), Span::call_site(), cipher, secret, rng).decrypt_string()
+}
+
+//pull the cipher, ciphertext, key and nonce back out of an expansion
+fn parse_expansion(tokens: TokenStream) -> (CipherKind, Vec<u8>, Vec<u8>, Vec<u8>) {
+    let call: ExprCall = syn::parse2(tokens).unwrap();
+    let cipher = match &*call.func {
+        Expr::Path(ExprPath { path, .. }) => {
+            let PathArguments::AngleBracketed(generics) = &path.segments.last().unwrap().arguments else {
+                panic!("expected cipher type argument");
+            };
+            let GenericArgument::Type(Type::Path(cipher_path)) = &generics.args[0] else {
+                panic!("expected cipher type argument");
+            };
+            let type_name = cipher_path.path.segments.last().unwrap().ident.to_string();
+            CipherKind::ALL.into_iter().find(|kind| kind.type_name() == type_name).unwrap()
+        }
+        _ => panic!("expected call to cryptify::decrypt_string"),
+    };
+    let mut byte_strings = call.args.into_iter().map(|arg| match arg {
+        Expr::Lit(ExprLit { lit: Lit::ByteStr(lit), .. }) => lit.value(),
+        other => panic!("expected byte string argument, got {}", quote!(#other)),
+    });
+    let encrypted = byte_strings.next().unwrap();
+    let key = byte_strings.next().unwrap();
+    let nonce = byte_strings.next().unwrap();
+    (cipher, encrypted, key, nonce)
+}
+
+fn decrypt_expansion(tokens: TokenStream) -> String {
+    let (cipher, mut data, key, nonce) = parse_expansion(tokens);
+    cipher.apply_keystream(&key, &nonce, &mut data);
+    String::from_utf8(data).unwrap()
+}
+
+#[test]
+fn test_encrypt_and_decrypt() {
+    let test_strings = ["Hello", "World", "1234", "!@#$%^&*()"];
+
+    for &original in &test_strings {
+        let tokens = encrypt_string(original, CipherKind::Xor, DEFAULT_SECRET.as_bytes(), &mut rand::thread_rng());
+        assert_eq!(original, decrypt_expansion(tokens), "Failed for string: {}", original);
+    }
+}
+
+#[test]
+fn test_encrypt_and_decrypt_customkey() {
+    //set key
+    env::set_var("CRYPTIFY_KEY", "testkey");
+    let key = secret();
+    assert_eq!(key, "testkey");
+    assert_ne!(derive_key(key.as_bytes(), 42, 8), derive_key(DEFAULT_SECRET.as_bytes(), 42, 8));
+
+    let test_strings = ["Hello", "World", "1234", "!@#$%^&*()"];
+    for &original in &test_strings {
+        let tokens = encrypt_string(original, CipherKind::Xor, key.as_bytes(), &mut rand::thread_rng());
+        assert_eq!(original, decrypt_expansion(tokens), "Failed for string: {}", original);
+    }
+    env::remove_var("CRYPTIFY_KEY");
+    assert_eq!(secret(), DEFAULT_SECRET);
+}
+
+#[test]
+fn test_encrypt_non_ascii_with_every_cipher() {
+    let test_strings = ["héllo wörld", "日本語のエラー", "🦀🔒", "Ω≈ç√∫ mixed ascii"];
+
+    for cipher in CipherKind::ALL {
+        for &original in &test_strings {
+            let tokens = encrypt_string(original, cipher, b"xnasff3wcedj", &mut rand::thread_rng());
+            let (parsed_cipher, encrypted, key, nonce) = parse_expansion(tokens.clone());
+            assert_eq!(parsed_cipher, cipher);
+            assert_eq!(key.len(), cipher.key_len());
+            assert_eq!(nonce.len(), cipher.nonce_len());
+            assert_ne!(encrypted, original.as_bytes());
+            assert_eq!(original, decrypt_expansion(tokens));
+        }
+    }
+}
+
+#[test]
+fn test_same_literal_encrypts_differently() {
+    let literal = "the same secret literal";
+    let mut rng = rand::thread_rng();
+
+    for cipher in CipherKind::ALL {
+        let first = encrypt_string(literal, cipher, b"xnasff3wcedj", &mut rng);
+        let second = encrypt_string(literal, cipher, b"xnasff3wcedj", &mut rng);
+
+        assert_ne!(parse_expansion(first.clone()).1, parse_expansion(second.clone()).1);
+        assert_eq!(decrypt_expansion(first), literal);
+        assert_eq!(decrypt_expansion(second), literal);
+    }
+}
+
+#[test]
+fn test_every_literal_gets_its_own_key() {
+    let mut rng = rand::thread_rng();
+
+    for cipher in CipherKind::ALL {
+        let first = EncryptedLiteral::new(b"per literal", Span::call_site(), cipher, b"xnasff3wcedj", &mut rng);
+        let second = EncryptedLiteral::new(b"per literal", Span::call_site(), cipher, b"xnasff3wcedj", &mut rng);
+
+        assert_ne!(first.key.to_string(), second.key.to_string(), "{:?} reused a key", cipher);
+        assert_ne!(first.encrypted.to_string(), second.encrypted.to_string(), "{:?} reused a ciphertext", cipher);
+    }
+}
+
+#[test]
+fn test_equal_prefixes_do_not_share_ciphertext() {
+    let mut rng = rand::thread_rng();
+
+    let (_, short_encrypted, _, _) = parse_expansion(encrypt_string("password", CipherKind::Xor, b"xnasff3wcedj", &mut rng));
+    let (_, long_encrypted, _, _) = parse_expansion(encrypt_string("password123", CipherKind::Xor, b"xnasff3wcedj", &mut rng));
+
+    assert_ne!(short_encrypted[..], long_encrypted[..short_encrypted.len()]);
+}
+
+#[test]
+fn test_decrypt_calls() {
+    let encrypted = EncryptedLiteral::new(b"\x00magic", Span::call_site(), CipherKind::Rc4, b"xnasff3wcedj", &mut rand::thread_rng());
+    for (tokens, function) in [
+        (encrypted.decrypt_secret(), "decrypt_secret"),
+        (encrypted.decrypt_bytes(), "decrypt_bytes"),
+        (encrypted.with_decrypted(quote!(|s| s.len())), "with_decrypted"),
+        (encrypted.decrypt_static_str(), "decrypt_string"),
+        (encrypted.decrypt_static_bytes(), "decrypt_bytes"),
+    ] {
+        assert!(syn::parse2::<Expr>(tokens.clone()).is_ok(), "{}", tokens);
+        let expected = format!("cryptify :: {} :: < cryptify :: cipher :: Rc4", function);
+        assert!(tokens.to_string().contains(&expected), "{}", tokens);
+    }
+}
+
+#[test]
+fn test_encrypt_int() {
+    let tokens = encrypt_int(200, "u8", &mut rand::thread_rng()).unwrap();
+    assert!(syn::parse2::<Expr>(tokens.clone()).is_ok(), "{}", tokens);
+    assert_eq!(tokens.to_string().matches("u8").count(), 3, "{}", tokens);
+    assert!(encrypt_int(5, "f32", &mut rand::thread_rng()).is_none());
+}
+
+#[test]
+fn test_encrypt_int_out_of_range() {
+    let mut rng = rand::thread_rng();
+    //the literals of `-128i8` and `i32::MIN` are one past the positive range of their type
+    assert!(encrypt_int(128, "i8", &mut rng).is_none());
+    assert!(encrypt_int(2147483648, "i32", &mut rng).is_none());
+    assert!(encrypt_int(256, "u8", &mut rng).is_none());
+
+    //a usize literal above u32::MAX only builds for 64-bit targets anyway
+    let tokens = encrypt_int(5000000000, "usize", &mut rng).unwrap();
+    assert!(syn::parse2::<Expr>(tokens.clone()).is_ok(), "{}", tokens);
+    assert_eq!(int_literal_bits("usize", 5000000000), Some(33));
+    assert_eq!(int_literal_bits("usize", 5), Some(32));
+    assert_eq!(int_literal_bits("usize", u64::MAX as u128 + 1), None);
+
+    //parts never overflow, even for a value wider than the bits asked for
+    for _ in 0..1000 {
+        let (a, b, k) = encode_int(128, 7, &mut rng);
+        assert_eq!((a - b) ^ k, 128);
+    }
+}
+
+#[test]
+fn test_int_literal_bits() {
+    assert_eq!(int_literal_bits("u8", 255), Some(8));
+    assert_eq!(int_literal_bits("i64", 5), Some(63));
+    assert_eq!(int_literal_bits("", 5), Some(7));
+    assert_eq!(int_literal_bits("", 300), Some(9));
+    assert_eq!(int_literal_bits("f32", 5), None);
+    assert_eq!(int_literal_bits("i8", 128), None);
+}
+
+#[test]
+fn test_encode_int_fits_type() {
+    let mut rng = rand::thread_rng();
+    for _ in 0..1000 {
+        let (a, b, k) = encode_int(127, 7, &mut rng);
+        assert!(a <= 127 && b <= 127 && k <= 127, "{} {} {} overflow i8", a, b, k);
+        assert_eq!((a - b) ^ k, 127);
+    }
+}
+
+proptest! {
+    #[test]
+    fn prop_encode_int_round_trips(value in any::<u128>(), suffix in prop::sample::select(vec!["", "u8", "u16", "u32", "u64", "u128"])) {
+        //cut the value down to what its type holds, unsuffixed literals hold anything
+        let width = int_literal_bits(suffix, 0).unwrap();
+        let value = if suffix.is_empty() || width >= 128 { value } else { value & ((1u128 << width) - 1) };
+        let bits = int_literal_bits(suffix, value).unwrap();
+        let (a, b, k) = encode_int(value, bits, &mut rand::thread_rng());
+        prop_assert!(a >= b);
+        if bits < 128 {
+            prop_assert!(a < (1u128 << bits));
+        }
+        prop_assert_eq!((a - b) ^ k, value);
+    }
+
+    #[test]
+    fn prop_encrypted_literal_tokens_round_trip(original in any::<String>(), secret in "[ -~]{1,32}") {
+        //the emitted expansion must reparse to exactly the encrypted bytes, key and nonce
+        for cipher in CipherKind::ALL {
+            let tokens = encrypt_string(&original, cipher, secret.as_bytes(), &mut rand::thread_rng());
+            let reparsed = syn::parse_str(&tokens.to_string()).unwrap();
+            prop_assert_eq!(&original, &decrypt_expansion(reparsed));
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! Junk code that `flow_stmt!` expands to and `rust-obfuscator` injects, a block that looks like real work
+//! (a hashing loop, a table lookup, a string being built, some bit twiddling or a recursive helper) whose
+//! result is thrown away.
+//!
+//! The template, the names of its locals, its constants and the order of its statements are all picked
+//! at random, so there is no name or shape every expansion shares. Values go through `std::hint::black_box`
+//! so the optimizer can't drop the work, and all arithmetic wraps so the block never panics.
+use proc_macro2::{ Ident, Literal, Span, TokenStream };
+use quote::quote;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+#[cfg(test)]
+mod junk_tests;
+
+//names real code gives to locals playing each role, no two lists share a word
+const ACCUMULATORS: &[&str] = &["acc", "hash", "digest", "sum", "crc", "checksum", "mix", "total"];
+const COUNTERS: &[&str] = &["count", "cursor", "offset", "pos", "remaining", "round", "steps"];
+const INDICES: &[&str] = &["i", "j", "idx", "index", "slot", "k"];
+const BOUNDS: &[&str] = &["limit", "bound", "end", "max", "cap", "threshold"];
+const STEPS: &[&str] = &["step", "stride", "delta", "inc", "advance"];
+const BUFFERS: &[&str] = &["table", "lut", "sbox", "lookup", "data", "bytes", "block"];
+const STRINGS: &[&str] = &["name", "label", "path", "text", "key", "buf"];
+const VALUES: &[&str] = &["x", "state", "word", "bits", "seed", "value", "mask"];
+const HELPERS: &[&str] = &["fold", "reduce", "walk", "scramble", "permute", "churn", "digest_step"];
+const SUFFIXES: &[&str] = &["tmp", "next", "prev", "inner", "local", "val"];
+
+/// Generates a block of junk code, a statement that can stand anywhere a statement can
+/// outside of const contexts.
+pub fn junk_block<R: Rng + ?Sized>(rng: &mut R) -> TokenStream {
+    let stmts = junk_stmts(rng);
+    quote! { { #stmts } }
+}
+
+/// Generates the statements of a block of junk code, for an arm or block of its own.
+pub fn junk_stmts<R: Rng + ?Sized>(rng: &mut R) -> TokenStream {
+    match rng.gen_range(0..6) {
+        0 => counter_loop(rng),
+        1 => hashing_loop(rng),
+        2 => table_lookup(rng),
+        3 => string_builder(rng),
+        4 => bit_twiddling(rng),
+        _ => recursive_helper(rng),
+    }
+}
+
+//a name for a local from `words`, sometimes with a number or a suffix
+fn name<R: Rng + ?Sized>(rng: &mut R, words: &[&str]) -> Ident {
+    let word = words.choose(rng).unwrap();
+    let name = match rng.gen_range(0..4) {
+        0 => format!("{}{}", word, rng.gen_range(0..10)),
+        1 => format!("{}_{}", word, SUFFIXES.choose(rng).unwrap()),
+        _ => word.to_string(),
+    };
+    Ident::new(&name, Span::call_site())
+}
+
+//`u32` or `u64`, with its width in bits
+fn int_type<R: Rng + ?Sized>(rng: &mut R) -> (TokenStream, u32) {
+    if rng.gen_bool(0.5) { (quote!(u32), 32) } else { (quote!(u64), 64) }
+}
+
+//a literal that fits either of the types `int_type` picks
+fn int<R: Rng + ?Sized>(rng: &mut R) -> Literal {
+    Literal::u64_unsuffixed(rng.gen_range(1..=u32::MAX as u64))
+}
+
+fn usize_in<R: Rng + ?Sized>(rng: &mut R, range: std::ops::RangeInclusive<usize>) -> Literal {
+    Literal::usize_unsuffixed(rng.gen_range(range))
+}
+
+//a counter stepping towards a bound, or a collatz sequence running down to it
+fn counter_loop<R: Rng + ?Sized>(rng: &mut R) -> TokenStream {
+    let counter = name(rng, COUNTERS);
+    let bound = name(rng, BOUNDS);
+    let (ty, _) = int_type(rng);
+    let limit = rng.gen_range(50..=100);
+    if rng.gen_bool(0.25) {
+        //sequences starting below 2^16 stay far from overflowing a `u64` on their way down
+        let start = Literal::u64_unsuffixed(rng.gen_range(limit..=0xffff));
+        let limit = Literal::u64_unsuffixed(limit);
+        return quote! {
+            let mut #counter: u64 = std::hint::black_box(#start);
+            let #bound: u64 = std::hint::black_box(#limit);
+            while #counter > #bound {
+                #counter = if #counter & 1 == 0 { #counter / 2 } else { #counter.wrapping_mul(3).wrapping_add(1) };
+            }
+            std::hint::black_box(#counter);
+        };
+    }
+    let start = Literal::u64_unsuffixed(rng.gen_range(1..=10));
+    let limit = Literal::u64_unsuffixed(limit);
+    let step = name(rng, STEPS);
+    let increment = Literal::u64_unsuffixed(rng.gen_range(1..=5));
+    let mut decls = vec![
+        quote! { let mut #counter: #ty = std::hint::black_box(#start); },
+        quote! { let #step: #ty = std::hint::black_box(#increment); },
+        quote! { let #bound: #ty = std::hint::black_box(#limit); },
+    ];
+    let mut result = quote! { #counter };
+    //an extra value that only feeds the result
+    if rng.gen_bool(0.5) {
+        let extra = name(rng, VALUES);
+        let value = int(rng);
+        decls.push(quote! { let #extra: #ty = std::hint::black_box(#value); });
+        result = quote! { #counter ^ #extra };
+    }
+    decls.shuffle(rng);
+    let body = if rng.gen_bool(0.5) {
+        quote! {
+            loop {
+                if std::hint::black_box(#counter) > std::hint::black_box(#bound) {
+                    break;
+                }
+                #counter = std::hint::black_box(std::hint::black_box(#counter).wrapping_add(std::hint::black_box(#step)));
+            }
+        }
+    } else {
+        quote! {
+            while std::hint::black_box(#counter) <= #bound {
+                #counter = std::hint::black_box(#counter.wrapping_add(#step));
+            }
+        }
+    };
+    quote! {
+        #(#decls)*
+        #body
+        std::hint::black_box(#result);
+    }
+}
+
+//a hash over a few bytes, in the style of FNV or a rotate-and-xor checksum
+fn hashing_loop<R: Rng + ?Sized>(rng: &mut R) -> TokenStream {
+    let data = name(rng, BUFFERS);
+    let hash = name(rng, ACCUMULATORS);
+    let byte = Ident::new(["b", "byte", "c", "ch"].choose(rng).unwrap(), Span::call_site());
+    let (ty, bits) = int_type(rng);
+    let bytes: Vec<Literal> = (0..rng.gen_range(4..=12)).map(|_| Literal::u8_unsuffixed(rng.gen())).collect();
+    let len = Literal::usize_unsuffixed(bytes.len());
+    let offset = int(rng);
+    let prime = Literal::u64_unsuffixed(rng.gen_range(1..=u32::MAX as u64) | 1);
+    let rotation = Literal::u32_unsuffixed(rng.gen_range(1..bits));
+    let mix = match rng.gen_range(0..3) {
+        0 => quote! { #hash ^= #byte as #ty; #hash = #hash.wrapping_mul(#prime); },
+        1 => quote! { #hash = #hash.rotate_left(#rotation) ^ (#byte as #ty); },
+        _ => quote! { #hash = #hash.wrapping_add(#byte as #ty).wrapping_mul(#prime); },
+    };
+    let iter = if rng.gen_bool(0.5) { quote! { #byte in #data } } else { quote! { &#byte in #data.iter() } };
+    quote! {
+        let #data: [u8; #len] = std::hint::black_box([#(#bytes),*]);
+        let mut #hash: #ty = std::hint::black_box(#offset);
+        for #iter {
+            #mix
+        }
+        std::hint::black_box(#hash);
+    }
+}
+
+//lookups into a table of constants, each one picking the next index
+fn table_lookup<R: Rng + ?Sized>(rng: &mut R) -> TokenStream {
+    let table = name(rng, BUFFERS);
+    let index = name(rng, INDICES);
+    let acc = name(rng, ACCUMULATORS);
+    let (ty, _) = int_type(rng);
+    let entries: Vec<Literal> = (0..rng.gen_range(4..=8)).map(|_| int(rng)).collect();
+    let len = Literal::usize_unsuffixed(entries.len());
+    let start = usize_in(rng, 0..=64);
+    let seed = int(rng);
+    let rounds = usize_in(rng, 2..=16);
+    let combine = if rng.gen_bool(0.5) {
+        quote! { #acc = #acc.wrapping_add(#table[#index]); }
+    } else {
+        quote! { #acc ^= #table[#index].rotate_left(#index as u32); }
+    };
+    let mut decls = vec![
+        quote! { let #table: [#ty; #len] = [#(#entries),*]; },
+        quote! { let mut #acc: #ty = std::hint::black_box(#seed); },
+    ];
+    decls.shuffle(rng);
+    quote! {
+        #(#decls)*
+        let mut #index = std::hint::black_box(#start) % #table.len();
+        for _ in 0..std::hint::black_box(#rounds) {
+            #combine
+            #index = (#acc as usize ^ #index) % #table.len();
+        }
+        std::hint::black_box(#acc);
+    }
+}
+
+//a string pushed together a character at a time
+fn string_builder<R: Rng + ?Sized>(rng: &mut R) -> TokenStream {
+    let text = name(rng, STRINGS);
+    let index = name(rng, INDICES);
+    let capacity = usize_in(rng, 8..=64);
+    let count = usize_in(rng, 3..=16);
+    let push = match rng.gen_range(0..3) {
+        0 => {
+            let first = Literal::byte_character(*b"abcdefghijklmnopqrstuvwxyz".choose(rng).unwrap());
+            quote! { #text.push(char::from(#first + (#index % 26) as u8)); }
+        }
+        1 => quote! { #text.push(char::from_digit((#index % 10) as u32, 10).unwrap_or('0')); },
+        _ => {
+            let separator = Literal::character(*['-', '_', '.', '/', ':'].choose(rng).unwrap());
+            quote! { if #index % 3 == 0 { #text.push(#separator); } else { #text.push(char::from(b'a' + (#index % 26) as u8)); } }
+        }
+    };
+    let result = if rng.gen_bool(0.5) {
+        quote! { #text.len() }
+    } else {
+        quote! { #text.bytes().fold(0u32, |acc, byte| acc.wrapping_mul(31).wrapping_add(byte as u32)) }
+    };
+    quote! {
+        let mut #text = std::string::String::with_capacity(#capacity);
+        for #index in 0..std::hint::black_box(#count) {
+            #push
+        }
+        std::hint::black_box(#result);
+    }
+}
+
+//a few shifts, rotations and multiplications of one value
+fn bit_twiddling<R: Rng + ?Sized>(rng: &mut R) -> TokenStream {
+    let value = name(rng, VALUES);
+    let (ty, bits) = int_type(rng);
+    let start = int(rng);
+    let ops: Vec<TokenStream> = (0..rng.gen_range(2..=5))
+        .map(|_| {
+            let shift = Literal::u32_unsuffixed(rng.gen_range(1..bits));
+            let constant = Literal::u64_unsuffixed(rng.gen_range(1..=u32::MAX as u64) | 1);
+            match rng.gen_range(0..7) {
+                0 => quote! { #value ^= #value >> #shift; },
+                1 => quote! { #value ^= #value << #shift; },
+                2 => quote! { #value = #value.wrapping_mul(#constant); },
+                3 => quote! { #value = #value.rotate_left(#shift); },
+                4 => quote! { #value = #value.swap_bytes(); },
+                5 => quote! { #value = #value.wrapping_add(#constant); },
+                _ => quote! { #value = !#value; },
+            }
+        })
+        .collect();
+    let result = if rng.gen_bool(0.5) { quote! { #value } } else { quote! { #value.count_ones() } };
+    quote! {
+        let mut #value: #ty = std::hint::black_box(#start);
+        #(#ops)*
+        std::hint::black_box(#result);
+    }
+}
+
+//a helper folding a value over a few levels of recursion
+fn recursive_helper<R: Rng + ?Sized>(rng: &mut R) -> TokenStream {
+    let helper = name(rng, HELPERS);
+    let depth = name(rng, COUNTERS);
+    let acc = name(rng, ACCUMULATORS);
+    let (ty, bits) = int_type(rng);
+    let rotation = Literal::u32_unsuffixed(rng.gen_range(1..bits));
+    let multiplier = Literal::u64_unsuffixed(rng.gen_range(1..=u32::MAX as u64) | 1);
+    let step = match rng.gen_range(0..3) {
+        0 => quote! { #acc.rotate_left(#rotation) ^ #depth },
+        1 => quote! { #acc.wrapping_mul(#multiplier).wrapping_add(#depth) },
+        _ => quote! { (#acc ^ #depth).rotate_right(#rotation) },
+    };
+    let levels = Literal::u64_unsuffixed(rng.gen_range(3..=24));
+    let seed = int(rng);
+    quote! {
+        fn #helper(#depth: #ty, #acc: #ty) -> #ty {
+            if #depth == 0 { #acc } else { #helper(#depth - 1, #step) }
+        }
+        std::hint::black_box(#helper(std::hint::black_box(#levels), std::hint::black_box(#seed)));
+    }
+}
@@ -0,0 +1,47 @@
+#[cfg(test)]
+use super::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::BTreeSet;
+
+fn idents(tokens: TokenStream, found: &mut BTreeSet<String>) {
+    for token in tokens {
+        match token {
+            proc_macro2::TokenTree::Ident(ident) => {
+                found.insert(ident.to_string());
+            }
+            proc_macro2::TokenTree::Group(group) => idents(group.stream(), found),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn test_junk_block_parses() {
+    for seed in 0..256 {
+        let block = junk_block(&mut StdRng::seed_from_u64(seed));
+        assert!(syn::parse2::<syn::Block>(block.clone()).is_ok(), "seed {}: {}", seed, block);
+    }
+}
+
+#[test]
+fn test_junk_blocks_differ() {
+    let blocks: Vec<BTreeSet<String>> = (0..64)
+        .map(|seed| {
+            let mut found = BTreeSet::new();
+            idents(junk_block(&mut StdRng::seed_from_u64(seed)), &mut found);
+            found
+        })
+        .collect();
+    //nothing but the path to `black_box` is in every block
+    let shared = blocks.iter().skip(1).fold(blocks[0].clone(), |shared, block| &shared & block);
+    assert_eq!(shared, ["black_box", "hint", "std"].iter().map(|ident| ident.to_string()).collect());
+    let distinct: BTreeSet<String> = (0..64).map(|seed| junk_block(&mut StdRng::seed_from_u64(seed)).to_string()).collect();
+    assert_eq!(distinct.len(), 64);
+}
+
+#[test]
+fn test_junk_block_is_reproducible() {
+    let block = |seed| junk_block(&mut StdRng::seed_from_u64(seed)).to_string();
+    assert_eq!(block(7), block(7));
+}
@@ -0,0 +1,12 @@
+//! `labyrinth_core` crate holds the logic shared by `cryptify` and `labyrinth_macros`. NOT MEANT TO BE USED STANDALONE.
+//!
+//! Anything the procedural macros do at compile time that has to be undone at runtime lives here,
+//! so both sides are guaranteed to agree, as does the seed derivation shared with `rust-obfuscator`.
+//! With the `codegen` feature it also generates the code the macros expand to, which `rust-obfuscator`
+//! writes out itself when macros are disabled, so both produce the same code.
+pub mod cipher;
+#[cfg(feature = "codegen")]
+pub mod codegen;
+#[cfg(feature = "codegen")]
+pub mod junk;
+pub mod seed;
@@ -0,0 +1,36 @@
+//! Seeds that make every random choice of `rust-obfuscator` and the procedural macros reproducible.
+//!
+//! A single build-wide seed is never used directly. Every file, pass and macro call site derives its own
+//! from it, so the output is stable between runs without repeating the same choices everywhere.
+
+#[cfg(test)]
+mod seed_tests;
+
+/// Turns a user supplied seed into a number. Plain integers are used as is, anything else
+/// (a commit hash, a release name) is hashed.
+pub fn parse_seed(seed: &str) -> u64 {
+    seed.trim().parse().unwrap_or_else(|_| fnv1a(seed.as_bytes()))
+}
+
+/// Derives an independent seed for one file, pass or call site, described by `context`.
+pub fn derive_seed(seed: u64, context: &[u8]) -> u64 {
+    let mut state = seed ^ fnv1a(context);
+    splitmix64(&mut state)
+}
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
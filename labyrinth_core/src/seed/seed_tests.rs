@@ -0,0 +1,17 @@
+#[cfg(test)]
+use super::*;
+
+#[test]
+fn test_parse_seed() {
+    assert_eq!(parse_seed("42"), 42);
+    assert_eq!(parse_seed(" 42\n"), 42);
+    assert_eq!(parse_seed("v1.2.0"), parse_seed("v1.2.0"));
+    assert_ne!(parse_seed("v1.2.0"), parse_seed("v1.2.1"));
+}
+
+#[test]
+fn test_derive_seed() {
+    assert_eq!(derive_seed(42, b"src/main.rs"), derive_seed(42, b"src/main.rs"));
+    assert_ne!(derive_seed(42, b"src/main.rs"), derive_seed(42, b"src/lib.rs"));
+    assert_ne!(derive_seed(42, b"src/main.rs"), derive_seed(43, b"src/main.rs"));
+}
@@ -4,133 +4,322 @@
 //! to enhance the security of Rust code by obfuscating strings and control flows.
 use proc_macro::TokenStream;
 use quote::quote;
-use rand::seq::SliceRandom;
-use rand::Rng;
+use rand::{ rngs::StdRng, SeedableRng };
 use std::env;
 use syn::*;
+use labyrinth_core::cipher::CipherKind;
+use labyrinth_core::codegen::{ self, secret, EncryptedLiteral };
+use labyrinth_core::junk::junk_block;
+use labyrinth_core::seed::{ derive_seed, parse_seed };
 
-/// A procedural macro that adds a compile-time randomly generated loop and variables.
+/// A procedural macro that expands to a block of randomly generated junk code, a hashing loop,
+/// a table lookup, a string being built, some bit twiddling or a recursive helper whose result is thrown away.
+/// Every call site gets its own template, names and constants, reproducible between builds when
+/// `CRYPTIFY_SEED` is set at compile time.
 ///
 /// # Note
-/// The unsafe operation is meant to help the dummy loop survive compiler optimizations. only writes to dummy variable
+/// The values go through `std::hint::black_box` to help the junk code survive compiler optimizations.
 ///
 #[proc_macro]
-pub fn flow_stmt(_: TokenStream) -> TokenStream {
-    let mut rng = rand::thread_rng();
-
-    let initial_value = rng.gen_range(1..=10);
-    let increment_value = rng.gen_range(1..=4);
-    let upper_bound = rng.gen_range(50..=100);
-    let add_extra_dummy_variable = rng.gen_bool(0.5);
-
-    let mut statements = vec![
-        quote! { let mut _dummy_counter = std::hint::black_box(#initial_value as i32); },
-        quote! { let _dummy_increment = std::hint::black_box(#increment_value as i32); },
-        quote! { let _dummy_upper_bound = std::hint::black_box(#upper_bound as i32); },
-    ];
-
-    //add random dummy variable occasionally
-    if add_extra_dummy_variable {
-        let extra_dummy_value = rng.gen_range(1..=10);
-        statements.push(
-            quote! { let _extra_dummy_var = std::hint::black_box(#extra_dummy_value as i32); },
-        );
-    }
+pub fn flow_stmt(input: TokenStream) -> TokenStream {
+    let mut rng = call_site_rng("flow_stmt", &input);
+    TokenStream::from(junk_block(&mut rng))
+}
+/// A procedural macro that encrypts a string literal at compile time.
+///
+/// The literal is encrypted as UTF-8 bytes and emitted as a byte string, so any valid
+/// Rust string literal (including non-ASCII text) round-trips through `cryptify::decrypt_string`.
+/// Every call site gets a fresh random key (derived from the build-wide secret read from
+/// `CRYPTIFY_KEY` at compile time) and nonce. Only the derived key is embedded in the expansion,
+/// so decryption does not depend on the runtime environment and equal literals never share a keystream.
+/// Setting `CRYPTIFY_SEED` at compile time makes the keys and nonces reproducible between builds, each call site
+/// still derives its own from the seed, its location and its input.
+///
+/// The cipher defaults to the one picked with the `cipher-*` cargo features of `cryptify`
+/// (`xor` if none is enabled) and can be overridden per call site:
+/// `encrypt_string!(cipher = "chacha20", "text")`.
+///
+/// # Parameters
+/// - `input`: The string literal to be encrypted, optionally preceded by `cipher = "<name>",`.
+///
+#[proc_macro]
+pub fn encrypt_string(input: TokenStream) -> TokenStream {
+    let mut rng = call_site_rng("encrypt_string", &input);
+    let input = parse_macro_input!(input as EncryptInput);
 
-    //randomize the order of variable assignments
-    statements.shuffle(&mut rng);
+    let encrypted = EncryptedLiteral::new(
+        input.literal.value().as_bytes(),
+        input.literal.span(),
+        input.cipher.unwrap_or_else(default_cipher),
+        secret().as_bytes(),
+        &mut rng
+    );
 
-    let loop_block = quote! {
-        loop {
-            if std::hint::black_box(_dummy_counter) > std::hint::black_box(_dummy_upper_bound) {
-                break;
+    TokenStream::from(encrypted.decrypt_string())
+}
+
+//parsed `[cipher = "<name>",] "literal"` macro input
+struct EncryptInput<L = LitStr> {
+    cipher: Option<CipherKind>,
+    literal: L,
+}
+
+impl<L: parse::Parse> parse::Parse for EncryptInput<L> {
+    fn parse(input: parse::ParseStream) -> Result<Self> {
+        let mut cipher = None;
+        if input.peek(Ident) && input.peek2(Token![=]) {
+            let name: Ident = input.parse()?;
+            if name != "cipher" {
+                return Err(Error::new(name.span(), "expected `cipher = \"<name>\"`"));
             }
-            //hide what expr evaluates to to stop compiler
-            _dummy_counter = std::hint::black_box(std::hint::black_box(_dummy_counter) + std::hint::black_box(_dummy_increment));
+            input.parse::<Token![=]>()?;
+            let value: LitStr = input.parse()?;
+            cipher = Some(
+                CipherKind::from_name(&value.value()).ok_or_else(|| {
+                    let names: Vec<&str> = CipherKind::ALL.iter().map(|kind| kind.name()).collect();
+                    Error::new(
+                        value.span(),
+                        format!("unknown cipher `{}`, expected one of: {}", value.value(), names.join(", "))
+                    )
+                })?
+            );
+            input.parse::<Token![,]>()?;
         }
-    };
+        let literal = input.parse()?;
+        Ok(Self { cipher, literal })
+    }
+}
 
-    let generated_loop = quote! {
-        {
-            let _is_dummy_145 = true;
-            #(#statements)*
-            #loop_block
+//deterministic per call site when `CRYPTIFY_SEED` is set at compile time, random otherwise.
+//the call site's file, line and column plus the macro input keep different call sites apart
+fn call_site_rng(name: &str, input: &TokenStream) -> StdRng {
+    match env::var("CRYPTIFY_SEED") {
+        Ok(seed) => {
+            let span = proc_macro::Span::call_site();
+            let context = format!("{}:{}:{}:{}!({})", span.file(), span.line(), span.column(), name, input);
+            StdRng::seed_from_u64(derive_seed(parse_seed(&seed), context.as_bytes()))
         }
-    };
+        Err(_) => StdRng::from_entropy(),
+    }
+}
 
-    TokenStream::from(generated_loop)
+//strongest cipher enabled through cargo features
+fn default_cipher() -> CipherKind {
+    if cfg!(feature = "cipher-chacha20") {
+        CipherKind::ChaCha20
+    } else if cfg!(feature = "cipher-aes-ctr") {
+        CipherKind::Aes128Ctr
+    } else if cfg!(feature = "cipher-rc4") {
+        CipherKind::Rc4
+    } else {
+        CipherKind::Xor
+    }
 }
-/// A procedural macro that encrypts a string literal at compile time.
+
+/// A procedural macro that encrypts a string literal at compile time and decrypts it into a `cryptify::SecretStr`.
+///
+/// The guard derefs to `&str` and wipes its buffer when dropped. Accepts the same arguments as `encrypt_string!`.
 ///
 /// # Parameters
-/// - `input`: The string literal to be encrypted.
+/// - `input`: The string literal to be encrypted, optionally preceded by `cipher = "<name>",`.
 ///
 #[proc_macro]
-pub fn encrypt_string(input: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(input as LitStr);
-    let string = input.value();
+pub fn encrypt_string_guarded(input: TokenStream) -> TokenStream {
+    let mut rng = call_site_rng("encrypt_string_guarded", &input);
+    let input = parse_macro_input!(input as EncryptInput);
 
-    //set key to seeded env key or default
-    let key = env::var("CRYPTIFY_KEY").unwrap_or_else(|_| "xnasff3wcedj".to_string());
+    let encrypted = EncryptedLiteral::new(
+        input.literal.value().as_bytes(),
+        input.literal.span(),
+        input.cipher.unwrap_or_else(default_cipher),
+        secret().as_bytes(),
+        &mut rng
+    );
 
-    let encrypted_string = xor_cipher(&string, &key);
+    TokenStream::from(encrypted.decrypt_secret())
+}
 
-    let output = quote! {
-        cryptify::decrypt_string(#encrypted_string)
+/// A procedural macro that decrypts a string literal into a stack buffer only for the duration of a closure.
+///
+/// The buffer is wiped as soon as the closure returns (or unwinds), and the closure's result is passed through.
+///
+/// # Parameters
+/// - `input`: The string literal, optionally preceded by `cipher = "<name>",`, followed by a closure taking `&str`.
+///
+/// # Example
+/// ```ignore
+/// let len = cryptify::with_decrypted!("api-token", |token| send(token));
+/// ```
+#[proc_macro]
+pub fn with_decrypted(input: TokenStream) -> TokenStream {
+    let mut rng = call_site_rng("with_decrypted", &input);
+    let input = parse_macro_input!(input as WithDecryptedInput);
+
+    let encrypted = EncryptedLiteral::new(
+        input.encrypt.literal.value().as_bytes(),
+        input.encrypt.literal.span(),
+        input.encrypt.cipher.unwrap_or_else(default_cipher),
+        secret().as_bytes(),
+        &mut rng
+    );
+    let body = input.body;
+
+    TokenStream::from(encrypted.with_decrypted(quote!(#body)))
+}
+
+/// A procedural macro that encrypts a byte string literal at compile time.
+///
+/// Expands to an expression of type `Vec<u8>`. Accepts the same `cipher` argument as `encrypt_string!`.
+///
+/// # Parameters
+/// - `input`: The byte string literal to be encrypted, optionally preceded by `cipher = "<name>",`.
+///
+#[proc_macro]
+pub fn encrypt_bytes(input: TokenStream) -> TokenStream {
+    let mut rng = call_site_rng("encrypt_bytes", &input);
+    let input = parse_macro_input!(input as EncryptInput<LitByteStr>);
+
+    let encrypted = EncryptedLiteral::new(
+        &input.literal.value(),
+        input.literal.span(),
+        input.cipher.unwrap_or_else(default_cipher),
+        secret().as_bytes(),
+        &mut rng
+    );
+
+    TokenStream::from(encrypted.decrypt_bytes())
+}
+
+/// A procedural macro that encrypts a string or byte string literal at compile time and decrypts it
+/// once, on first use, into a lazily initialized static.
+///
+/// Expands to `&'static str` for string literals and `&'static [u8]` for byte string literals, so it can be
+/// used where a plain literal was required, e.g. values stored in long-lived structs or returned from functions.
+///
+/// # Parameters
+/// - `input`: The literal to be encrypted, optionally preceded by `cipher = "<name>",`.
+///
+#[proc_macro]
+pub fn encrypt_static(input: TokenStream) -> TokenStream {
+    let mut rng = call_site_rng("encrypt_static", &input);
+    let input = parse_macro_input!(input as EncryptInput<Lit>);
+
+    let (plaintext, is_bytes) = match &input.literal {
+        Lit::Str(lit) => (lit.value().into_bytes(), false),
+        Lit::ByteStr(lit) => (lit.value(), true),
+        other => {
+            return TokenStream::from(
+                Error::new(other.span(), "expected a string or byte string literal").to_compile_error()
+            );
+        }
     };
 
+    let encrypted = EncryptedLiteral::new(
+        &plaintext,
+        input.literal.span(),
+        input.cipher.unwrap_or_else(default_cipher),
+        secret().as_bytes(),
+        &mut rng
+    );
+
+    let output = if is_bytes { encrypted.decrypt_static_bytes() } else { encrypted.decrypt_static_str() };
     TokenStream::from(output)
 }
 
-fn xor_cipher(input: &str, key: &str) -> String {
-    input
-        .chars()
-        .zip(key.chars().cycle())
-        .map(|(input_char, key_char)| ((input_char as u8) ^ (key_char as u8)) as char)
-        .collect()
+/// An attribute that tells `rust-obfuscator` to leave an item alone, expands to the item unchanged.
+///
+/// `#[obfuscate(skip)]` turns every pass off for the item and everything inside it,
+/// `#[obfuscate(no_strings)]`, `#[obfuscate(no_ints)]`, `#[obfuscate(no_flow)]` and
+/// `#[obfuscate(rename = false)]` only one of them. The obfuscator strips the attribute from its output.
+///
+/// # Example
+/// ```ignore
+/// #[cryptify::obfuscate(skip)]
+/// #[no_mangle]
+/// pub extern "C" fn exported() {}
+/// ```
+#[proc_macro_attribute]
+pub fn obfuscate(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    item
+}
+
+/// A procedural macro that hides an integer literal behind an opaque arithmetic and XOR decode at runtime.
+///
+/// The literal is split into three random values combined as `(a - b) ^ k`, each passed through
+/// `std::hint::black_box` so the compiler cannot fold them back into the original constant.
+/// Suffixed literals keep their type, unsuffixed ones stay inferred from the surrounding code.
+/// Not usable in const contexts such as array lengths or `const` items.
+///
+/// # Parameters
+/// - `input`: The integer literal to be hidden.
+///
+#[proc_macro]
+pub fn encrypt_int(input: TokenStream) -> TokenStream {
+    let mut rng = call_site_rng("encrypt_int", &input);
+    let input = parse_macro_input!(input as LitInt);
+    let value: u128 = match input.base10_parse() {
+        Ok(value) => value,
+        Err(err) => {
+            return TokenStream::from(err.to_compile_error());
+        }
+    };
+    match codegen::encrypt_int(value, input.suffix(), &mut rng) {
+        Some(output) => TokenStream::from(output),
+        None => TokenStream::from(Error::new(input.span(), "expected an integer literal that fits its type").to_compile_error()),
+    }
+}
+
+//parsed `[cipher = "<name>",] "literal", |s| ...` macro input
+struct WithDecryptedInput {
+    encrypt: EncryptInput,
+    body: Expr,
 }
 
-//for self-contained tests
-#[allow(dead_code)]
-fn decrypt_string(encrypted: &str) -> String {
-    let key = std::env::var("CRYPTIFY_KEY").unwrap_or_else(|_| "xnasff3wcedj".to_string());
-    encrypted
-        .chars()
-        .zip(key.chars().cycle())
-        .map(|(encrypted_char, key_char)| ((encrypted_char as u8) ^ (key_char as u8)) as char)
-        .collect()
+impl parse::Parse for WithDecryptedInput {
+    fn parse(input: parse::ParseStream) -> Result<Self> {
+        let encrypt = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let body = input.parse()?;
+        Ok(Self { encrypt, body })
+    }
 }
 
-//unit tests testing decryption logic
+//unit tests testing macro input parsing, what the macros expand to is tested in labyrinth_core
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_xor_cipher_and_decrypt() {
-        std::env::remove_var("CRYPTIFY_KEY");
-        let key = "xnasff3wcedj";
-        let test_strings = ["Hello", "World", "1234", "!@#$%^&*()"];
-
-        for &original in &test_strings {
-            let encrypted = xor_cipher(original, &key);
-            let decrypted = decrypt_string(&encrypted);
-            assert_eq!(original, decrypted, "Failed for string: {}", original);
-        }
+    fn test_parse_cipher_argument() {
+        let input: EncryptInput = parse_quote!(cipher = "chacha20", "text");
+        assert_eq!(input.cipher, Some(CipherKind::ChaCha20));
+        assert_eq!(input.literal.value(), "text");
+
+        let input: EncryptInput = parse_quote!("text");
+        assert_eq!(input.cipher, None);
+
+        assert!(parse_str::<EncryptInput>(r#"cipher = "des", "text""#).is_err());
+        assert!(parse_str::<EncryptInput>(r#"key = "xor", "text""#).is_err());
     }
+
     #[test]
-    fn test_xor_cipher_and_decrypt_customkey() {
-        //set key
-        std::env::set_var("CRYPTIFY_KEY", "testkey");
-        //test loc from encrypt_string meant to extract key
-        let key = env::var("CRYPTIFY_KEY").unwrap_or_else(|_| "xnasff3wcedj".to_string());
-        assert_eq!(key, "testkey");
-
-        let test_strings = ["Hello", "World", "1234", "!@#$%^&*()"];
-        for &original in &test_strings {
-            let encrypted = xor_cipher(original, &key);
-            let decrypted = decrypt_string(&encrypted);
-            assert_eq!(original, decrypted, "Failed for string: {}", original);
-        }
+    fn test_parse_literal_kinds() {
+        let input: EncryptInput<LitByteStr> = parse_quote!(cipher = "aes-ctr", b"\x00magic");
+        assert_eq!(input.cipher, Some(CipherKind::Aes128Ctr));
+        assert_eq!(input.literal.value(), b"\x00magic");
+
+        assert!(parse_str::<EncryptInput<LitByteStr>>(r#""not bytes""#).is_err());
+        assert!(matches!(parse_str::<EncryptInput<Lit>>(r#"b"bytes""#).unwrap().literal, Lit::ByteStr(_)));
+        assert!(matches!(parse_str::<EncryptInput<Lit>>(r#""text""#).unwrap().literal, Lit::Str(_)));
+    }
+
+    #[test]
+    fn test_parse_with_decrypted_input() {
+        let input: WithDecryptedInput = parse_quote!(cipher = "rc4", "text", |s| s.len());
+        assert_eq!(input.encrypt.cipher, Some(CipherKind::Rc4));
+        assert_eq!(input.encrypt.literal.value(), "text");
+        assert!(matches!(input.body, Expr::Closure(_)));
+
+        assert!(parse_str::<WithDecryptedInput>(r#""text""#).is_err());
     }
 }